@@ -18,7 +18,7 @@ fn main() {
             Some(s) => match &s.rdf {
                 Some(_) => {
                     if let [kegg, eccode] = s.flatten().unwrap()[..] {
-                        Some((kegg.split('/').last(), eccode.split('/').last()))
+                        Some((kegg.split('/').next_back(), eccode.split('/').next_back()))
                     } else {
                         None
                     }
@@ -1,8 +1,8 @@
-use rust_sbml::ModelRaw;
+use rust_sbml::SbmlDocument;
 
 fn main() {
     let file_str = std::fs::read_to_string("examples/EcoliCore.xml").unwrap();
-    let res = ModelRaw::parse(&file_str).unwrap();
-    println!("{:?}", res);
+    let res = SbmlDocument::parse(&file_str).unwrap();
+    println!("{:?}", res.model);
     std::fs::write("ecoli_from_memory.xml", res.to_string().unwrap()).unwrap();
 }
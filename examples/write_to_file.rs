@@ -19,7 +19,7 @@ fn main() {
             </listOfReactions>
          </model>
     </sbml>"#;
-    let res = ModelRaw::parse(&example).unwrap();
+    let res = ModelRaw::parse(example).unwrap();
     println!("{:?}", res);
     std::fs::write("from_memory.xml", res.to_string().unwrap()).unwrap();
 }
@@ -82,7 +82,7 @@ pub enum Bqbiol {
         #[serde(rename = "$unflatten=rdf:Bag", default)]
         rdf_bag: RdfBag,
     },
-    #[serde(rename = "bqbiol:encodes")]
+    #[serde(rename = "bqbiol:hasProperty")]
     HasProperty {
         #[serde(rename = "$unflatten=rdf:Bag", default)]
         rdf_bag: RdfBag,
@@ -147,7 +147,7 @@ pub enum Bqbiol {
         #[serde(rename = "$unflatten=rdf:Bag", default)]
         rdf_bag: RdfBag,
     },
-    #[serde(rename = "bqmodel:encodes")]
+    #[serde(rename = "bqmodel:hasProperty")]
     ModelHasProperty {
         #[serde(rename = "$unflatten=rdf:Bag", default)]
         rdf_bag: RdfBag,
@@ -1,10 +1,11 @@
 // use mathml::MathNode;
 use super::annotation::Annotation;
-use super::mathml::Math;
+use super::mathml::{Base, Cn, Math, MathNode, NumberType};
 use super::UnitSIdRef;
 #[cfg(feature = "default")]
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// A compartment in SBML represents a bounded space in which species are located.
 ///
@@ -193,6 +194,45 @@ pub struct ListOfSpeciesReferences {
     pub species_references: Vec<SpeciesReference>,
 }
 
+/// Species that affects a reaction without being consumed or produced by it,
+/// such as an enzyme or an inhibitor. The kind of modification is given by
+/// its SBO term (e.g., `SBO:0000020` for an inhibitor).
+///
+/// # Example
+///
+/// ```
+/// use quick_xml::de::from_str;
+/// use rust_sbml::Reaction;
+///
+/// let reaction: Reaction = from_str(
+/// "<reaction id='J1' reversible='false'>
+///     <listOfModifiers>
+///         <modifierSpeciesReference species='E' sboTerm='SBO:0000460'/>
+///         <modifierSpeciesReference species='I' sboTerm='SBO:0000020'/>
+///     </listOfModifiers>
+/// </reaction>",
+/// )
+/// .unwrap();
+/// let modifiers = &reaction.list_of_modifiers.modifier_species_references;
+/// assert_eq!(modifiers[1].species, "I");
+/// assert_eq!(modifiers[1].sbo_term.as_deref(), Some("SBO:0000020"));
+/// ```
+#[cfg_attr(feature = "default", pyclass)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifierSpeciesReference {
+    pub species: String,
+    pub sbo_term: Option<String>,
+    pub id: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Deserialize, Serialize)]
+pub struct ListOfModifierSpeciesReferences {
+    #[serde(rename = "modifierSpeciesReference", default)]
+    pub modifier_species_references: Vec<ModifierSpeciesReference>,
+}
+
 /// The [`KineticLaw`] object within a Reaction object can contain a
 /// ListOfLocalParameters object containing the definitions of local parameter
 /// that are only accessible within the scope of that particular reaction.
@@ -207,7 +247,7 @@ pub struct LocalParameter {
 
 #[derive(Debug, PartialEq, Clone, Default, Deserialize, Serialize)]
 pub struct ListOfLocalParameters {
-    #[serde(rename = "localParameter")]
+    #[serde(rename = "localParameter", default)]
     pub local_parameter: Vec<LocalParameter>,
 }
 
@@ -216,13 +256,46 @@ pub struct ListOfLocalParameters {
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KineticLaw {
-    math: Math,
+    pub math: Math,
     id: Option<String>,
     sbo_term: Option<String>,
+    #[serde(default)]
     pub list_of_local_parameters: ListOfLocalParameters,
     pub annotation: Option<Annotation>,
 }
 
+impl KineticLaw {
+    /// The rate law with the values of the local parameters and the lambdas
+    /// of the `functions` inlined, [simplified](MathNode::simplify).
+    /// The local parameters keep their units.
+    pub fn simplified_math(&self, functions: &HashMap<String, FunctionDefinition>) -> MathNode {
+        let bindings: HashMap<String, MathNode> = self
+            .list_of_local_parameters
+            .local_parameter
+            .iter()
+            .filter_map(|parameter| {
+                let value = parameter.value?;
+                Some((
+                    parameter.id.clone(),
+                    MathNode::Cn(Cn {
+                        content: value.to_string(),
+                        sep_content: None,
+                        unit: parameter.units.clone(),
+                        cn_type: NumberType::Real,
+                        base: Base::default(),
+                        definition_url: None,
+                        encoding: None,
+                    }),
+                ))
+            })
+            .collect();
+        self.math
+            .content
+            .substitute(&bindings)
+            .simplify_with_functions(functions)
+    }
+}
+
 /// A reaction in SBML represents any kind of process that can change the
 /// quantity of one or more species in a model. Examples of such processes can
 /// include transformation, transport, molecular interactions, and more.
@@ -268,6 +341,8 @@ pub struct Reaction {
     pub list_of_reactants: ListOfSpeciesReferences,
     #[serde(default)]
     pub list_of_products: ListOfSpeciesReferences,
+    #[serde(default)]
+    pub list_of_modifiers: ListOfModifierSpeciesReferences,
     pub reversible: bool,
     pub compartment: Option<String>,
     pub name: Option<String>,
@@ -275,8 +350,13 @@ pub struct Reaction {
     pub kinetic_law: Option<KineticLaw>,
     #[serde(rename = "fbc:lowerFluxBound")]
     pub lower_bound: Option<String>,
-    #[serde(rename = "fbc:lowerUpperBound")]
+    #[serde(rename = "fbc:upperFluxBound")]
     pub upper_bound: Option<String>,
+    #[serde(
+        rename = "fbc:geneProductAssociation",
+        alias = "geneProductAssociation"
+    )]
+    pub gene_product_association: Option<GeneProductAssociation>,
     pub annotation: Option<Annotation>,
 }
 
@@ -416,6 +496,99 @@ pub struct Constraint {
     pub sbo_term: Option<String>,
 }
 
+/// Discontinuous change in the model state, applied when the [`Trigger`]
+/// condition goes from false to true (or after its [`Delay`]).
+///
+/// # Example
+///
+/// ```
+/// use quick_xml::de::from_str;
+/// use rust_sbml::Event;
+///
+/// let event: Event = from_str(r#"
+/// <event id="dose" useValuesFromTriggerTime="true">
+///     <trigger initialValue="false" persistent="true">
+///         <math xmlns="http://www.w3.org/1998/Math/MathML">
+///             <apply><lt/><ci> S1 </ci><cn> 1 </cn></apply>
+///         </math>
+///     </trigger>
+///     <listOfEventAssignments>
+///         <eventAssignment variable="S1">
+///             <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 5 </cn></math>
+///         </eventAssignment>
+///     </listOfEventAssignments>
+/// </event>"#).unwrap();
+///
+/// assert!(!event.trigger.as_ref().unwrap().initial_value);
+/// assert!(event.delay.is_none());
+/// assert_eq!(event.list_of_event_assignments.event_assignments[0].variable, "S1");
+/// ```
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(rename = "event", rename_all = "camelCase")]
+pub struct Event {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub metaid: Option<String>,
+    pub sbo_term: Option<String>,
+    #[serde(default = "default_true")]
+    pub use_values_from_trigger_time: bool,
+    pub trigger: Option<Trigger>,
+    pub priority: Option<Priority>,
+    pub delay: Option<Delay>,
+    #[serde(default)]
+    pub list_of_event_assignments: ListOfEventAssignments,
+}
+
+/// Condition that fires an [`Event`].
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Trigger {
+    /// Value of the trigger before the start of the simulation.
+    #[serde(default = "default_true")]
+    pub initial_value: bool,
+    /// Whether the event is executed even if the trigger turns false
+    /// again before its delay elapses.
+    #[serde(default = "default_true")]
+    pub persistent: bool,
+    pub math: Option<Math>,
+    pub sbo_term: Option<String>,
+}
+
+/// Time between the trigger of an [`Event`] and its execution.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Delay {
+    pub math: Option<Math>,
+    pub sbo_term: Option<String>,
+}
+
+/// Order of execution of simultaneous [`Event`]s (higher first).
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Priority {
+    pub math: Option<Math>,
+    pub sbo_term: Option<String>,
+}
+
+/// New value of `variable` when an [`Event`] is executed.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventAssignment {
+    pub variable: String,
+    pub math: Option<Math>,
+    pub sbo_term: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Default, Clone)]
+pub struct ListOfEventAssignments {
+    #[serde(rename = "eventAssignment", default)]
+    pub event_assignments: Vec<EventAssignment>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
 /// The Flux Balance Constraints package of SBML defines extensions for the
 /// model, including the FBC Objective.
 ///
@@ -454,13 +627,17 @@ pub struct Objective {
     pub sbo_term: Option<String>,
     #[serde(rename = "fbc:type")]
     pub sense: String,
-    #[serde(rename = "listOfFluxObjectives", default)]
+    #[serde(
+        rename = "fbc:listOfFluxObjectives",
+        alias = "listOfFluxObjectives",
+        default
+    )]
     pub list_of_flux_objectives: ListOfFluxObjectives,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Default, Clone)]
 pub struct ListOfFluxObjectives {
-    #[serde(rename = "fluxObjective", default)]
+    #[serde(rename = "fbc:fluxObjective", alias = "fluxObjective", default)]
     pub flux_objectives: Vec<FluxObjective>,
 }
 
@@ -473,3 +650,156 @@ pub struct FluxObjective {
     #[serde(rename = "fbc:reaction")]
     pub reaction: Option<String>,
 }
+
+/// A gene product, defined in the Flux Balance Constraints package, is a
+/// protein or RNA that is referenced by the gene-protein-reaction
+/// [`Association`]s of the reactions.
+///
+/// # Example
+///
+/// ```
+/// use quick_xml::de::from_str;
+/// use rust_sbml::GeneProduct;
+///
+/// let gene_products: Vec<GeneProduct> = from_str(
+/// "<fbc:geneProduct fbc:id=\"G_b0351\" fbc:label=\"b0351\" metaid=\"G_b0351\"/>
+/// <fbc:geneProduct fbc:id=\"G_s0001\" fbc:label=\"s0001\" fbc:name=\"spontaneous\"/>").unwrap();
+///
+/// assert_eq!(gene_products[0].label, "b0351");
+/// assert_eq!(gene_products[1].name.as_deref(), Some("spontaneous"));
+/// ```
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct GeneProduct {
+    #[serde(rename = "fbc:id")]
+    pub id: String,
+    #[serde(rename = "fbc:name")]
+    pub name: Option<String>,
+    #[serde(rename = "fbc:label")]
+    pub label: String,
+    #[serde(rename = "fbc:associatedSpecies")]
+    pub associated_species: Option<String>,
+    pub metaid: Option<String>,
+    #[serde(rename = "sboTerm")]
+    pub sbo_term: Option<String>,
+    pub annotation: Option<Annotation>,
+}
+
+/// Gene-protein-reaction rule of a [`Reaction`], the root of an
+/// [`Association`] tree.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct GeneProductAssociation {
+    #[serde(rename = "fbc:id")]
+    pub id: Option<String>,
+    #[serde(rename = "fbc:name")]
+    pub name: Option<String>,
+    #[serde(rename = "$value")]
+    pub association: Association,
+}
+
+/// Boolean combination of [`GeneProduct`]s that must be present for a
+/// reaction to be catalyzed.
+///
+/// # Example
+///
+/// The reaction is catalyzed by b0351 or by the complex of b1241 and b1849:
+///
+/// ```
+/// use quick_xml::de::from_str;
+/// use rust_sbml::Association;
+///
+/// let association: Association = from_str(
+/// "<fbc:or sboTerm=\"SBO:0000174\">
+///     <fbc:geneProductRef fbc:geneProduct=\"G_b0351\"/>
+///     <fbc:and>
+///         <fbc:geneProductRef fbc:geneProduct=\"G_b1241\"/>
+///         <fbc:geneProductRef fbc:geneProduct=\"G_b1849\"/>
+///     </fbc:and>
+/// </fbc:or>").unwrap();
+///
+/// assert_eq!(association.gene_products(), vec!["G_b0351", "G_b1241", "G_b1849"]);
+/// ```
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub enum Association {
+    #[serde(rename = "fbc:and")]
+    And {
+        #[serde(rename = "fbc:id")]
+        id: Option<String>,
+        #[serde(rename = "fbc:name")]
+        name: Option<String>,
+        metaid: Option<String>,
+        #[serde(rename = "sboTerm")]
+        sbo_term: Option<String>,
+        #[serde(rename = "$value")]
+        children: Vec<Association>,
+    },
+    #[serde(rename = "fbc:or")]
+    Or {
+        #[serde(rename = "fbc:id")]
+        id: Option<String>,
+        #[serde(rename = "fbc:name")]
+        name: Option<String>,
+        metaid: Option<String>,
+        #[serde(rename = "sboTerm")]
+        sbo_term: Option<String>,
+        #[serde(rename = "$value")]
+        children: Vec<Association>,
+    },
+    #[serde(rename = "fbc:geneProductRef")]
+    GeneProductRef {
+        #[serde(rename = "fbc:id")]
+        id: Option<String>,
+        #[serde(rename = "fbc:name")]
+        name: Option<String>,
+        metaid: Option<String>,
+        #[serde(rename = "sboTerm")]
+        sbo_term: Option<String>,
+        #[serde(rename = "fbc:geneProduct")]
+        gene_product: String,
+    },
+}
+
+impl Association {
+    /// Identifiers of the [`GeneProduct`]s referenced in the tree, in document order.
+    pub fn gene_products(&self) -> Vec<&str> {
+        match self {
+            Association::And { children, .. } | Association::Or { children, .. } => children
+                .iter()
+                .flat_map(|child| child.gene_products())
+                .collect(),
+            Association::GeneProductRef { gene_product, .. } => vec![gene_product.as_str()],
+        }
+    }
+
+    /// Evaluate the boolean rule, with `is_active` telling whether each
+    /// gene product is present.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use quick_xml::de::from_str;
+    /// use rust_sbml::Association;
+    ///
+    /// let association: Association = from_str(
+    /// "<fbc:or>
+    ///     <fbc:geneProductRef fbc:geneProduct=\"G_b0351\"/>
+    ///     <fbc:and>
+    ///         <fbc:geneProductRef fbc:geneProduct=\"G_b1241\"/>
+    ///         <fbc:geneProductRef fbc:geneProduct=\"G_b1849\"/>
+    ///     </fbc:and>
+    /// </fbc:or>").unwrap();
+    ///
+    /// assert!(association.evaluate(&|gene| gene != "G_b1241"));
+    /// assert!(!association.evaluate(&|gene| gene == "G_b1849"));
+    /// ```
+    pub fn evaluate(&self, is_active: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Association::And { children, .. } => {
+                children.iter().all(|child| child.evaluate(is_active))
+            }
+            Association::Or { children, .. } => {
+                children.iter().any(|child| child.evaluate(is_active))
+            }
+            Association::GeneProductRef { gene_product, .. } => is_active(gene_product),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Elemental composition of species and mass and charge balance of
+//! reactions, from the `fbc:chemicalFormula` and `fbc:charge` of the FBC
+//! package.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Model, Reaction};
+
+/// Symbols of the chemical elements, by atomic number.
+const ELEMENTS: [&str; 118] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl",
+    "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As",
+    "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb",
+    "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl",
+    "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk",
+    "Cf", "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh",
+    "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+/// SBO terms of the reactions that exchange species with the outside of
+/// the model: exchange, demand and sink reactions.
+const BOUNDARY_SBO_TERMS: [&str; 3] = ["SBO:0000627", "SBO:0000628", "SBO:0000632"];
+
+/// Imbalances smaller than this are rounding errors of the stoichiometry.
+const TOLERANCE: f64 = 1e-9;
+
+/// Elemental composition of a species, parsed from its
+/// `fbc:chemicalFormula`.
+///
+/// Elements are a capital letter followed by lowercase letters and an
+/// optional count, which may be fractional, and groups in parentheses or
+/// brackets may be repeated by a count. Symbols that are not chemical
+/// elements, such as the `R` of a generic side chain, are kept as
+/// [`ChemicalFormula::unknown_groups`] and balanced like elements.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::ChemicalFormula;
+///
+/// let formula: ChemicalFormula = "Ca3(PO4)2R".parse().unwrap();
+/// assert_eq!(formula.elements["O"], 8.);
+/// assert_eq!(formula.elements["Ca"], 3.);
+/// assert_eq!(formula.unknown_groups(), vec!["R"]);
+/// assert!("C6H12O6)".parse::<ChemicalFormula>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChemicalFormula {
+    /// Count of each element, by symbol.
+    pub elements: BTreeMap<String, f64>,
+}
+
+impl ChemicalFormula {
+    /// Symbols that are not chemical elements.
+    pub fn unknown_groups(&self) -> Vec<&str> {
+        self.elements
+            .keys()
+            .map(|symbol| symbol.as_str())
+            .filter(|symbol| !ELEMENTS.contains(symbol))
+            .collect()
+    }
+}
+
+/// Error raised while parsing a [`ChemicalFormula`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChemicalFormulaError {
+    /// Byte offset of the offending character in the formula.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ChemicalFormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ChemicalFormulaError {}
+
+impl FromStr for ChemicalFormula {
+    type Err = ChemicalFormulaError;
+
+    fn from_str(formula: &str) -> Result<Self, Self::Err> {
+        let bytes = formula.as_bytes();
+        let error = |position: usize, message: &str| ChemicalFormulaError {
+            position,
+            message: message.to_string(),
+        };
+        // counts of the open groups, the whole formula first
+        let mut groups: Vec<(usize, u8, BTreeMap<String, f64>)> = vec![(0, 0, BTreeMap::new())];
+        let mut i = 0;
+        while i < bytes.len() {
+            let start = i;
+            match bytes[i] {
+                b'(' | b'[' => {
+                    let close = if bytes[i] == b'(' { b')' } else { b']' };
+                    groups.push((i, close, BTreeMap::new()));
+                    i += 1;
+                }
+                c @ (b')' | b']') => {
+                    let (_, close, group) = groups.pop().expect("the whole formula");
+                    if groups.is_empty() || close != c {
+                        return Err(error(i, "unmatched closing bracket"));
+                    }
+                    i += 1;
+                    let (count, end) = count(bytes, i).ok_or_else(|| error(i, "bad count"))?;
+                    i = end;
+                    let parent = &mut groups.last_mut().expect("the whole formula").2;
+                    for (symbol, n) in group {
+                        *parent.entry(symbol).or_insert(0.) += n * count;
+                    }
+                }
+                c if c.is_ascii_uppercase() => {
+                    i += 1;
+                    while i < bytes.len() && bytes[i].is_ascii_lowercase() {
+                        i += 1;
+                    }
+                    let symbol = &formula[start..i];
+                    let (count, end) = count(bytes, i).ok_or_else(|| error(i, "bad count"))?;
+                    i = end;
+                    let group = &mut groups.last_mut().expect("the whole formula").2;
+                    *group.entry(symbol.to_string()).or_insert(0.) += count;
+                }
+                c if c.is_ascii_whitespace() => i += 1,
+                _ => return Err(error(i, "unexpected character")),
+            }
+        }
+        let (position, _, elements) = groups.pop().expect("the whole formula");
+        if !groups.is_empty() {
+            return Err(error(position, "unclosed bracket"));
+        }
+        Ok(ChemicalFormula {
+            elements: elements.into_iter().filter(|(_, n)| *n != 0.).collect(),
+        })
+    }
+}
+
+/// The count that starts at `start`, 1 if there is none, and the position
+/// after it.
+fn count(bytes: &[u8], start: usize) -> Option<(f64, usize)> {
+    let mut end = start;
+    while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+        end += 1;
+    }
+    if end == start {
+        return Some((1., end));
+    }
+    let count = std::str::from_utf8(&bytes[start..end]).ok()?.parse().ok()?;
+    Some((count, end))
+}
+
+/// Elements and charge that a reaction creates: those of its products minus
+/// those of its reactants, weighted by their stoichiometry.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MassBalance {
+    /// Imbalance of each element that is not balanced, by symbol.
+    pub elements: BTreeMap<String, f64>,
+    /// Imbalance of the charge, or `None` if some species has no
+    /// `fbc:charge`.
+    pub charge: Option<f64>,
+}
+
+impl MassBalance {
+    /// Whether neither the elements nor the known charge are created nor
+    /// destroyed.
+    pub fn is_balanced(&self) -> bool {
+        self.elements.is_empty() && self.charge.unwrap_or(0.) == 0.
+    }
+}
+
+/// Error raised when the [`MassBalance`] of a reaction cannot be computed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MassBalanceError {
+    /// A species reference to a species that is not in the model.
+    UnknownSpecies(String),
+    /// A species without `fbc:chemicalFormula`.
+    MissingFormula(String),
+    /// A species whose formula cannot be parsed.
+    BadFormula {
+        species: String,
+        error: ChemicalFormulaError,
+    },
+}
+
+impl fmt::Display for MassBalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MassBalanceError::UnknownSpecies(id) => write!(f, "unknown species `{}`", id),
+            MassBalanceError::MissingFormula(id) => {
+                write!(f, "species `{}` has no chemical formula", id)
+            }
+            MassBalanceError::BadFormula { species, error } => {
+                write!(f, "bad formula of species `{}`: {}", species, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MassBalanceError {}
+
+/// Result of [`Model::mass_balance_report`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MassBalanceReport {
+    /// Imbalance of each unbalanced reaction, by id.
+    pub unbalanced: BTreeMap<String, MassBalance>,
+    /// Reactions whose balance could not be computed, by id.
+    pub unchecked: BTreeMap<String, MassBalanceError>,
+    /// Exchange, demand and sink reactions, which are not checked.
+    pub skipped: Vec<String>,
+}
+
+impl Reaction {
+    /// Per-element and charge imbalance of the reaction, from the
+    /// `fbc:chemicalFormula` and `fbc:charge` of its species in `model`. A
+    /// missing `stoichiometry` counts as 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::Model;
+    /// use std::fs;
+    ///
+    /// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+    /// let model = Model::parse(&ecoli).unwrap();
+    /// let balance = model.reactions["R_PGK"].mass_balance(&model).unwrap();
+    /// assert!(balance.is_balanced());
+    /// // the species of this model have no charge
+    /// assert_eq!(balance.charge, None);
+    /// ```
+    pub fn mass_balance(&self, model: &Model) -> Result<MassBalance, MassBalanceError> {
+        let mut balance = MassBalance {
+            elements: BTreeMap::new(),
+            charge: Some(0.),
+        };
+        let reactants = self.list_of_reactants.species_references.iter();
+        let products = self.list_of_products.species_references.iter();
+        let participants = reactants
+            .map(|sref| (sref, -1.))
+            .chain(products.map(|sref| (sref, 1.)));
+        for (sref, sign) in participants {
+            let species = model
+                .species
+                .get(&sref.species)
+                .ok_or_else(|| MassBalanceError::UnknownSpecies(sref.species.to_owned()))?;
+            let formula: ChemicalFormula = species
+                .formula
+                .as_deref()
+                .ok_or_else(|| MassBalanceError::MissingFormula(species.id.to_owned()))?
+                .parse()
+                .map_err(|error| MassBalanceError::BadFormula {
+                    species: species.id.to_owned(),
+                    error,
+                })?;
+            let coefficient = sign * sref.stoichiometry.unwrap_or(1.);
+            for (symbol, count) in formula.elements {
+                *balance.elements.entry(symbol).or_insert(0.) += coefficient * count;
+            }
+            balance.charge = balance
+                .charge
+                .zip(species.charge)
+                .map(|(total, charge)| total + coefficient * charge as f64);
+        }
+        balance.elements.retain(|_, n| n.abs() > TOLERANCE);
+        if let Some(charge) = balance.charge.as_mut() {
+            if charge.abs() <= TOLERANCE {
+                *charge = 0.;
+            }
+        }
+        Ok(balance)
+    }
+
+    /// Whether the reaction exchanges species with the outside of the
+    /// model: its SBO term is that of an exchange, demand or sink reaction,
+    /// or it has a species with `boundaryCondition="true"`.
+    pub fn is_boundary(&self, model: &Model) -> bool {
+        matches!(self.sbo_term.as_deref(), Some(term) if BOUNDARY_SBO_TERMS.contains(&term))
+            || self
+                .list_of_reactants
+                .species_references
+                .iter()
+                .chain(self.list_of_products.species_references.iter())
+                .any(|sref| {
+                    matches!(model.species.get(&sref.species), Some(sp) if sp.boundary_condition)
+                })
+    }
+}
+
+impl Model {
+    /// [`Reaction::mass_balance`] of all the reactions that are not
+    /// [`Reaction::is_boundary`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::Model;
+    /// use std::fs;
+    ///
+    /// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+    /// let report = Model::parse(&ecoli).unwrap().mass_balance_report();
+    /// assert_eq!(report.skipped.len(), 20);
+    /// assert!(report.unbalanced.contains_key("R_BIOMASS_Ecoli_core_w_GAM"));
+    /// ```
+    pub fn mass_balance_report(&self) -> MassBalanceReport {
+        let mut report = MassBalanceReport::default();
+        let mut reactions: Vec<(&String, &Reaction)> = self.reactions.iter().collect();
+        reactions.sort_by_key(|(id, _)| *id);
+        for (id, reaction) in reactions {
+            if reaction.is_boundary(self) {
+                report.skipped.push(id.to_owned());
+                continue;
+            }
+            match reaction.mass_balance(self) {
+                Ok(balance) if balance.is_balanced() => {}
+                Ok(balance) => {
+                    report.unbalanced.insert(id.to_owned(), balance);
+                }
+                Err(e) => {
+                    report.unchecked.insert(id.to_owned(), e);
+                }
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,221 @@
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::{DeError, Writer};
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use std::fmt;
+
+use super::error::{self, Error, Location, Result};
+use super::ModelRaw;
+
+const SBML_L3V2_CORE: &str = "http://www.sbml.org/sbml/level3/version2/core";
+const FBC_V2: &str = "http://www.sbml.org/sbml/level3/version1/fbc/version2";
+
+/// SBML Level 3 package declared on the root `<sbml>` element, through an
+/// `xmlns:prefix` namespace and a `prefix:required` attribute.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Package {
+    /// Namespace prefix used in the document (e.g., `fbc`).
+    pub prefix: String,
+    /// Namespace URI, which encodes the package version.
+    pub uri: Option<String>,
+    /// Whether the package can change the mathematical meaning of the model.
+    pub required: Option<bool>,
+}
+
+/// Root `<sbml>` element of a document. On top of the [`ModelRaw`], it keeps
+/// the SBML level and version, the core namespace and the declared packages,
+/// which are written back by [`SbmlDocument::to_string`].
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::SbmlDocument;
+/// use std::fs;
+///
+/// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+/// let document = SbmlDocument::parse(&ecoli).unwrap();
+/// assert_eq!((document.level, document.version), (3, 1));
+/// assert!(document.is_declared("fbc"));
+/// assert!(!document.is_required("fbc"));
+/// assert_eq!(document.model.id.as_deref(), Some("e_coli_core"));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct SbmlDocument {
+    /// Namespace of SBML core, which encodes the level and version.
+    pub xmlns: Option<String>,
+    /// SBML level, e.g., 3.
+    pub level: u32,
+    /// Version of the SBML level.
+    pub version: u32,
+    /// SBO term of the document, e.g., `SBO:0000624` for flux balance
+    /// framework.
+    pub sbo_term: Option<String>,
+    /// Packages declared on the root element, in document order.
+    pub packages: Vec<Package>,
+    /// The model of the document.
+    pub model: ModelRaw,
+}
+
+impl Default for SbmlDocument {
+    /// Level 3 Version 2 document with the FBC package declared as not required.
+    fn default() -> Self {
+        Self {
+            xmlns: Some(SBML_L3V2_CORE.to_string()),
+            level: 3,
+            version: 2,
+            sbo_term: None,
+            packages: vec![Package {
+                prefix: "fbc".to_string(),
+                uri: Some(FBC_V2.to_string()),
+                required: Some(false),
+            }],
+            model: ModelRaw::default(),
+        }
+    }
+}
+
+impl From<ModelRaw> for SbmlDocument {
+    fn from(model: ModelRaw) -> Self {
+        Self {
+            model,
+            ..Default::default()
+        }
+    }
+}
+
+impl SbmlDocument {
+    /// Parse an SBML document from the XML string `doc`.
+    pub fn parse(doc: &str) -> Result<Self> {
+        error::from_str(doc)
+    }
+
+    /// Serialize the document to XML, writing back the level, version and
+    /// package declarations of the root element.
+    pub fn to_string(&self) -> Result<String> {
+        self.write()
+            .map_err(|e| Error::from_de(e, Location::default()))
+    }
+
+    fn write(&self) -> Result<String, DeError> {
+        let mut writer = Writer::new(Vec::new());
+        let mut root = BytesStart::borrowed_name(b"sbml");
+        if let Some(xmlns) = &self.xmlns {
+            root.push_attribute(("xmlns", xmlns.as_str()));
+        }
+        root.push_attribute(("level", self.level.to_string().as_str()));
+        root.push_attribute(("version", self.version.to_string().as_str()));
+        if let Some(sbo_term) = &self.sbo_term {
+            root.push_attribute(("sboTerm", sbo_term.as_str()));
+        }
+        for package in self.packages.iter() {
+            if let Some(uri) = &package.uri {
+                root.push_attribute((format!("xmlns:{}", package.prefix).as_str(), uri.as_str()));
+            }
+            if let Some(required) = package.required {
+                root.push_attribute((
+                    format!("{}:required", package.prefix).as_str(),
+                    if required { "true" } else { "false" },
+                ));
+            }
+        }
+        writer.write_event(Event::Start(root))?;
+        quick_xml::se::to_writer(writer.inner(), &self.model)?;
+        writer.write_event(Event::End(BytesEnd::borrowed(b"sbml")))?;
+        String::from_utf8(writer.into_inner())
+            .map_err(|e| DeError::Xml(quick_xml::Error::Utf8(e.utf8_error())))
+    }
+
+    /// Prefixes of all packages with a namespace declaration.
+    pub fn declared_packages(&self) -> impl Iterator<Item = &str> {
+        self.packages
+            .iter()
+            .filter(|p| p.uri.is_some())
+            .map(|p| p.prefix.as_str())
+    }
+
+    /// Prefixes of the packages marked as `required="true"`.
+    pub fn required_packages(&self) -> impl Iterator<Item = &str> {
+        self.packages
+            .iter()
+            .filter(|p| p.required.unwrap_or(false))
+            .map(|p| p.prefix.as_str())
+    }
+
+    /// Whether the package with `prefix` has a namespace declaration.
+    pub fn is_declared(&self, prefix: &str) -> bool {
+        self.declared_packages().any(|p| p == prefix)
+    }
+
+    /// Whether the package with `prefix` is marked as `required="true"`.
+    pub fn is_required(&self, prefix: &str) -> bool {
+        self.required_packages().any(|p| p == prefix)
+    }
+
+    fn package_mut(&mut self, prefix: &str) -> &mut Package {
+        match self.packages.iter().position(|p| p.prefix == prefix) {
+            Some(i) => &mut self.packages[i],
+            None => {
+                self.packages.push(Package {
+                    prefix: prefix.to_string(),
+                    uri: None,
+                    required: None,
+                });
+                self.packages.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+/// The package attributes of `<sbml>` are only known at runtime, so the
+/// root element is visited as a map of attributes and children.
+impl<'de> Deserialize<'de> for SbmlDocument {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SbmlVisitor;
+
+        impl<'de> Visitor<'de> for SbmlVisitor {
+            type Value = SbmlDocument;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an <sbml> element")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut document = SbmlDocument {
+                    xmlns: None,
+                    packages: Vec::new(),
+                    ..Default::default()
+                };
+                let mut model = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "xmlns" => document.xmlns = Some(map.next_value()?),
+                        "level" => document.level = map.next_value()?,
+                        "version" => document.version = map.next_value()?,
+                        "sboTerm" => document.sbo_term = Some(map.next_value()?),
+                        "model" => model = Some(map.next_value()?),
+                        key => match key.split_once(':') {
+                            Some(("xmlns", prefix)) => {
+                                document.package_mut(prefix).uri = Some(map.next_value()?)
+                            }
+                            Some((prefix, "required")) => {
+                                document.package_mut(prefix).required = Some(map.next_value()?)
+                            }
+                            _ => {
+                                map.next_value::<IgnoredAny>()?;
+                            }
+                        },
+                    }
+                }
+                document.model = model.ok_or_else(|| de::Error::missing_field("model"))?;
+                Ok(document)
+            }
+        }
+
+        deserializer.deserialize_struct("sbml", &[], SbmlVisitor)
+    }
+}
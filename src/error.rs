@@ -0,0 +1,243 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{DeError, Reader};
+use serde::de::DeserializeOwned;
+use std::cell::Cell;
+use std::fmt;
+use std::io::{BufRead, Read};
+
+/// Shortcut for results of this crate, defaulting to [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Position of an error in the source document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Byte offset from the start of the document.
+    pub offset: usize,
+    /// 1-based line.
+    pub line: usize,
+    /// 1-based column, in characters.
+    pub column: usize,
+}
+
+/// Where an [`Error`] happened: the path of elements from the `<model>` down
+/// to the offending one, as in `model/listOfReactions/reaction[id=R_PGK]`,
+/// and its position in the text when it is known.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Location {
+    /// Elements from the `<model>` to the offending one, separated by `/`,
+    /// with the id of those that have one.
+    pub path: String,
+    /// Position in the text, `None` when the error is found after parsing,
+    /// such as an unknown id, or while writing.
+    pub position: Option<Position>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(Position { line, column, .. }) => {
+                write!(f, "line {}, column {} ({})", line, column, self.path)
+            }
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Error raised while parsing an SBML document or querying a [`crate::Model`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The document is not well-formed XML.
+    Xml { message: String, location: Location },
+    /// A required attribute (or child element) is not present.
+    MissingAttribute {
+        attribute: String,
+        location: Location,
+    },
+    /// An attribute or text content could not be parsed into its type.
+    BadValue { message: String, location: Location },
+    /// An element or construct that this crate does not implement.
+    Unsupported { message: String, location: Location },
+    /// An identifier that does not refer to any element of the model.
+    UnknownId { id: String, location: Location },
+}
+
+impl Error {
+    /// Where the error happened.
+    pub fn location(&self) -> &Location {
+        match self {
+            Error::Xml { location, .. }
+            | Error::MissingAttribute { location, .. }
+            | Error::BadValue { location, .. }
+            | Error::Unsupported { location, .. }
+            | Error::UnknownId { location, .. } => location,
+        }
+    }
+
+    pub(crate) fn from_de(err: DeError, location: Location) -> Self {
+        match err {
+            DeError::Xml(e) => Error::Xml {
+                message: e.to_string(),
+                location,
+            },
+            DeError::Eof => Error::Xml {
+                message: "unexpected end of document".to_string(),
+                location,
+            },
+            DeError::Int(_) | DeError::Float(_) | DeError::InvalidBoolean(_) => Error::BadValue {
+                message: err.to_string(),
+                location,
+            },
+            DeError::Custom(message) => {
+                if let Some(field) = message.strip_prefix("missing field ") {
+                    Error::MissingAttribute {
+                        attribute: field.trim_matches('`').to_string(),
+                        location,
+                    }
+                } else if message.starts_with("unknown variant") {
+                    Error::Unsupported { message, location }
+                } else {
+                    Error::BadValue { message, location }
+                }
+            }
+            _ => Error::Unsupported {
+                message: err.to_string(),
+                location,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Xml { message, location } => {
+                write!(f, "malformed XML at {}: {}", location, message)
+            }
+            Error::MissingAttribute {
+                attribute,
+                location,
+            } => write!(
+                f,
+                "missing required attribute `{}` at {}",
+                attribute, location
+            ),
+            Error::BadValue { message, location } => {
+                write!(f, "bad value at {}: {}", location, message)
+            }
+            Error::Unsupported { message, location } => {
+                write!(f, "unsupported construct at {}: {}", location, message)
+            }
+            Error::UnknownId { id, location } => {
+                write!(f, "unknown identifier `{}` at {}", id, location)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Deserialize `doc`. On failure, the document is deserialized again keeping
+/// track of the consumed bytes to locate the error.
+pub(crate) fn from_str<T: DeserializeOwned>(doc: &str) -> Result<T> {
+    quick_xml::de::from_str(doc).map_err(|original| {
+        let consumed = Cell::new(0);
+        let mut de = quick_xml::de::Deserializer::from_reader(TrackedReader {
+            inner: doc.as_bytes(),
+            consumed: &consumed,
+        });
+        match T::deserialize(&mut de) {
+            Err(e) => Error::from_de(e, locate(doc, consumed.get())),
+            // the reader changed the outcome: report the error unlocated
+            Ok(_) => Error::from_de(original, Location::default()),
+        }
+    })
+}
+
+/// Slice reader that counts the bytes consumed by the XML reader.
+struct TrackedReader<'a> {
+    inner: &'a [u8],
+    consumed: &'a Cell<usize>,
+}
+
+impl Read for TrackedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed.set(self.consumed.get() + n);
+        Ok(n)
+    }
+}
+
+impl BufRead for TrackedReader<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        Ok(self.inner)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.consumed.set(self.consumed.get() + amt);
+    }
+}
+
+/// `name[id=ID]` label of an element, used to build the element path.
+fn label(e: &BytesStart) -> String {
+    let name = String::from_utf8_lossy(e.name()).into_owned();
+    let id = e.attributes().filter_map(|a| a.ok()).find(|a| {
+        let key = a.key;
+        key == b"id" || key.ends_with(b":id")
+    });
+    match id {
+        Some(id) => format!("{}[id={}]", name, String::from_utf8_lossy(&id.value)),
+        None => name,
+    }
+}
+
+/// Find the element that was being read when `consumed` bytes of `doc` had
+/// been read. If that element was already closed, it is still reported.
+fn locate(doc: &str, consumed: usize) -> Location {
+    let mut reader = Reader::from_str(doc);
+    reader.expand_empty_elements(true).check_end_names(true);
+    let mut stack: Vec<(String, usize)> = Vec::new();
+    let mut closed: Option<(String, usize)> = None;
+    let mut offset = 0;
+    let mut buf = Vec::new();
+    loop {
+        let before = reader.buffer_position();
+        if before >= consumed {
+            break;
+        }
+        offset = before;
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) => {
+                stack.push((label(&e), before));
+                closed = None;
+            }
+            Ok(Event::End(_)) => closed = stack.pop(),
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => closed = None,
+        }
+        buf.clear();
+    }
+    if consumed >= doc.len() {
+        // truncated document: report the elements left open
+        offset = doc.len();
+    } else if let Some(element) = closed {
+        offset = element.1;
+        stack.push(element);
+    }
+    let path = stack
+        .into_iter()
+        .map(|(name, _)| name)
+        .skip_while(|name| name == "sbml")
+        .collect::<Vec<String>>()
+        .join("/");
+    let before = &doc[..offset.min(doc.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Location {
+        path,
+        position: Some(Position {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }),
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use super::{parallel_map, FbaError, FluxProblem};
+use crate::Model;
+
+/// Optimal objective value of the model after knocking out each of `genes`
+/// (all the gene products if `None`), by gene product id. Knockouts that
+/// leave the model infeasible are reported as `None`.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::{fba, Model};
+/// use std::fs;
+///
+/// let ecoli = Model::parse(&fs::read_to_string("examples/EcoliCore.xml").unwrap()).unwrap();
+/// let growth = fba::single_gene_deletion(&ecoli, Some(&["G_b1779", "G_b0008"])).unwrap();
+/// // gapA is essential, while the transaldolase is also encoded by talA
+/// assert!(growth["G_b1779"].unwrap().abs() < 1e-6);
+/// assert!((growth["G_b0008"].unwrap() - 0.8739).abs() < 1e-4);
+/// ```
+pub fn single_gene_deletion(
+    model: &Model,
+    genes: Option<&[&str]>,
+) -> Result<HashMap<String, Option<f64>>, FbaError> {
+    let genes = selected_genes(model, genes);
+    let scan = KnockoutScan::new(model)?;
+    let values = parallel_map(&genes, |gene| scan.objective_value(&[gene]))?;
+    Ok(genes
+        .into_iter()
+        .map(|gene| gene.to_owned())
+        .zip(values)
+        .collect())
+}
+
+/// Optimal objective value of the model after knocking out each pair of
+/// `genes` (all the gene products if `None`), keyed by the pair of gene
+/// product ids in lexicographic order. Knockouts that leave the model
+/// infeasible are reported as `None`.
+pub fn double_gene_deletion(
+    model: &Model,
+    genes: Option<&[&str]>,
+) -> Result<HashMap<(String, String), Option<f64>>, FbaError> {
+    let mut genes = selected_genes(model, genes);
+    genes.sort_unstable();
+    genes.dedup();
+    let pairs: Vec<(&str, &str)> = genes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| genes[i + 1..].iter().map(move |b| (*a, *b)))
+        .collect();
+    let scan = KnockoutScan::new(model)?;
+    let values = parallel_map(&pairs, |(a, b)| scan.objective_value(&[a, b]))?;
+    Ok(pairs
+        .into_iter()
+        .map(|(a, b)| (a.to_owned(), b.to_owned()))
+        .zip(values)
+        .collect())
+}
+
+fn selected_genes<'a>(model: &'a Model, genes: Option<&[&'a str]>) -> Vec<&'a str> {
+    match genes {
+        Some(genes) => genes.to_vec(),
+        None => {
+            let mut genes: Vec<&str> = model.gene_products.keys().map(|id| id.as_str()).collect();
+            genes.sort_unstable();
+            genes
+        }
+    }
+}
+
+/// FBA problem of the wild type, reused for every knockout.
+struct KnockoutScan<'a> {
+    model: &'a Model,
+    problem: FluxProblem,
+    wild_type: Option<f64>,
+}
+
+impl<'a> KnockoutScan<'a> {
+    fn new(model: &'a Model) -> Result<Self, FbaError> {
+        let problem = FluxProblem::new(model)?;
+        let wild_type = optimum(&problem)?;
+        Ok(KnockoutScan {
+            model,
+            problem,
+            wild_type,
+        })
+    }
+
+    fn objective_value(&self, genes: &[&str]) -> Result<Option<f64>, FbaError> {
+        let inactive = self.model.inactive_reactions(genes)?;
+        if inactive.is_empty() {
+            return Ok(self.wild_type);
+        }
+        let mut problem = self.problem.clone();
+        for reaction in inactive {
+            if let Ok(j) = problem
+                .matrix
+                .reactions
+                .binary_search_by(|r| r.as_str().cmp(reaction))
+            {
+                problem.lower[j] = 0.;
+                problem.upper[j] = 0.;
+            }
+        }
+        optimum(&problem)
+    }
+}
+
+fn optimum(problem: &FluxProblem) -> Result<Option<f64>, FbaError> {
+    match problem.solve() {
+        Ok(solution) => Ok(Some(solution.objective_value)),
+        Err(FbaError::Infeasible) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
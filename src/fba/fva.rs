@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use super::simplex::{self, LinearProgram, LpError};
+use super::{parallel_map, FbaError, FluxProblem};
+use crate::{Error, Location, Model, ObjectiveSense};
+
+/// Flux Variability Analysis: the minimum and maximum flux of each reaction
+/// in `reactions` (all of them if `None`) while the objective stays within
+/// `fraction_of_optimum` of its optimal value.
+///
+/// The objective may be worse than its optimum by `1 - fraction_of_optimum`
+/// times the absolute value of the optimum: for a maximization, it is
+/// constrained to be at least `optimum - (1 - fraction_of_optimum) |optimum|`;
+/// for a minimization, at most `optimum + (1 - fraction_of_optimum) |optimum|`.
+/// Fluxes that can grow without limit are reported as infinite. The linear
+/// programs of the reactions are solved in parallel.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::{fba, Model};
+/// use std::fs;
+///
+/// let ecoli = Model::parse(&fs::read_to_string("examples/EcoliCore.xml").unwrap()).unwrap();
+/// let ranges = fba::flux_variability(&ecoli, Some(&["R_EX_glc__D_e", "R_PGI"]), 1.).unwrap();
+/// let (min, max) = ranges["R_EX_glc__D_e"];
+/// assert!((min + 10.).abs() < 1e-6 && (max + 10.).abs() < 1e-6);
+/// ```
+pub fn flux_variability(
+    model: &Model,
+    reactions: Option<&[&str]>,
+    fraction_of_optimum: f64,
+) -> Result<HashMap<String, (f64, f64)>, FbaError> {
+    let problem = FluxProblem::new(model)?;
+    let columns: Vec<usize> = match reactions {
+        Some(reactions) => reactions
+            .iter()
+            .map(|id| {
+                problem
+                    .matrix
+                    .reactions
+                    .binary_search_by(|r| r.as_str().cmp(id))
+                    .map_err(|_| {
+                        FbaError::Model(Error::UnknownId {
+                            id: id.to_string(),
+                            location: Location {
+                                path: "model/listOfReactions".to_string(),
+                                position: None,
+                            },
+                        })
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+        None => (0..problem.matrix.reactions.len()).collect(),
+    };
+    let optimum = problem.solve()?.objective_value;
+
+    // keep the objective near its optimum through an extra row
+    // c·v - z = 0, with z bounded by the fraction of the optimum
+    let slack = (1. - fraction_of_optimum) * optimum.abs();
+    let mut lp = problem.linear_program();
+    let n = lp.columns.len();
+    let row = lp.n_rows;
+    for (j, column) in lp.columns.iter_mut().enumerate() {
+        if problem.objective[j] != 0. {
+            column.push((row, problem.objective[j]));
+        }
+    }
+    lp.columns.push(vec![(row, -1.)]);
+    lp.n_rows += 1;
+    lp.b.push(0.);
+    lp.c = vec![0.; n + 1];
+    match problem.sense {
+        ObjectiveSense::Maximize => {
+            lp.lower.push(optimum - slack);
+            lp.upper.push(f64::INFINITY);
+        }
+        ObjectiveSense::Minimize => {
+            lp.lower.push(f64::NEG_INFINITY);
+            lp.upper.push(optimum + slack);
+        }
+    }
+
+    let ranges = parallel_map(&columns, |&j| {
+        Ok((j, (extreme_flux(&lp, j, 1.)?, -extreme_flux(&lp, j, -1.)?)))
+    })?;
+    Ok(ranges
+        .into_iter()
+        .map(|(j, range)| (problem.matrix.reactions[j].clone(), range))
+        .collect())
+}
+
+impl Model {
+    /// [Flux Variability Analysis](flux_variability) of the model.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::Model;
+    /// use std::fs;
+    ///
+    /// let ecoli = Model::parse(&fs::read_to_string("examples/EcoliCore.xml").unwrap()).unwrap();
+    /// let ranges = ecoli.flux_variability(Some(&["R_PGI"]), 0.9).unwrap();
+    /// let (min, max) = ranges["R_PGI"];
+    /// assert!(min < max);
+    /// ```
+    pub fn flux_variability(
+        &self,
+        reactions: Option<&[&str]>,
+        fraction_of_optimum: f64,
+    ) -> Result<HashMap<String, (f64, f64)>, FbaError> {
+        flux_variability(self, reactions, fraction_of_optimum)
+    }
+}
+
+/// Minimum of `sign * v_j`.
+fn extreme_flux(lp: &LinearProgram, j: usize, sign: f64) -> Result<f64, FbaError> {
+    let mut lp = lp.clone();
+    lp.c[j] = sign;
+    match simplex::solve(&lp) {
+        Ok(solution) => Ok(solution.objective),
+        Err(LpError::Unbounded) => Ok(f64::NEG_INFINITY),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,245 @@
+//! Flux Balance Analysis (FBA) of constraint-based models.
+//!
+//! The linear program
+//!
+//! $$ \max / \min \; c \cdot v \quad \text{s.t.} \quad S v = 0, \; lb \le v \le ub $$
+//!
+//! is built from the [`StoichiometricMatrix`] of the model (without boundary
+//! species), the resolved [FBC flux bounds](Model::flux_bounds) and the
+//! [active objective](Model::active_objective), and solved with a built-in
+//! simplex. Requires the `fba` feature (enabled by default).
+mod deletion;
+mod fva;
+mod simplex;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::thread;
+
+use crate::{Error, Model, ObjectiveSense, StoichiometricMatrix};
+pub use deletion::{double_gene_deletion, single_gene_deletion};
+pub use fva::flux_variability;
+use simplex::{LinearProgram, LpError};
+
+/// Optimal solution of an FBA problem. All the maps are keyed by identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FbaSolution {
+    pub objective_value: f64,
+    /// Flux of each reaction.
+    pub fluxes: HashMap<String, f64>,
+    /// Dual value of the mass balance of each species: the change of the
+    /// objective value when $S_i v = 0$ is relaxed to $S_i v = 1$.
+    pub shadow_prices: HashMap<String, f64>,
+    /// Change of the objective value per unit of flux forced through each
+    /// reaction, $c_j - S_j^T y$.
+    pub reduced_costs: HashMap<String, f64>,
+}
+
+/// Error raised while solving an FBA problem.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FbaError {
+    /// The model could not be turned into a linear program.
+    Model(Error),
+    /// The model has no active FBC objective.
+    NoObjective,
+    /// No flux distribution satisfies the constraints.
+    Infeasible,
+    /// The objective can grow without limit.
+    Unbounded,
+    /// The solver gave up before reaching the optimum.
+    IterationLimit,
+    /// A worker thread of a parallel analysis panicked, with its message.
+    WorkerPanicked(String),
+}
+
+impl fmt::Display for FbaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FbaError::Model(e) => write!(f, "{}", e),
+            FbaError::NoObjective => write!(f, "the model has no active objective"),
+            FbaError::Infeasible => write!(f, "the problem is infeasible"),
+            FbaError::Unbounded => write!(f, "the problem is unbounded"),
+            FbaError::IterationLimit => write!(f, "the solver reached the iteration limit"),
+            FbaError::WorkerPanicked(message) => write!(f, "a worker thread panicked: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FbaError {}
+
+impl From<Error> for FbaError {
+    fn from(e: Error) -> Self {
+        FbaError::Model(e)
+    }
+}
+
+impl From<LpError> for FbaError {
+    fn from(e: LpError) -> Self {
+        match e {
+            LpError::Infeasible => FbaError::Infeasible,
+            LpError::Unbounded => FbaError::Unbounded,
+            LpError::IterationLimit => FbaError::IterationLimit,
+        }
+    }
+}
+
+/// Optimize the active objective of `model`.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::{fba, Model};
+/// use std::fs;
+///
+/// let ecoli = Model::parse(&fs::read_to_string("examples/EcoliCore.xml").unwrap()).unwrap();
+/// let solution = fba::optimize(&ecoli).unwrap();
+/// assert!((solution.objective_value - 0.8739).abs() < 1e-4);
+/// assert!((solution.fluxes["R_EX_glc__D_e"] + 10.).abs() < 1e-6);
+/// ```
+pub fn optimize(model: &Model) -> Result<FbaSolution, FbaError> {
+    FluxProblem::new(model)?.solve()
+}
+
+/// Linear program of a model, indexed by the columns of its stoichiometric
+/// matrix.
+#[derive(Debug, Clone)]
+pub(crate) struct FluxProblem {
+    pub matrix: StoichiometricMatrix,
+    pub lower: Vec<f64>,
+    pub upper: Vec<f64>,
+    pub objective: Vec<f64>,
+    pub sense: ObjectiveSense,
+}
+
+impl FluxProblem {
+    pub fn new(model: &Model) -> Result<Self, FbaError> {
+        let objective_function = model.active_objective().ok_or(FbaError::NoObjective)?;
+        let matrix = model.stoichiometric_matrix(true);
+        let bounds = model.all_flux_bounds()?;
+        let mut objective = vec![0.; matrix.reactions.len()];
+        for (reaction, coefficient) in objective_function.coefficients.iter() {
+            let j = matrix
+                .reactions
+                .binary_search(reaction)
+                .map_err(|_| Error::UnknownId {
+                    id: reaction.to_owned(),
+                    location: crate::Location {
+                        path: format!(
+                            "model/fbc:listOfObjectives/fbc:objective[id={}]",
+                            objective_function.id
+                        ),
+                        position: None,
+                    },
+                })?;
+            objective[j] += coefficient;
+        }
+        Ok(FluxProblem {
+            lower: matrix.reactions.iter().map(|r| bounds[r].0).collect(),
+            upper: matrix.reactions.iter().map(|r| bounds[r].1).collect(),
+            objective,
+            sense: objective_function.sense,
+            matrix,
+        })
+    }
+
+    /// Factor that turns the objective into a minimization.
+    fn sign(&self) -> f64 {
+        match self.sense {
+            ObjectiveSense::Maximize => -1.,
+            ObjectiveSense::Minimize => 1.,
+        }
+    }
+
+    pub fn linear_program(&self) -> LinearProgram {
+        let sign = self.sign();
+        LinearProgram {
+            n_rows: self.matrix.species.len(),
+            columns: (0..self.matrix.reactions.len())
+                .map(|j| self.matrix.column(j).collect())
+                .collect(),
+            b: vec![0.; self.matrix.species.len()],
+            c: self.objective.iter().map(|c| sign * c).collect(),
+            lower: self.lower.clone(),
+            upper: self.upper.clone(),
+        }
+    }
+
+    pub fn solve(&self) -> Result<FbaSolution, FbaError> {
+        let solution = simplex::solve(&self.linear_program())?;
+        let sign = self.sign();
+        let reactions = self.matrix.reactions.iter().cloned();
+        Ok(FbaSolution {
+            objective_value: sign * solution.objective,
+            fluxes: reactions.clone().zip(solution.x).collect(),
+            shadow_prices: self
+                .matrix
+                .species
+                .iter()
+                .cloned()
+                .zip(solution.duals.iter().map(|y| sign * y))
+                .collect(),
+            reduced_costs: reactions
+                .zip(solution.reduced_costs.iter().map(|d| sign * d))
+                .collect(),
+        })
+    }
+}
+
+/// Apply `f` to each item on a pool of scoped threads, keeping the order.
+fn parallel_map<T, R, F>(items: &[T], f: F) -> Result<Vec<R>, FbaError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, FbaError> + Sync,
+{
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(items.len())
+        .max(1);
+    let chunk_size = ((items.len() + threads - 1) / threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let f = &f;
+                scope.spawn(move || chunk.iter().map(f).collect::<Result<Vec<R>, _>>())
+            })
+            .collect();
+        let mut results = Vec::with_capacity(items.len());
+        for handle in handles {
+            let chunk = handle.join().map_err(|payload| {
+                let message = match payload.downcast::<String>() {
+                    Ok(message) => *message,
+                    Err(payload) => payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .unwrap_or_default(),
+                };
+                FbaError::WorkerPanicked(message)
+            })?;
+            results.extend(chunk?);
+        }
+        Ok(results)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worker_panics_become_errors() {
+        let result = parallel_map(&[1, 2, 3], |&i| {
+            if i == 2 {
+                panic!("bad item {}", i);
+            }
+            Ok(i)
+        });
+        assert_eq!(
+            result,
+            Err(FbaError::WorkerPanicked("bad item 2".to_string()))
+        );
+        assert_eq!(parallel_map(&[1, 2, 3], |&i| Ok(i * 2)), Ok(vec![2, 4, 6]));
+    }
+}
@@ -0,0 +1,325 @@
+//! Dense two-phase primal simplex for bounded variables.
+
+const TOL: f64 = 1e-9;
+const PIVOT_TOL: f64 = 1e-9;
+const FEASIBILITY_TOL: f64 = 1e-7;
+/// Consecutive degenerate pivots before switching to Bland's rule.
+const MAX_DEGENERATE: usize = 50;
+
+/// $\min c \cdot x$ subject to $A x = b$ and $l \le x \le u$, where $A$ is
+/// given by sparse columns and bounds may be infinite.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LinearProgram {
+    pub n_rows: usize,
+    pub columns: Vec<Vec<(usize, f64)>>,
+    pub b: Vec<f64>,
+    pub c: Vec<f64>,
+    pub lower: Vec<f64>,
+    pub upper: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LpSolution {
+    pub x: Vec<f64>,
+    pub objective: f64,
+    /// Dual values $y$ of the rows, such that the reduced costs are
+    /// $d = c - A^T y$.
+    pub duals: Vec<f64>,
+    pub reduced_costs: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LpError {
+    Infeasible,
+    Unbounded,
+    IterationLimit,
+}
+
+struct Tableau {
+    m: usize,
+    width: usize,
+    /// $B^{-1} [A | D]$, row-major, where $D$ holds the artificial columns.
+    t: Vec<f64>,
+    basis: Vec<usize>,
+    is_basic: Vec<bool>,
+    x: Vec<f64>,
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+    cost: Vec<f64>,
+    d: Vec<f64>,
+}
+
+impl Tableau {
+    fn reset_reduced_costs(&mut self) {
+        self.d = self.cost.clone();
+        for (i, &bi) in self.basis.iter().enumerate() {
+            let cb = self.cost[bi];
+            if cb != 0. {
+                let row = &self.t[i * self.width..(i + 1) * self.width];
+                self.d.iter_mut().zip(row).for_each(|(d, a)| *d -= cb * a);
+            }
+        }
+        for &bi in self.basis.iter() {
+            self.d[bi] = 0.;
+        }
+    }
+
+    fn pivot(&mut self, p: usize, q: usize) {
+        let width = self.width;
+        let piv = self.t[p * width + q];
+        self.t[p * width..(p + 1) * width]
+            .iter_mut()
+            .for_each(|a| *a /= piv);
+        let row_p = self.t[p * width..(p + 1) * width].to_vec();
+        for i in (0..self.m).filter(|&i| i != p) {
+            let f = self.t[i * width + q];
+            if f != 0. {
+                self.t[i * width..(i + 1) * width]
+                    .iter_mut()
+                    .zip(row_p.iter())
+                    .for_each(|(a, ap)| *a -= f * ap);
+            }
+        }
+        let f = self.d[q];
+        self.d
+            .iter_mut()
+            .zip(row_p.iter())
+            .for_each(|(d, ap)| *d -= f * ap);
+        self.d[q] = 0.;
+        self.is_basic[self.basis[p]] = false;
+        self.is_basic[q] = true;
+        self.basis[p] = q;
+    }
+
+    /// Entering variable and its direction of change.
+    fn price(&self, bland: bool) -> Option<(usize, f64)> {
+        let mut best: Option<(usize, f64, f64)> = None;
+        for j in (0..self.width).filter(|&j| !self.is_basic[j]) {
+            let dj = self.d[j];
+            let candidate = if dj < -TOL && self.x[j] < self.upper[j] - TOL {
+                (j, 1., -dj)
+            } else if dj > TOL && self.x[j] > self.lower[j] + TOL {
+                (j, -1., dj)
+            } else {
+                continue;
+            };
+            if bland {
+                return Some((candidate.0, candidate.1));
+            }
+            if best.map(|b| candidate.2 > b.2).unwrap_or(true) {
+                best = Some(candidate);
+            }
+        }
+        best.map(|(j, dir, _)| (j, dir))
+    }
+
+    fn run(&mut self, max_iter: usize) -> Result<(), LpError> {
+        let mut degenerate = 0;
+        for _ in 0..max_iter {
+            let (q, dir) = match self.price(degenerate > MAX_DEGENERATE) {
+                Some(entering) => entering,
+                None => return Ok(()),
+            };
+            // ratio test
+            let mut theta = self.upper[q] - self.lower[q];
+            let mut leave: Option<(usize, bool)> = None;
+            for i in 0..self.m {
+                let a = self.t[i * self.width + q];
+                if a.abs() < PIVOT_TOL {
+                    continue;
+                }
+                let rate = -dir * a;
+                let bi = self.basis[i];
+                let (ti, to_lower) = if rate < 0. && self.lower[bi].is_finite() {
+                    ((self.x[bi] - self.lower[bi]) / -rate, true)
+                } else if rate > 0. && self.upper[bi].is_finite() {
+                    ((self.upper[bi] - self.x[bi]) / rate, false)
+                } else {
+                    continue;
+                };
+                let ti = ti.max(0.);
+                let better = match leave {
+                    _ if ti < theta - TOL => true,
+                    Some((l, _)) => ti < theta + TOL && a.abs() > self.t[l * self.width + q].abs(),
+                    None => false,
+                };
+                if better {
+                    theta = ti;
+                    leave = Some((i, to_lower));
+                }
+            }
+            if theta.is_infinite() {
+                return Err(LpError::Unbounded);
+            }
+            degenerate = if theta < TOL { degenerate + 1 } else { 0 };
+            self.x[q] += dir * theta;
+            for i in 0..self.m {
+                let a = self.t[i * self.width + q];
+                self.x[self.basis[i]] -= dir * theta * a;
+            }
+            match leave {
+                None => {
+                    self.x[q] = if dir > 0. {
+                        self.upper[q]
+                    } else {
+                        self.lower[q]
+                    }
+                }
+                Some((p, to_lower)) => {
+                    let bp = self.basis[p];
+                    self.x[bp] = if to_lower {
+                        self.lower[bp]
+                    } else {
+                        self.upper[bp]
+                    };
+                    self.pivot(p, q);
+                }
+            }
+        }
+        Err(LpError::IterationLimit)
+    }
+}
+
+/// Solve `lp` starting from an artificial basis: phase 1 minimizes the sum
+/// of the artificials and phase 2 the original objective.
+pub(crate) fn solve(lp: &LinearProgram) -> Result<LpSolution, LpError> {
+    let m = lp.n_rows;
+    let n = lp.columns.len();
+    let width = n + m;
+    let max_iter = 50 * (width + 1);
+
+    let mut x: Vec<f64> = (0..n)
+        .map(|j| {
+            if lp.lower[j].is_finite() {
+                lp.lower[j]
+            } else if lp.upper[j].is_finite() {
+                lp.upper[j]
+            } else {
+                0.
+            }
+        })
+        .collect();
+    let mut residual = lp.b.clone();
+    for (j, column) in lp.columns.iter().enumerate() {
+        for &(i, a) in column.iter() {
+            residual[i] -= a * x[j];
+        }
+    }
+    let signs: Vec<f64> = residual
+        .iter()
+        .map(|r| if *r >= 0. { 1. } else { -1. })
+        .collect();
+    x.extend(residual.iter().map(|r| r.abs()));
+
+    let mut t = vec![0.; m * width];
+    for (j, column) in lp.columns.iter().enumerate() {
+        for &(i, a) in column.iter() {
+            t[i * width + j] += signs[i] * a;
+        }
+    }
+    for i in 0..m {
+        t[i * width + n + i] = 1.;
+    }
+    let mut lower = lp.lower.clone();
+    lower.resize(width, 0.);
+    let mut upper = lp.upper.clone();
+    upper.resize(width, f64::INFINITY);
+    let mut cost = vec![0.; n];
+    cost.resize(width, 1.);
+    let mut is_basic = vec![false; n];
+    is_basic.resize(width, true);
+
+    let mut tableau = Tableau {
+        m,
+        width,
+        t,
+        basis: (n..width).collect(),
+        is_basic,
+        x,
+        lower,
+        upper,
+        cost,
+        d: Vec::new(),
+    };
+    tableau.reset_reduced_costs();
+    tableau.run(max_iter)?;
+    let infeasibility: f64 = tableau.x[n..].iter().sum();
+    if infeasibility > FEASIBILITY_TOL * lp.b.iter().fold(1., |acc: f64, b| acc.max(b.abs())) {
+        return Err(LpError::Infeasible);
+    }
+
+    // phase 2: artificials are fixed to zero
+    for k in n..width {
+        tableau.upper[k] = 0.;
+        if !tableau.is_basic[k] {
+            tableau.x[k] = 0.;
+        }
+    }
+    tableau.cost = lp.c.clone();
+    tableau.cost.resize(width, 0.);
+    tableau.reset_reduced_costs();
+    tableau.run(max_iter)?;
+
+    // recompute the basic values from the nonbasic ones to drop the
+    // accumulated round-off: x_B = B^-1 (b - N x_N)
+    let mut residual = lp.b.clone();
+    for (j, column) in lp.columns.iter().enumerate() {
+        if !tableau.is_basic[j] {
+            for &(i, a) in column.iter() {
+                residual[i] -= a * tableau.x[j];
+            }
+        }
+    }
+    for (k, &bk) in tableau.basis.iter().enumerate() {
+        tableau.x[bk] = (0..m)
+            .map(|i| signs[i] * tableau.t[k * width + n + i] * residual[i])
+            .sum();
+    }
+
+    let x = tableau.x[..n].to_vec();
+    Ok(LpSolution {
+        objective: x.iter().zip(lp.c.iter()).map(|(x, c)| x * c).sum(),
+        duals: (0..m).map(|i| -signs[i] * tableau.d[n + i]).collect(),
+        reduced_costs: tableau.d[..n].to_vec(),
+        x,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solves_bounded_lp_with_duals() {
+        // min -x0 - 2 x1  s.t. x0 + x1 - x2 = 0, x2 in [0, 4], x1 in [0, 3]
+        let lp = LinearProgram {
+            n_rows: 1,
+            columns: vec![vec![(0, 1.)], vec![(0, 1.)], vec![(0, -1.)]],
+            b: vec![0.],
+            c: vec![-1., -2., 0.],
+            lower: vec![0., 0., 0.],
+            upper: vec![f64::INFINITY, 3., 4.],
+        };
+        let solution = solve(&lp).unwrap();
+        assert!((solution.objective + 7.).abs() < 1e-9);
+        assert!((solution.x[0] - 1.).abs() < 1e-9);
+        assert!((solution.x[1] - 3.).abs() < 1e-9);
+        assert!((solution.duals[0] + 1.).abs() < 1e-9);
+        assert!((solution.reduced_costs[1] + 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn detects_infeasible_and_unbounded() {
+        let mut lp = LinearProgram {
+            n_rows: 1,
+            columns: vec![vec![(0, 1.)], vec![(0, -1.)]],
+            b: vec![0.],
+            c: vec![-1., 0.],
+            lower: vec![1., 0.],
+            upper: vec![2., 0.5],
+        };
+        assert_eq!(solve(&lp), Err(LpError::Infeasible));
+        lp.upper = vec![f64::INFINITY, f64::INFINITY];
+        assert_eq!(solve(&lp), Err(LpError::Unbounded));
+    }
+}
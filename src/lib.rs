@@ -83,20 +83,37 @@
 //! * Test suite with libsbml comparison trough cobrapy.
 pub mod annotation;
 mod base_types;
+mod chemistry;
+mod document;
+mod error;
+#[cfg(feature = "fba")]
+pub mod fba;
 mod list_of;
 pub mod mathml;
 mod model;
 #[cfg(feature = "default")]
 mod pyo;
-mod unit;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+mod stoichiometry;
+pub mod unit;
+pub mod validation;
 
 pub use base_types::{
-    Compartment, Constraint, FunctionDefinition, InitialAssignment, KineticLaw, LocalParameter,
-    Message, Objective, Parameter, Reaction, Rule, Species, SpeciesReference,
+    Association, Compartment, Constraint, Delay, Event, EventAssignment, FunctionDefinition,
+    GeneProduct, GeneProductAssociation, InitialAssignment, KineticLaw, LocalParameter, Message,
+    ModifierSpeciesReference, Objective, Parameter, Priority, Reaction, Rule, Species,
+    SpeciesReference, Trigger,
 };
-pub use unit::{Unit, UnitSId, UnitSIdRef};
+pub use chemistry::{
+    ChemicalFormula, ChemicalFormulaError, MassBalance, MassBalanceError, MassBalanceReport,
+};
+pub use stoichiometry::StoichiometricMatrix;
+pub use unit::{Unit, UnitDefinition, UnitSId, UnitSIdRef};
 
-pub use model::{parse_document, Model, ModelRaw, ModelUnits};
+pub use document::{Package, SbmlDocument};
+pub use error::{Error, Location, Position, Result};
+pub use model::{parse_document, Model, ModelRaw, ModelUnits, ObjectiveFunction, ObjectiveSense};
 #[cfg(feature = "default")]
 pub use pyo::*;
 
@@ -1,8 +1,8 @@
 use serde::{Deserialize, Serialize};
 
 use super::base_types::{
-    Compartment, Constraint, FunctionDefinition, InitialAssignment, Objective, Parameter, Reaction,
-    Rule, Species,
+    Compartment, Constraint, Event, FunctionDefinition, GeneProduct, InitialAssignment, Objective,
+    Parameter, Reaction, Rule, Species,
 };
 use super::unit::UnitDefinition;
 
@@ -20,6 +20,7 @@ pub struct ListOfCompartments {
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Default, Clone)]
 pub struct ListOfSpecies {
+    #[serde(default)]
     pub species: Vec<Species>,
 }
 
@@ -37,7 +38,7 @@ pub struct ListOfParameters {
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Default, Clone)]
 pub struct ListOfInitialAssignments {
-    #[serde(rename = "initialAssigment", default)]
+    #[serde(rename = "initialAssignment", default)]
     pub initial_assignments: Vec<InitialAssignment>,
 }
 
@@ -49,7 +50,9 @@ pub struct ListOfConstraints {
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Default, Clone)]
 pub struct ListOfObjectives {
-    #[serde(rename = "objective", default)]
+    #[serde(rename = "fbc:activeObjective")]
+    pub active_objective: Option<String>,
+    #[serde(rename = "fbc:objective", alias = "objective", default)]
     pub objectives: Vec<Objective>,
 }
 
@@ -64,3 +67,15 @@ pub struct ListOfFunctionDefinitions {
     #[serde(rename = "functionDefinition", default)]
     pub function_definitions: Vec<FunctionDefinition>,
 }
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Default, Clone)]
+pub struct ListOfGeneProducts {
+    #[serde(rename = "fbc:geneProduct", alias = "geneProduct", default)]
+    pub gene_products: Vec<GeneProduct>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Default, Clone)]
+pub struct ListOfEvents {
+    #[serde(rename = "event", default)]
+    pub events: Vec<Event>,
+}
@@ -0,0 +1,177 @@
+//! Constructors of expressions that simplify the trivial cases: numeric
+//! operands are folded, identity elements dropped and nested sums and
+//! products flattened.
+use super::{Base, Cn, MathNode, NumberType};
+
+/// Value of a number without units.
+pub(super) fn as_number(node: &MathNode) -> Option<f64> {
+    match node {
+        MathNode::Cn(cn) if cn.unit.is_none() => cn.value().ok(),
+        _ => None,
+    }
+}
+
+pub(super) fn is_zero(node: &MathNode) -> bool {
+    as_number(node) == Some(0.)
+}
+
+pub(super) fn num(value: f64) -> MathNode {
+    // no negative zero
+    let value = if value == 0. { 0. } else { value };
+    let (content, cn_type) = if value.fract() == 0. && value.abs() < 1e15 {
+        (format!("{}", value), NumberType::Integer)
+    } else {
+        (format!("{:?}", value), NumberType::Real)
+    };
+    MathNode::Cn(Cn {
+        content,
+        sep_content: None,
+        unit: None,
+        cn_type,
+        base: Base::default(),
+        definition_url: None,
+        encoding: None,
+    })
+}
+
+/// Operands of an apply of `operator`, if `node` is one.
+pub(super) fn operands<'a>(node: &'a MathNode, operator: &MathNode) -> Option<&'a [MathNode]> {
+    match node {
+        MathNode::Apply(apply) if apply.content.first() == Some(operator) => {
+            Some(&apply.content[1..])
+        }
+        _ => None,
+    }
+}
+
+pub(super) fn apply(operator: MathNode, args: Vec<MathNode>) -> MathNode {
+    let mut content = Vec::with_capacity(args.len() + 1);
+    content.push(operator);
+    content.extend(args);
+    MathNode::apply(content)
+}
+
+/// Sum of the `terms`, with the numbers folded into a single last term, or
+/// subtracted if negative.
+pub(super) fn sum(terms: Vec<MathNode>) -> MathNode {
+    let mut constant = 0.;
+    let mut rest = Vec::new();
+    for term in terms {
+        add_term(term, &mut constant, &mut rest);
+    }
+    if rest.is_empty() {
+        return num(constant);
+    }
+    if constant > 0. {
+        rest.push(num(constant));
+    }
+    let terms = match rest.len() {
+        1 => rest.pop().unwrap(),
+        _ => apply(MathNode::Plus, rest),
+    };
+    if constant < 0. {
+        apply(MathNode::Minus, vec![terms, num(-constant)])
+    } else {
+        terms
+    }
+}
+
+fn add_term(term: MathNode, constant: &mut f64, rest: &mut Vec<MathNode>) {
+    if let Some(value) = as_number(&term) {
+        *constant += value;
+    } else if let Some(terms) = operands(&term, &MathNode::Plus) {
+        for term in terms {
+            add_term(term.clone(), constant, rest);
+        }
+    } else if let Some([a, b]) = operands(&term, &MathNode::Minus) {
+        match as_number(b) {
+            Some(value) => {
+                add_term(a.clone(), constant, rest);
+                *constant -= value;
+            }
+            None => rest.push(term),
+        }
+    } else {
+        rest.push(term);
+    }
+}
+
+/// Product of the `factors`, with the numbers folded into a single first
+/// factor. A zero does not cancel the other factors, which may be infinite.
+pub(super) fn product(factors: Vec<MathNode>) -> MathNode {
+    let mut constant = 1.;
+    let mut rest = Vec::new();
+    for factor in factors {
+        multiply_factor(factor, &mut constant, &mut rest);
+    }
+    if rest.is_empty() {
+        return num(constant);
+    }
+    if constant == -1. {
+        let product = product(rest);
+        return neg(product);
+    }
+    if constant != 1. {
+        rest.insert(0, num(constant));
+    }
+    match rest.len() {
+        1 => rest.pop().unwrap(),
+        _ => apply(MathNode::Times, rest),
+    }
+}
+
+fn multiply_factor(factor: MathNode, constant: &mut f64, rest: &mut Vec<MathNode>) {
+    if let Some(value) = as_number(&factor) {
+        *constant *= value;
+    } else if let Some(factors) = operands(&factor, &MathNode::Times) {
+        for factor in factors {
+            multiply_factor(factor.clone(), constant, rest);
+        }
+    } else {
+        rest.push(factor);
+    }
+}
+
+pub(super) fn add(a: MathNode, b: MathNode) -> MathNode {
+    sum(vec![a, b])
+}
+
+pub(super) fn neg(a: MathNode) -> MathNode {
+    if let Some(value) = as_number(&a) {
+        return num(-value);
+    }
+    match operands(&a, &MathNode::Minus) {
+        Some([inner]) => inner.clone(),
+        _ => apply(MathNode::Minus, vec![a]),
+    }
+}
+
+pub(super) fn sub(a: MathNode, b: MathNode) -> MathNode {
+    match (as_number(&a), as_number(&b)) {
+        (_, Some(y)) => sum(vec![a, num(-y)]),
+        (Some(0.), _) => neg(b),
+        _ => apply(MathNode::Minus, vec![a, b]),
+    }
+}
+
+pub(super) fn div(a: MathNode, b: MathNode) -> MathNode {
+    match (as_number(&a), as_number(&b)) {
+        (Some(x), Some(y)) if y != 0. => num(x / y),
+        (_, Some(1.)) => a,
+        _ => apply(MathNode::Divide, vec![a, b]),
+    }
+}
+
+pub(super) fn pow(a: MathNode, b: MathNode) -> MathNode {
+    match (as_number(&a), as_number(&b)) {
+        (Some(x), Some(y)) if x.powf(y).is_finite() => num(x.powf(y)),
+        (_, Some(0.)) => num(1.),
+        (_, Some(1.)) => a,
+        _ => apply(MathNode::Power, vec![a, b]),
+    }
+}
+
+/// Unary function, such as [`MathNode::Sin`], applied to `a`.
+pub(super) fn call(function: MathNode, a: MathNode) -> MathNode {
+    apply(function, vec![a])
+}
@@ -0,0 +1,251 @@
+use super::algebra::{self, add, apply, as_number, call, is_zero, neg, num, pow, sub, sum};
+use super::{MathNode, AVOGADRO_URL, TIME_URL};
+
+impl MathNode {
+    /// Symbolic derivative of the expression with respect to the identifier
+    /// `var`, or to the simulation time if `var` is [`TIME_URL`].
+    ///
+    /// The result is simplified as it is built: numbers are folded and the
+    /// terms multiplied by zero dropped. Relational, logical and rounding
+    /// operators are piecewise constant, so their derivative is zero, and the
+    /// derivative of a piecewise expression keeps its conditions. Calls to
+    /// function definitions and operators without a rule are left as an
+    /// unevaluated MathML `<diff/>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::mathml::Math;
+    ///
+    /// let rate = Math::from_formula("Vmax * S / (Km + S)").unwrap();
+    /// let derivative = rate.content.derivative("S");
+    /// assert_eq!(
+    ///     derivative.to_formula(),
+    ///     "(Vmax * (Km + S) - Vmax * S) / (Km + S)^2"
+    /// );
+    /// ```
+    pub fn derivative(&self, var: &str) -> MathNode {
+        match self {
+            MathNode::Ci { content, .. } => num(if content.trim() == var { 1. } else { 0. }),
+            MathNode::Csymbol {
+                definition_url: Some(url),
+                ..
+            } if url == TIME_URL => num(if var == TIME_URL { 1. } else { 0. }),
+            MathNode::Csymbol {
+                definition_url: Some(url),
+                ..
+            } if url == AVOGADRO_URL => num(0.),
+            MathNode::Cn(_)
+            | MathNode::True
+            | MathNode::False
+            | MathNode::Exponentiale
+            | MathNode::Infinity
+            | MathNode::Pi
+            | MathNode::Notanumber => num(0.),
+            MathNode::Semantics { children, .. } if !children.is_empty() => {
+                children[0].derivative(var)
+            }
+            MathNode::Piecewise { children } => MathNode::Piecewise {
+                children: children
+                    .iter()
+                    .map(|child| match child {
+                        MathNode::Piece { children } if children.len() == 2 => MathNode::Piece {
+                            children: vec![children[0].derivative(var), children[1].clone()],
+                        },
+                        MathNode::Otherwise { children } if children.len() == 1 => {
+                            MathNode::Otherwise {
+                                children: vec![children[0].derivative(var)],
+                            }
+                        }
+                        child => child.clone(),
+                    })
+                    .collect(),
+            },
+            MathNode::Apply(apply) => match apply.content.split_first() {
+                Some((operator, args)) => {
+                    derive_apply(operator, args, var).unwrap_or_else(|| self.unevaluated(var))
+                }
+                None => self.unevaluated(var),
+            },
+            _ => self.unevaluated(var),
+        }
+    }
+
+    /// `<apply><diff/><bvar><ci> var </ci></bvar> self </apply>`
+    fn unevaluated(&self, var: &str) -> MathNode {
+        apply(
+            MathNode::Diff,
+            vec![
+                MathNode::Bvar {
+                    children: vec![MathNode::Ci {
+                        content: var.to_string(),
+                        ci_type: None,
+                    }],
+                },
+                self.clone(),
+            ],
+        )
+    }
+}
+
+/// Derivative of `operator` applied to `args`, if there is a rule for it.
+fn derive_apply(operator: &MathNode, args: &[MathNode], var: &str) -> Option<MathNode> {
+    let d = |i: usize| args[i].derivative(var);
+    let f = |i: usize| args[i].clone();
+    // chain rule for the unary functions: f'(g) g'
+    let chain = |outer: MathNode| {
+        let inner = d(0);
+        if is_zero(&inner) {
+            num(0.)
+        } else {
+            mul(outer, inner)
+        }
+    };
+    let node = match (operator, args.len()) {
+        (MathNode::Plus, _) => sum((0..args.len()).map(d).collect()),
+        (MathNode::Minus, 1) => neg(d(0)),
+        (MathNode::Minus, 2) => sub(d(0), d(1)),
+        (MathNode::Times, _) => sum((0..args.len())
+            .map(|i| {
+                product(
+                    (0..args.len())
+                        .map(|j| if i == j { d(j) } else { f(j) })
+                        .collect(),
+                )
+            })
+            .collect()),
+        (MathNode::Divide, 2) => {
+            let (df, dg) = (d(0), d(1));
+            if is_zero(&dg) {
+                div(df, f(1))
+            } else {
+                div(sub(mul(df, f(1)), mul(f(0), dg)), pow(f(1), num(2.)))
+            }
+        }
+        (MathNode::Power, 2) => {
+            let (df, dg) = (d(0), d(1));
+            if is_zero(&dg) {
+                // n f^(n - 1) f'
+                let exponent = match as_number(&args[1]) {
+                    Some(n) => num(n - 1.),
+                    None => sub(f(1), num(1.)),
+                };
+                product(vec![f(1), pow(f(0), exponent), df])
+            } else {
+                // f^g (g' ln(f) + g f' / f)
+                mul(
+                    pow(f(0), f(1)),
+                    add(mul(dg, call(MathNode::Ln, f(0))), div(mul(f(1), df), f(0))),
+                )
+            }
+        }
+        (MathNode::Root, 1) => chain(div(num(1.), mul(num(2.), call(MathNode::Root, f(0))))),
+        (MathNode::Root, 2) => {
+            // the degree must not depend on var
+            let degree = match &args[0] {
+                MathNode::Degree { children } if children.len() == 1 => children[0].clone(),
+                _ => return None,
+            };
+            if !is_zero(&degree.derivative(var)) {
+                return None;
+            }
+            let inner = d(1);
+            if is_zero(&inner) {
+                return Some(num(0.));
+            }
+            let root = apply(MathNode::Root, vec![f(0), f(1)]);
+            div(inner, mul(degree.clone(), pow(root, sub(degree, num(1.)))))
+        }
+        (MathNode::Exp, 1) => chain(call(MathNode::Exp, f(0))),
+        (MathNode::Ln, 1) => chain(div(num(1.), f(0))),
+        (MathNode::Log, 1) => chain(div(num(1.), mul(f(0), call(MathNode::Ln, num(10.))))),
+        (MathNode::Log, 2) => {
+            let base = match &args[0] {
+                MathNode::Logbase { children } if children.len() == 1 => children[0].clone(),
+                _ => return None,
+            };
+            if !is_zero(&base.derivative(var)) {
+                return None;
+            }
+            let inner = d(1);
+            if is_zero(&inner) {
+                return Some(num(0.));
+            }
+            div(inner, mul(f(1), call(MathNode::Ln, base)))
+        }
+        (MathNode::Abs, 1) => chain(div(f(0), call(MathNode::Abs, f(0)))),
+        (MathNode::Sin, 1) => chain(call(MathNode::Cos, f(0))),
+        (MathNode::Cos, 1) => chain(neg(call(MathNode::Sin, f(0)))),
+        (MathNode::Tan, 1) => chain(div(num(1.), pow(call(MathNode::Cos, f(0)), num(2.)))),
+        (MathNode::Sec, 1) => chain(mul(call(MathNode::Sec, f(0)), call(MathNode::Tan, f(0)))),
+        (MathNode::Csc, 1) => chain(neg(mul(
+            call(MathNode::Csc, f(0)),
+            call(MathNode::Cot, f(0)),
+        ))),
+        (MathNode::Cot, 1) => chain(neg(div(num(1.), pow(call(MathNode::Sin, f(0)), num(2.))))),
+        (MathNode::Sinh, 1) => chain(call(MathNode::Cosh, f(0))),
+        (MathNode::Cosh, 1) => chain(call(MathNode::Sinh, f(0))),
+        (MathNode::Tanh, 1) => chain(div(num(1.), pow(call(MathNode::Cosh, f(0)), num(2.)))),
+        (MathNode::Arcsin, 1) => chain(div(
+            num(1.),
+            call(MathNode::Root, sub(num(1.), pow(f(0), num(2.)))),
+        )),
+        (MathNode::Arccos, 1) => chain(neg(div(
+            num(1.),
+            call(MathNode::Root, sub(num(1.), pow(f(0), num(2.)))),
+        ))),
+        (MathNode::Arctan, 1) => chain(div(num(1.), add(num(1.), pow(f(0), num(2.))))),
+        (MathNode::Arcsinh, 1) => chain(div(
+            num(1.),
+            call(MathNode::Root, add(pow(f(0), num(2.)), num(1.))),
+        )),
+        (MathNode::Arccosh, 1) => chain(div(
+            num(1.),
+            call(MathNode::Root, sub(pow(f(0), num(2.)), num(1.))),
+        )),
+        (MathNode::Arctanh, 1) => chain(div(num(1.), sub(num(1.), pow(f(0), num(2.))))),
+        // piecewise constant
+        (
+            MathNode::Floor
+            | MathNode::Ceiling
+            | MathNode::Quotient
+            | MathNode::Eq
+            | MathNode::Neq
+            | MathNode::Gt
+            | MathNode::Lt
+            | MathNode::Geq
+            | MathNode::Leq
+            | MathNode::And
+            | MathNode::Or
+            | MathNode::Xor
+            | MathNode::Not
+            | MathNode::Implies,
+            _,
+        ) => num(0.),
+        _ => return None,
+    };
+    Some(node)
+}
+
+// The rules take the expressions to be finite, so a zero factor cancels the
+// others, unlike in the value-preserving constructors of `algebra`.
+
+fn product(factors: Vec<MathNode>) -> MathNode {
+    if factors.iter().any(is_zero) {
+        num(0.)
+    } else {
+        algebra::product(factors)
+    }
+}
+
+fn mul(a: MathNode, b: MathNode) -> MathNode {
+    product(vec![a, b])
+}
+
+fn div(a: MathNode, b: MathNode) -> MathNode {
+    if is_zero(&a) {
+        a
+    } else {
+        algebra::div(a, b)
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{
+    recursive_functions, Cn, Math, MathNode, NumberType, AVOGADRO, AVOGADRO_URL, TIME_URL,
+};
+use crate::FunctionDefinition;
+
+/// Error raised while evaluating a [`MathNode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// An identifier that is not provided by the environment.
+    UnknownSymbol(String),
+    /// A call to a function that is not defined.
+    UnknownFunction(String),
+    /// An operator or function called with the wrong number of arguments.
+    WrongArity {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A number that could not be parsed.
+    BadNumber(String),
+    /// A piecewise expression with no matching piece nor otherwise.
+    UndefinedPiecewise,
+    /// A node that cannot be evaluated to a number.
+    Unsupported(String),
+    /// A call to a function definition that calls itself, directly or
+    /// through other function definitions.
+    RecursiveFunction(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::UnknownSymbol(id) => write!(f, "unknown symbol `{}`", id),
+            EvalError::UnknownFunction(id) => write!(f, "unknown function `{}`", id),
+            EvalError::WrongArity {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} argument(s) but {} were given",
+                name, expected, found
+            ),
+            EvalError::BadNumber(n) => write!(f, "bad number `{}`", n),
+            EvalError::UndefinedPiecewise => {
+                write!(f, "no piece of the piecewise expression applies")
+            }
+            EvalError::Unsupported(node) => write!(f, "cannot evaluate {}", node),
+            EvalError::RecursiveFunction(id) => {
+                write!(f, "the function definition `{}` calls itself", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+fn truth(value: bool) -> f64 {
+    if value {
+        1.
+    } else {
+        0.
+    }
+}
+
+fn arity(name: &str, args: &[f64], expected: usize) -> Result<(), EvalError> {
+    if args.len() == expected {
+        Ok(())
+    } else {
+        Err(EvalError::WrongArity {
+            name: name.to_string(),
+            expected,
+            found: args.len(),
+        })
+    }
+}
+
+impl Math {
+    /// Shortcut to [`MathNode::evaluate`] on the content.
+    pub fn evaluate(&self, env: &impl Fn(&str) -> Option<f64>) -> Result<f64, EvalError> {
+        self.content.evaluate(env)
+    }
+}
+
+impl MathNode {
+    /// Evaluate the expression, looking up the values of the identifiers in
+    /// `env`. Booleans are represented as 1 (true) and 0 (false).
+    ///
+    /// The simulation time [`MathNode::Csymbol`] is looked up in `env` by its
+    /// `definitionURL`, [`TIME_URL`](super::TIME_URL).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use quick_xml::de::from_str;
+    /// use rust_sbml::mathml::{Math, TIME_URL};
+    ///
+    /// let math: Math = from_str(
+    ///     r#"<math xmlns="http://www.w3.org/1998/Math/MathML">
+    ///     <apply><divide/>
+    ///         <apply><times/><ci> Vmax </ci><ci> S </ci></apply>
+    ///         <apply><plus/><ci> Km </ci><ci> S </ci><csymbol encoding="text"
+    ///             definitionURL="http://www.sbml.org/sbml/symbols/time"> t </csymbol></apply>
+    ///     </apply>
+    ///     </math>"#,
+    /// )
+    /// .unwrap();
+    /// let env = |id: &str| match id {
+    ///     "Vmax" => Some(10.),
+    ///     "S" => Some(2.),
+    ///     "Km" => Some(1.),
+    ///     TIME_URL => Some(1.),
+    ///     _ => None,
+    /// };
+    /// assert_eq!(math.evaluate(&env).unwrap(), 5.);
+    /// ```
+    pub fn evaluate(&self, env: &impl Fn(&str) -> Option<f64>) -> Result<f64, EvalError> {
+        self.evaluate_with_functions(env, &HashMap::new())
+    }
+
+    /// [`MathNode::evaluate`], calling the lambdas of `functions`, by id,
+    /// when they are applied. Calling a function that calls itself, directly
+    /// or through other functions, is an [`EvalError::RecursiveFunction`].
+    pub fn evaluate_with_functions(
+        &self,
+        env: &impl Fn(&str) -> Option<f64>,
+        functions: &HashMap<String, FunctionDefinition>,
+    ) -> Result<f64, EvalError> {
+        self.eval(env, functions, &recursive_functions(functions))
+    }
+
+    fn eval(
+        &self,
+        env: &dyn Fn(&str) -> Option<f64>,
+        functions: &HashMap<String, FunctionDefinition>,
+        recursive: &[String],
+    ) -> Result<f64, EvalError> {
+        match self {
+            MathNode::Cn(cn) => cn.value(),
+            MathNode::Ci { content, .. } => {
+                let id = content.trim();
+                env(id).ok_or_else(|| EvalError::UnknownSymbol(id.to_string()))
+            }
+            MathNode::Csymbol {
+                definition_url: Some(url),
+                ..
+            } if url == TIME_URL => {
+                env(TIME_URL).ok_or_else(|| EvalError::UnknownSymbol(TIME_URL.to_string()))
+            }
+            MathNode::Csymbol {
+                definition_url: Some(url),
+                ..
+            } if url == AVOGADRO_URL => Ok(AVOGADRO),
+            MathNode::Notanumber => Ok(f64::NAN),
+            MathNode::True => Ok(1.),
+            MathNode::False => Ok(0.),
+            MathNode::Exponentiale => Ok(std::f64::consts::E),
+            MathNode::Infinity => Ok(f64::INFINITY),
+            MathNode::Pi => Ok(std::f64::consts::PI),
+            MathNode::Degree { children } | MathNode::Logbase { children }
+                if children.len() == 1 =>
+            {
+                children[0].eval(env, functions, recursive)
+            }
+            MathNode::Semantics { children, .. } => match children.first() {
+                Some(expression) => expression.eval(env, functions, recursive),
+                None => Err(EvalError::Unsupported("empty semantics".to_string())),
+            },
+            MathNode::Piecewise { children } => {
+                for child in children.iter() {
+                    match child {
+                        MathNode::Piece { children } if children.len() == 2 => {
+                            if children[1].eval(env, functions, recursive)? != 0. {
+                                return children[0].eval(env, functions, recursive);
+                            }
+                        }
+                        MathNode::Otherwise { children } if children.len() == 1 => {
+                            return children[0].eval(env, functions, recursive)
+                        }
+                        _ => return Err(EvalError::Unsupported(format!("{:?}", child))),
+                    }
+                }
+                Err(EvalError::UndefinedPiecewise)
+            }
+            MathNode::Apply(apply) => {
+                let (operator, operands) = match apply.content.split_first() {
+                    Some(split) => split,
+                    None => return Err(EvalError::Unsupported("empty apply".to_string())),
+                };
+                let args = || {
+                    operands
+                        .iter()
+                        .map(|arg| arg.eval(env, functions, recursive))
+                        .collect::<Result<Vec<f64>, EvalError>>()
+                };
+                match operator {
+                    MathNode::Ci { content, .. } => {
+                        let id = content.trim();
+                        let function = functions
+                            .get(id)
+                            .ok_or_else(|| EvalError::UnknownFunction(id.to_string()))?;
+                        if recursive.iter().any(|r| r == id) {
+                            return Err(EvalError::RecursiveFunction(id.to_string()));
+                        }
+                        call_lambda(
+                            id,
+                            &function.math.content,
+                            &args()?,
+                            env,
+                            functions,
+                            recursive,
+                        )
+                    }
+                    MathNode::Lambda { .. } => {
+                        call_lambda("lambda", operator, &args()?, env, functions, recursive)
+                    }
+                    // logical operators are evaluated lazily
+                    MathNode::And => {
+                        for arg in operands {
+                            if arg.eval(env, functions, recursive)? == 0. {
+                                return Ok(0.);
+                            }
+                        }
+                        Ok(1.)
+                    }
+                    MathNode::Or => {
+                        for arg in operands {
+                            if arg.eval(env, functions, recursive)? != 0. {
+                                return Ok(1.);
+                            }
+                        }
+                        Ok(0.)
+                    }
+                    _ => apply_operator(operator, &args()?),
+                }
+            }
+            node => Err(EvalError::Unsupported(format!("{:?}", node))),
+        }
+    }
+}
+
+impl Cn {
+    pub(super) fn value(&self) -> Result<f64, EvalError> {
+        let parse = |content: &str| {
+            let content = content.trim();
+            let number = if self.base.0 != 10 {
+                i64::from_str_radix(content, self.base.0)
+                    .map(|n| n as f64)
+                    .ok()
+            } else {
+                content.parse::<f64>().ok()
+            };
+            number.ok_or_else(|| EvalError::BadNumber(content.to_string()))
+        };
+        let sep_content = || {
+            self.sep_content
+                .as_deref()
+                .ok_or_else(|| EvalError::BadNumber(format!("{} without <sep/>", self.content)))
+        };
+        match self.cn_type {
+            NumberType::Real | NumberType::Integer => parse(&self.content),
+            NumberType::ENotation => Ok(parse(&self.content)? * 10f64.powf(parse(sep_content()?)?)),
+            NumberType::Rational => Ok(parse(&self.content)? / parse(sep_content()?)?),
+            _ => Err(EvalError::Unsupported(format!(
+                "{:?} numbers",
+                self.cn_type
+            ))),
+        }
+    }
+}
+
+/// Evaluate the body of a lambda with its bound variables set to `args`.
+fn call_lambda(
+    name: &str,
+    lambda: &MathNode,
+    args: &[f64],
+    env: &dyn Fn(&str) -> Option<f64>,
+    functions: &HashMap<String, FunctionDefinition>,
+    recursive: &[String],
+) -> Result<f64, EvalError> {
+    let children = match lambda {
+        MathNode::Lambda { children } => children,
+        _ => return Err(EvalError::Unsupported(format!("function `{}`", name))),
+    };
+    let (body, bvars) = children
+        .split_last()
+        .ok_or_else(|| EvalError::Unsupported(format!("empty function `{}`", name)))?;
+    let bvars: Vec<&str> = bvars
+        .iter()
+        .filter_map(|bvar| match bvar {
+            MathNode::Bvar { children } => children.iter().find_map(|child| match child {
+                MathNode::Ci { content, .. } => Some(content.trim()),
+                _ => None,
+            }),
+            _ => None,
+        })
+        .collect();
+    if bvars.len() != args.len() {
+        return Err(EvalError::WrongArity {
+            name: name.to_string(),
+            expected: bvars.len(),
+            found: args.len(),
+        });
+    }
+    // the body only sees its arguments and the csymbols
+    let scope = |id: &str| match bvars.iter().position(|bvar| *bvar == id) {
+        Some(i) => Some(args[i]),
+        None if id == TIME_URL => env(id),
+        None => None,
+    };
+    body.eval(&scope, functions, recursive)
+}
+
+pub(super) fn apply_operator(operator: &MathNode, args: &[f64]) -> Result<f64, EvalError> {
+    let unary = |name: &str, f: fn(f64) -> f64| -> Result<f64, EvalError> {
+        arity(name, args, 1)?;
+        Ok(f(args[0]))
+    };
+    let binary = |name: &str, f: fn(f64, f64) -> f64| -> Result<f64, EvalError> {
+        arity(name, args, 2)?;
+        Ok(f(args[0], args[1]))
+    };
+    let chain = |f: fn(f64, f64) -> bool| Ok(truth(args.windows(2).all(|w| f(w[0], w[1]))));
+    match operator {
+        MathNode::Plus => Ok(args.iter().sum()),
+        MathNode::Times => Ok(args.iter().product()),
+        MathNode::Minus => match args.len() {
+            1 => Ok(-args[0]),
+            _ => binary("minus", |a, b| a - b),
+        },
+        MathNode::Divide => binary("divide", |a, b| a / b),
+        MathNode::Power => binary("power", f64::powf),
+        // the degree and the base come first when given
+        MathNode::Root => match args.len() {
+            2 => Ok(args[1].powf(1. / args[0])),
+            _ => unary("root", f64::sqrt),
+        },
+        MathNode::Exp => unary("exp", f64::exp),
+        MathNode::Ln => unary("ln", f64::ln),
+        MathNode::Log => match args.len() {
+            2 => Ok(args[1].log(args[0])),
+            _ => unary("log", f64::log10),
+        },
+        MathNode::Abs => unary("abs", f64::abs),
+        MathNode::Floor => unary("floor", f64::floor),
+        MathNode::Ceiling => unary("ceiling", f64::ceil),
+        MathNode::Factorial => {
+            arity("factorial", args, 1)?;
+            let n = args[0];
+            if n < 0. || n.fract() != 0. {
+                return Err(EvalError::BadNumber(format!("factorial of {}", n)));
+            }
+            // 171! is already beyond f64::MAX
+            if n > 170. {
+                return Ok(f64::INFINITY);
+            }
+            Ok((1..=n as u64).map(|k| k as f64).product())
+        }
+        MathNode::Max => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        MathNode::Min => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        MathNode::Rem => binary("rem", |a, b| a % b),
+        MathNode::Quotient => binary("quotient", |a, b| (a / b).trunc()),
+        MathNode::Sin => unary("sin", f64::sin),
+        MathNode::Cos => unary("cos", f64::cos),
+        MathNode::Tan => unary("tan", f64::tan),
+        MathNode::Sec => unary("sec", |x| 1. / x.cos()),
+        MathNode::Csc => unary("csc", |x| 1. / x.sin()),
+        MathNode::Cot => unary("cot", |x| 1. / x.tan()),
+        MathNode::Sinh => unary("sinh", f64::sinh),
+        MathNode::Cosh => unary("cosh", f64::cosh),
+        MathNode::Tanh => unary("tanh", f64::tanh),
+        MathNode::Sech => unary("sech", |x| 1. / x.cosh()),
+        MathNode::Csch => unary("csch", |x| 1. / x.sinh()),
+        MathNode::Coth => unary("coth", |x| 1. / x.tanh()),
+        MathNode::Arcsin => unary("arcsin", f64::asin),
+        MathNode::Arccos => unary("arccos", f64::acos),
+        MathNode::Arctan => unary("arctan", f64::atan),
+        MathNode::Arcsec => unary("arcsec", |x| (1. / x).acos()),
+        MathNode::Arccsc => unary("arccsc", |x| (1. / x).asin()),
+        MathNode::Arccot => unary("arccot", |x| (1. / x).atan()),
+        MathNode::Arcsinh => unary("arcsinh", f64::asinh),
+        MathNode::Arccosh => unary("arccosh", f64::acosh),
+        MathNode::Arctanh => unary("arctanh", f64::atanh),
+        MathNode::Arcsech => unary("arcsech", |x| (1. / x).acosh()),
+        MathNode::Arccsch => unary("arccsch", |x| (1. / x).asinh()),
+        MathNode::Arccoth => unary("arccoth", |x| (1. / x).atanh()),
+        MathNode::Eq => chain(|a, b| a == b),
+        MathNode::Neq => binary("neq", |a, b| truth(a != b)),
+        MathNode::Gt => chain(|a, b| a > b),
+        MathNode::Lt => chain(|a, b| a < b),
+        MathNode::Geq => chain(|a, b| a >= b),
+        MathNode::Leq => chain(|a, b| a <= b),
+        MathNode::Not => unary("not", |a| truth(a == 0.)),
+        MathNode::Xor => Ok(truth(args.iter().filter(|a| **a != 0.).count() % 2 == 1)),
+        MathNode::Implies => binary("implies", |a, b| truth(a == 0. || b != 0.)),
+        node => Err(EvalError::Unsupported(format!("{:?}", node))),
+    }
+}
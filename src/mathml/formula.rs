@@ -0,0 +1,697 @@
+use std::fmt;
+
+use super::{Base, Cn, Math, MathNode, NumberType, AVOGADRO_URL, TIME_URL};
+use crate::UnitSIdRef;
+
+// precedence of the L3 formula syntax, from loosest to tightest
+const OR: u8 = 1;
+const AND: u8 = 2;
+const RELATIONAL: u8 = 3;
+const SUM: u8 = 4;
+const PRODUCT: u8 = 5;
+const UNARY: u8 = 6;
+const POWER: u8 = 7;
+const ATOM: u8 = 8;
+
+/// Functions of the L3 formula syntax. The first name of each operator is
+/// the one used when rendering; the rest are accepted when parsing.
+const FUNCTIONS: &[(&str, MathNode)] = &[
+    ("abs", MathNode::Abs),
+    ("ceil", MathNode::Ceiling),
+    ("ceiling", MathNode::Ceiling),
+    ("floor", MathNode::Floor),
+    ("exp", MathNode::Exp),
+    ("ln", MathNode::Ln),
+    ("log10", MathNode::Log),
+    ("log", MathNode::Log),
+    ("sqrt", MathNode::Root),
+    ("root", MathNode::Root),
+    ("factorial", MathNode::Factorial),
+    ("max", MathNode::Max),
+    ("min", MathNode::Min),
+    ("rem", MathNode::Rem),
+    ("quotient", MathNode::Quotient),
+    ("xor", MathNode::Xor),
+    ("implies", MathNode::Implies),
+    ("sin", MathNode::Sin),
+    ("cos", MathNode::Cos),
+    ("tan", MathNode::Tan),
+    ("sec", MathNode::Sec),
+    ("csc", MathNode::Csc),
+    ("cot", MathNode::Cot),
+    ("sinh", MathNode::Sinh),
+    ("cosh", MathNode::Cosh),
+    ("tanh", MathNode::Tanh),
+    ("sech", MathNode::Sech),
+    ("csch", MathNode::Csch),
+    ("coth", MathNode::Coth),
+    ("arcsin", MathNode::Arcsin),
+    ("asin", MathNode::Arcsin),
+    ("arccos", MathNode::Arccos),
+    ("acos", MathNode::Arccos),
+    ("arctan", MathNode::Arctan),
+    ("atan", MathNode::Arctan),
+    ("arcsec", MathNode::Arcsec),
+    ("asec", MathNode::Arcsec),
+    ("arccsc", MathNode::Arccsc),
+    ("acsc", MathNode::Arccsc),
+    ("arccot", MathNode::Arccot),
+    ("acot", MathNode::Arccot),
+    ("arcsinh", MathNode::Arcsinh),
+    ("asinh", MathNode::Arcsinh),
+    ("arccosh", MathNode::Arccosh),
+    ("acosh", MathNode::Arccosh),
+    ("arctanh", MathNode::Arctanh),
+    ("atanh", MathNode::Arctanh),
+    ("arcsech", MathNode::Arcsech),
+    ("asech", MathNode::Arcsech),
+    ("arccsch", MathNode::Arccsch),
+    ("acsch", MathNode::Arccsch),
+    ("arccoth", MathNode::Arccoth),
+    ("acoth", MathNode::Arccoth),
+    ("plus", MathNode::Plus),
+    ("minus", MathNode::Minus),
+    ("times", MathNode::Times),
+    ("divide", MathNode::Divide),
+    ("pow", MathNode::Power),
+    ("power", MathNode::Power),
+    ("and", MathNode::And),
+    ("or", MathNode::Or),
+    ("not", MathNode::Not),
+    ("eq", MathNode::Eq),
+    ("neq", MathNode::Neq),
+    ("gt", MathNode::Gt),
+    ("lt", MathNode::Lt),
+    ("geq", MathNode::Geq),
+    ("leq", MathNode::Leq),
+];
+
+/// Error raised while parsing an infix formula.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormulaError {
+    /// Byte offset of the offending token in the formula.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+impl Math {
+    /// Shortcut to [`MathNode::to_formula`] on the content.
+    pub fn to_formula(&self) -> String {
+        self.content.to_formula()
+    }
+
+    /// Parse an infix formula in the SBML Level 3 syntax of libSBML, the
+    /// reverse of [`Math::to_formula`].
+    ///
+    /// Besides the usual arithmetic, `^` is the power, `==`, `!=`, `<`, `>`,
+    /// `<=` and `>=` compare, `&&`, `||` and `!` are the logical operators
+    /// and `time` and `avogadro` are the SBML symbols. `true`, `false`, `pi`,
+    /// `exponentiale`, `INF` and `NaN` are constants, and `root(n, x)` and
+    /// `log(b, x)` take a degree and a base. A number followed by an
+    /// identifier carries units (`3 mole`). Unknown functions are calls to
+    /// function definitions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::mathml::Math;
+    ///
+    /// let math = Math::from_formula("Vmax * S / (Km + S)").unwrap();
+    /// let env = |id: &str| match id {
+    ///     "Vmax" => Some(10.),
+    ///     "S" => Some(2.),
+    ///     "Km" => Some(2.),
+    ///     _ => None,
+    /// };
+    /// assert_eq!(math.evaluate(&env).unwrap(), 5.);
+    /// assert_eq!(math.to_formula(), "Vmax * S / (Km + S)");
+    /// ```
+    pub fn from_formula(formula: &str) -> Result<Math, FormulaError> {
+        let mut parser = Parser {
+            tokens: tokenize(formula)?,
+            next: 0,
+            end: formula.len(),
+        };
+        let content = parser.or()?;
+        match parser.peek() {
+            None => Ok(Math { content }),
+            Some(_) => Err(parser.error("unexpected token")),
+        }
+    }
+}
+
+impl MathNode {
+    /// Render the expression as an infix formula in the SBML Level 3 syntax
+    /// of libSBML, e.g. `k1 * S1 / (Km + S1)`, with only the parentheses
+    /// required by the precedence of the operators.
+    ///
+    /// Parsing the result with [`Math::from_formula`] gives back the same
+    /// tree, except for the representation of the numbers.
+    pub fn to_formula(&self) -> String {
+        self.render().0
+    }
+
+    /// Formula and precedence of the node.
+    fn render(&self) -> (String, u8) {
+        match self {
+            MathNode::Cn(cn) => {
+                let first = match cn.base {
+                    Base(10) => cn.content.trim().to_string(),
+                    Base(base) => i64::from_str_radix(cn.content.trim(), base)
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|_| cn.content.trim().to_string()),
+                };
+                let precedence = if first.starts_with('-') { UNARY } else { ATOM };
+                let (number, precedence) = match (&cn.cn_type, &cn.sep_content) {
+                    (NumberType::ENotation, Some(exponent)) => {
+                        (format!("{}e{}", first, exponent.trim()), precedence)
+                    }
+                    (NumberType::Rational, Some(denominator)) => {
+                        (format!("({}/{})", first, denominator.trim()), ATOM)
+                    }
+                    _ => (first, precedence),
+                };
+                match &cn.unit {
+                    Some(unit) => (format!("{} {}", number, unit), precedence),
+                    None => (number, precedence),
+                }
+            }
+            MathNode::Ci { content, .. } => (content.trim().to_string(), ATOM),
+            MathNode::Csymbol { .. } => (symbol_name(self), ATOM),
+            MathNode::Text(text) => (text.trim().to_string(), ATOM),
+            MathNode::Notanumber => ("NaN".to_string(), ATOM),
+            MathNode::True => ("true".to_string(), ATOM),
+            MathNode::False => ("false".to_string(), ATOM),
+            MathNode::Exponentiale => ("exponentiale".to_string(), ATOM),
+            MathNode::Infinity => ("INF".to_string(), ATOM),
+            MathNode::Pi => ("pi".to_string(), ATOM),
+            // the annotations have no infix form
+            MathNode::Semantics { children, .. } => children
+                .first()
+                .map_or((String::new(), ATOM), MathNode::render),
+            MathNode::Lambda { children } | MathNode::Piecewise { children } => {
+                let name = match self {
+                    MathNode::Lambda { .. } => "lambda",
+                    _ => "piecewise",
+                };
+                (call(name, children), ATOM)
+            }
+            MathNode::Bvar { children }
+            | MathNode::Piece { children }
+            | MathNode::Otherwise { children }
+            | MathNode::Degree { children }
+            | MathNode::Logbase { children } => (join(children), ATOM),
+            MathNode::Apply(apply) => match apply.content.split_first() {
+                Some((operator, args)) => render_apply(operator, args),
+                None => (String::new(), ATOM),
+            },
+            node => (operator_name(node), ATOM),
+        }
+    }
+
+    fn ci(id: &str) -> MathNode {
+        MathNode::Ci {
+            content: id.to_string(),
+            ci_type: None,
+        }
+    }
+}
+
+fn render_apply(operator: &MathNode, args: &[MathNode]) -> (String, u8) {
+    let infix = |separator: &str, precedence: u8| {
+        let terms: Vec<String> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let (term, inner) = arg.render();
+                // a nested chain of the same n-ary operator would be flattened
+                let nested = is_n_ary(operator)
+                    && matches!(arg, MathNode::Apply(apply)
+                        if apply.content.first() == Some(operator));
+                parenthesize(
+                    term,
+                    inner < precedence || (i > 0 && inner == precedence) || nested,
+                )
+            })
+            .collect();
+        (terms.join(separator), precedence)
+    };
+    let prefix = |symbol: &str| {
+        let (term, inner) = args[0].render();
+        (
+            format!("{}{}", symbol, parenthesize(term, inner < UNARY)),
+            UNARY,
+        )
+    };
+    match (operator, args.len()) {
+        (MathNode::Plus, n) if n >= 2 => infix(" + ", SUM),
+        (MathNode::Minus, 2) => infix(" - ", SUM),
+        (MathNode::Minus, 1) => prefix("-"),
+        (MathNode::Times, n) if n >= 2 => infix(" * ", PRODUCT),
+        (MathNode::Divide, 2) => infix(" / ", PRODUCT),
+        (MathNode::And, n) if n >= 2 => infix(" && ", AND),
+        (MathNode::Or, n) if n >= 2 => infix(" || ", OR),
+        (MathNode::Not, 1) => prefix("!"),
+        (MathNode::Neq, 2) => infix(" != ", RELATIONAL),
+        (MathNode::Eq, n) if n >= 2 => infix(" == ", RELATIONAL),
+        (MathNode::Gt, n) if n >= 2 => infix(" > ", RELATIONAL),
+        (MathNode::Lt, n) if n >= 2 => infix(" < ", RELATIONAL),
+        (MathNode::Geq, n) if n >= 2 => infix(" >= ", RELATIONAL),
+        (MathNode::Leq, n) if n >= 2 => infix(" <= ", RELATIONAL),
+        (MathNode::Power, 2) => {
+            // right-associative, and the exponent may be negated
+            let (base, base_precedence) = args[0].render();
+            let (exponent, exponent_precedence) = args[1].render();
+            (
+                format!(
+                    "{}^{}",
+                    parenthesize(base, base_precedence <= POWER),
+                    parenthesize(exponent, exponent_precedence < UNARY)
+                ),
+                POWER,
+            )
+        }
+        // with a degree or a base
+        (MathNode::Root, 2) => (call("root", args), ATOM),
+        (MathNode::Log, 2) => (call("log", args), ATOM),
+        (MathNode::Ci { content, .. }, _) => (call(content.trim(), args), ATOM),
+        (MathNode::Csymbol { .. }, _) => (call(&symbol_name(operator), args), ATOM),
+        _ => (call(&operator_name(operator), args), ATOM),
+    }
+}
+
+/// Infix operators that take any number of operands.
+fn is_n_ary(operator: &MathNode) -> bool {
+    !matches!(
+        operator,
+        MathNode::Minus | MathNode::Divide | MathNode::Neq | MathNode::Power
+    )
+}
+
+fn parenthesize(term: String, needed: bool) -> String {
+    if needed {
+        format!("({})", term)
+    } else {
+        term
+    }
+}
+
+fn join(args: &[MathNode]) -> String {
+    args.iter()
+        .map(MathNode::to_formula)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn call(name: &str, args: &[MathNode]) -> String {
+    format!("{}({})", name, join(args))
+}
+
+fn symbol_name(csymbol: &MathNode) -> String {
+    match csymbol {
+        MathNode::Csymbol {
+            definition_url: Some(url),
+            ..
+        } if url == TIME_URL => "time".to_string(),
+        MathNode::Csymbol {
+            definition_url: Some(url),
+            ..
+        } if url == AVOGADRO_URL => "avogadro".to_string(),
+        MathNode::Csymbol {
+            definition_url,
+            content,
+            ..
+        } => match (content.trim(), definition_url) {
+            ("", Some(url)) => url.rsplit('/').next().unwrap_or_default().to_string(),
+            (content, _) => content.to_string(),
+        },
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn operator_name(operator: &MathNode) -> String {
+    match FUNCTIONS.iter().find(|(_, node)| node == operator) {
+        Some((name, _)) => name.to_string(),
+        None => format!("{:?}", operator).to_lowercase(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Id(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 17] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "^", "(", ")", ",",
+];
+
+fn tokenize(formula: &str) -> Result<Vec<(usize, Token)>, FormulaError> {
+    let bytes = formula.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            // digits, at most one `.` and an exponent with digits
+            let digits = |mut i: usize| {
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                i
+            };
+            i = digits(i);
+            if i < bytes.len() && bytes[i] == b'.' {
+                i = digits(i + 1);
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if digits(j) == j {
+                    return Err(FormulaError {
+                        position: j,
+                        message: "expected the digits of the exponent".to_string(),
+                    });
+                }
+                i = digits(j);
+            }
+            if i < bytes.len() && bytes[i] == b'.' {
+                return Err(FormulaError {
+                    position: i,
+                    message: format!("malformed number `{}.`", &formula[start..i]),
+                });
+            }
+            tokens.push((start, Token::Number(formula[start..i].to_string())));
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((start, Token::Id(formula[start..i].to_string())));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| formula[i..].starts_with(*symbol))
+                .ok_or_else(|| FormulaError {
+                    position: i,
+                    message: format!(
+                        "unexpected character `{}`",
+                        formula[i..].chars().next().unwrap_or_default()
+                    ),
+                })?;
+            i += symbol.len();
+            tokens.push((start, Token::Symbol(symbol)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one method per level of precedence.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn error(&self, message: &str) -> FormulaError {
+        FormulaError {
+            position: self.tokens.get(self.next).map_or(self.end, |(i, _)| *i),
+            message: message.to_string(),
+        }
+    }
+
+    /// Consume the next token if it is one of `symbols`.
+    fn eat(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Symbol(symbol)) if symbols.contains(symbol) => {
+                let symbol = *symbol;
+                self.next += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), FormulaError> {
+        self.eat(&[symbol])
+            .map(|_| ())
+            .ok_or_else(|| self.error(&format!("expected `{}`", symbol)))
+    }
+
+    /// Left-associative chain of the binary `operators` over `operand`.
+    /// Repeated n-ary operators are collected in a single apply.
+    fn chain(
+        &mut self,
+        operators: &[(&'static str, MathNode)],
+        operand: fn(&mut Self) -> Result<MathNode, FormulaError>,
+    ) -> Result<MathNode, FormulaError> {
+        let symbols: Vec<&'static str> = operators.iter().map(|(symbol, _)| *symbol).collect();
+        let mut lhs = operand(self)?;
+        let mut last: Option<&'static str> = None;
+        while let Some(symbol) = self.eat(&symbols) {
+            let rhs = operand(self)?;
+            let operator = &operators.iter().find(|(s, _)| *s == symbol).unwrap().1;
+            match &mut lhs {
+                MathNode::Apply(apply) if is_n_ary(operator) && last == Some(symbol) => {
+                    apply.content.push(rhs)
+                }
+                _ => lhs = MathNode::apply(vec![operator.clone(), lhs, rhs]),
+            }
+            last = Some(symbol);
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<MathNode, FormulaError> {
+        self.chain(&[("||", MathNode::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<MathNode, FormulaError> {
+        self.chain(&[("&&", MathNode::And)], Self::relational)
+    }
+
+    fn relational(&mut self) -> Result<MathNode, FormulaError> {
+        self.chain(
+            &[
+                ("==", MathNode::Eq),
+                ("!=", MathNode::Neq),
+                ("<=", MathNode::Leq),
+                (">=", MathNode::Geq),
+                ("<", MathNode::Lt),
+                (">", MathNode::Gt),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Result<MathNode, FormulaError> {
+        self.chain(
+            &[("+", MathNode::Plus), ("-", MathNode::Minus)],
+            Self::product,
+        )
+    }
+
+    fn product(&mut self) -> Result<MathNode, FormulaError> {
+        self.chain(
+            &[("*", MathNode::Times), ("/", MathNode::Divide)],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<MathNode, FormulaError> {
+        match self.eat(&["-", "!", "+"]) {
+            Some("-") => Ok(MathNode::apply(vec![MathNode::Minus, self.unary()?])),
+            Some("!") => Ok(MathNode::apply(vec![MathNode::Not, self.unary()?])),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<MathNode, FormulaError> {
+        let base = self.primary()?;
+        match self.eat(&["^"]) {
+            Some(_) => Ok(MathNode::apply(vec![MathNode::Power, base, self.unary()?])),
+            None => Ok(base),
+        }
+    }
+
+    fn primary(&mut self) -> Result<MathNode, FormulaError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end of formula"))?;
+        match token {
+            Token::Symbol("(") => {
+                self.next += 1;
+                let inner = self.or()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Number(content) => {
+                self.next += 1;
+                let unit = match self.peek() {
+                    Some(Token::Id(unit)) => {
+                        let unit = UnitSIdRef::from(unit.as_str());
+                        self.next += 1;
+                        Some(unit)
+                    }
+                    _ => None,
+                };
+                let (content, sep_content, cn_type) = match content.split_once(['e', 'E']) {
+                    Some((mantissa, exponent)) => (
+                        mantissa.to_string(),
+                        Some(exponent.to_string()),
+                        NumberType::ENotation,
+                    ),
+                    None if content.contains('.') => (content, None, NumberType::Real),
+                    None => (content, None, NumberType::Integer),
+                };
+                Ok(MathNode::Cn(Cn {
+                    content,
+                    sep_content,
+                    unit,
+                    cn_type,
+                    base: Base::default(),
+                    definition_url: None,
+                    encoding: None,
+                }))
+            }
+            Token::Id(id) => {
+                let position = self.next;
+                self.next += 1;
+                if self.eat(&["("]).is_some() {
+                    let args = self.arguments()?;
+                    self.function(&id, args).map_err(|message| FormulaError {
+                        position: self.tokens[position].0,
+                        message,
+                    })
+                } else {
+                    Ok(constant(&id))
+                }
+            }
+            Token::Symbol(_) => Err(self.error("unexpected token")),
+        }
+    }
+
+    /// Comma-separated arguments after the opening parenthesis of a call.
+    fn arguments(&mut self) -> Result<Vec<MathNode>, FormulaError> {
+        let mut args = Vec::new();
+        if self.eat(&[")"]).is_some() {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.or()?);
+            if self.eat(&[","]).is_none() {
+                self.expect(")")?;
+                return Ok(args);
+            }
+        }
+    }
+
+    fn function(&self, name: &str, args: Vec<MathNode>) -> Result<MathNode, String> {
+        match name {
+            "piecewise" if !args.is_empty() => {
+                let mut args = args.into_iter();
+                let mut children = Vec::new();
+                while let Some(value) = args.next() {
+                    children.push(match args.next() {
+                        Some(condition) => MathNode::Piece {
+                            children: vec![value, condition],
+                        },
+                        None => MathNode::Otherwise {
+                            children: vec![value],
+                        },
+                    });
+                }
+                Ok(MathNode::Piecewise { children })
+            }
+            "lambda" if !args.is_empty() => {
+                let mut children = args;
+                let body = children.pop().unwrap();
+                let mut children = children
+                    .into_iter()
+                    .map(|bvar| match bvar {
+                        MathNode::Ci { .. } => Ok(MathNode::Bvar {
+                            children: vec![bvar],
+                        }),
+                        _ => Err("the arguments of a lambda must be identifiers".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                children.push(body);
+                Ok(MathNode::Lambda { children })
+            }
+            "piecewise" | "lambda" => Err(format!("`{}` needs at least one argument", name)),
+            "root" | "log" if args.len() == 2 => {
+                let mut args = args.into_iter();
+                let first = vec![args.next().unwrap()];
+                let (operator, qualifier) = match name {
+                    "root" => (MathNode::Root, MathNode::Degree { children: first }),
+                    _ => (MathNode::Log, MathNode::Logbase { children: first }),
+                };
+                Ok(MathNode::apply(vec![
+                    operator,
+                    qualifier,
+                    args.next().unwrap(),
+                ]))
+            }
+            "root" | "log" if args.len() != 1 => {
+                Err(format!("`{}` takes one or two arguments", name))
+            }
+            "sqrt" | "log10" if args.len() != 1 => {
+                Err(format!("`{}` takes exactly one argument", name))
+            }
+            _ => {
+                let operator = FUNCTIONS
+                    .iter()
+                    .find(|(function, _)| *function == name)
+                    .map(|(_, operator)| operator.clone())
+                    .unwrap_or_else(|| MathNode::ci(name));
+                let mut content = vec![operator];
+                content.extend(args);
+                Ok(MathNode::apply(content))
+            }
+        }
+    }
+}
+
+/// Identifier that is not followed by arguments.
+fn constant(id: &str) -> MathNode {
+    let csymbol = |url: &str| MathNode::Csymbol {
+        cd: None,
+        encoding: Some("text".to_string()),
+        definition_url: Some(url.to_string()),
+        content: id.to_string(),
+    };
+    match id {
+        "time" => csymbol(TIME_URL),
+        "avogadro" => csymbol(AVOGADRO_URL),
+        "NaN" | "notanumber" => MathNode::Notanumber,
+        "true" => MathNode::True,
+        "false" => MathNode::False,
+        "exponentiale" => MathNode::Exponentiale,
+        "INF" | "infinity" => MathNode::Infinity,
+        "pi" => MathNode::Pi,
+        _ => MathNode::ci(id),
+    }
+}
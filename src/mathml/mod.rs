@@ -0,0 +1,478 @@
+mod algebra;
+mod derivative;
+mod eval;
+mod formula;
+mod simplify;
+
+pub use eval::EvalError;
+pub(crate) use formula::operator_name;
+pub use formula::FormulaError;
+pub(crate) use simplify::{called_functions, recursive_functions};
+
+use super::UnitSIdRef;
+use serde::de::{EnumAccess, VariantAccess, Visitor};
+use serde::ser::{SerializeStruct, SerializeStructVariant};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+const MATHML_NS: &str = "http://www.w3.org/1998/Math/MathML";
+/// `definitionURL` of the [`MathNode::Csymbol`] for the simulation time.
+pub const TIME_URL: &str = "http://www.sbml.org/sbml/symbols/time";
+/// `definitionURL` of the [`MathNode::Csymbol`] for the Avogadro constant.
+pub const AVOGADRO_URL: &str = "http://www.sbml.org/sbml/symbols/avogadro";
+/// Value of the Avogadro constant, as defined by SBML Level 3 Version 2, for
+/// both the [`AVOGADRO_URL`] csymbol and the `avogadro` unit.
+pub const AVOGADRO: f64 = 6.022_140_76e23;
+
+/// Math attribute which contains MathNodes of the very partially implemented
+/// [MathML version 3.0 spec](https://www.w3.org/TR/2014/REC-MathML3-20140410).
+#[derive(Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct Math {
+    #[serde(rename = "$value")]
+    pub content: MathNode,
+}
+
+impl Serialize for Math {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut math = serializer.serialize_struct("math", 2)?;
+        math.serialize_field("xmlns", MATHML_NS)?;
+        math.serialize_field("$value", &self.content)?;
+        math.end()
+    }
+}
+
+/// Content identifier <ci>
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Apply {
+    #[serde(rename = "$value")]
+    pub content: Vec<MathNode>,
+}
+
+/// Content identifier <ci>
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Ci {
+    #[serde(rename = "$value")]
+    content: String,
+    #[serde(rename = "type")]
+    ci_type: Option<String>,
+}
+
+/// Content identifier <ci>
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+#[serde(rename = "lowercase")]
+pub struct Bvar {
+    #[serde(rename = "$value")]
+    ci: Ci,
+    ci_type: Option<String>,
+}
+
+/// Number type (default to Real)
+#[derive(Debug, Serialize, Eq, PartialEq, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum NumberType {
+    #[default]
+    Real,
+    Integer,
+    Rational,
+    #[serde(rename = "complex-cartesian")]
+    ComplexCartesian,
+    #[serde(rename = "complex-polar")]
+    ComplexPolar,
+    Constant,
+    /// Mantissa and base 10 exponent, separated by `<sep/>`.
+    #[serde(rename = "e-notation")]
+    ENotation,
+}
+
+impl NumberType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NumberType::Real => "real",
+            NumberType::Integer => "integer",
+            NumberType::Rational => "rational",
+            NumberType::ComplexCartesian => "complex-cartesian",
+            NumberType::ComplexPolar => "complex-polar",
+            NumberType::Constant => "constant",
+            NumberType::ENotation => "e-notation",
+        }
+    }
+}
+
+/// Base of a number (default to 10)
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Base(pub u32);
+
+impl Default for Base {
+    fn default() -> Self {
+        Base(10)
+    }
+}
+
+/// Numbers <cn>
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone)]
+#[serde(from = "CnRaw")]
+pub struct Cn {
+    pub content: String,
+    /// Content after the `<sep/>` of `e-notation` (the exponent) and
+    /// `rational` (the denominator) numbers.
+    pub sep_content: Option<String>,
+    pub unit: Option<UnitSIdRef>,
+    pub cn_type: NumberType,
+    pub base: Base,
+    pub definition_url: Option<String>,
+    pub encoding: Option<String>,
+}
+
+/// Write the fields of `cn` to `node`, either a struct or a struct variant.
+macro_rules! serialize_cn_fields {
+    ($node:ident, $cn:expr) => {{
+        let cn: &Cn = $cn;
+        if cn.cn_type != NumberType::Real {
+            $node.serialize_field("type", cn.cn_type.as_str())?;
+        }
+        if cn.base != Base::default() {
+            $node.serialize_field("base", &cn.base.0)?;
+        }
+        $node.serialize_field("sbml:units", &cn.unit)?;
+        $node.serialize_field("definitionURL", &cn.definition_url)?;
+        $node.serialize_field("encoding", &cn.encoding)?;
+        match &cn.sep_content {
+            Some(sep_content) => $node.serialize_field(
+                "$value",
+                &vec![
+                    CnPart::Text(cn.content.clone()),
+                    CnPart::Sep,
+                    CnPart::Text(sep_content.clone()),
+                ],
+            )?,
+            None => $node.serialize_field("$value", &cn.content)?,
+        }
+        $node.end()
+    }};
+}
+
+impl Serialize for Cn {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut cn = serializer.serialize_struct("cn", 6)?;
+        serialize_cn_fields!(cn, self)
+    }
+}
+
+#[derive(Deserialize)]
+struct CnRaw {
+    #[serde(rename = "$value", default)]
+    content: Vec<CnPart>,
+    #[serde(rename = "sbml:units")]
+    unit: Option<UnitSIdRef>,
+    #[serde(rename = "type", default)]
+    cn_type: NumberType,
+    #[serde(default)]
+    base: Base,
+    #[serde(rename = "definitionURL")]
+    definition_url: Option<String>,
+    encoding: Option<String>,
+}
+
+impl From<CnRaw> for Cn {
+    fn from(raw: CnRaw) -> Self {
+        let mut parts = raw.content.split(|part| *part == CnPart::Sep).map(|texts| {
+            texts
+                .iter()
+                .filter_map(|part| match part {
+                    CnPart::Text(text) => Some(text.as_str()),
+                    CnPart::Sep => None,
+                })
+                .collect::<String>()
+        });
+        Cn {
+            content: parts.next().unwrap_or_default(),
+            sep_content: parts.next(),
+            unit: raw.unit,
+            cn_type: raw.cn_type,
+            base: raw.base,
+            definition_url: raw.definition_url,
+            encoding: raw.encoding,
+        }
+    }
+}
+
+/// Mixed content of a <cn>: text and <sep/> elements.
+#[derive(Debug, PartialEq)]
+enum CnPart {
+    Text(String),
+    Sep,
+}
+
+// quick-xml reads text in a sequence as a variant named after the text itself
+impl<'de> Deserialize<'de> for CnPart {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CnPartVisitor;
+
+        impl<'de> Visitor<'de> for CnPartVisitor {
+            type Value = CnPart;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "text or <sep/>")
+            }
+
+            fn visit_enum<A>(self, data: A) -> Result<CnPart, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (name, variant): (String, _) = data.variant()?;
+                variant.unit_variant()?;
+                Ok(match name.as_str() {
+                    "sep" => CnPart::Sep,
+                    _ => CnPart::Text(name),
+                })
+            }
+        }
+
+        deserializer.deserialize_enum("CnPart", &["sep"], CnPartVisitor)
+    }
+}
+
+impl Serialize for CnPart {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            CnPart::Text(text) => serializer.serialize_str(text),
+            CnPart::Sep => serializer.serialize_unit_variant("CnPart", 1, "sep"),
+        }
+    }
+}
+
+/// Main node of MathML
+///
+/// Very partial implementation
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+#[serde(remote = "Self", rename_all = "camelCase")]
+pub enum MathNode {
+    Apply(Box<Apply>),
+    Text(String),
+    Ci {
+        #[serde(rename = "$value")]
+        content: String,
+        #[serde(rename = "type")]
+        ci_type: Option<String>,
+    },
+    /// SBML symbols such as the simulation time; see [`TIME_URL`] and
+    /// [`AVOGADRO_URL`].
+    Csymbol {
+        cd: Option<String>,
+        encoding: Option<String>,
+        #[serde(rename = "definitionURL")]
+        definition_url: Option<String>,
+        #[serde(rename = "$value", default)]
+        content: String,
+    },
+    Cn(Cn),
+    Comment(String),
+    PI(String, Option<String>),
+    Lambda {
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    Notanumber,
+    /// Bound variable of a [`MathNode::Lambda`].
+    Bvar {
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    /// Conditional expression made of [`MathNode::Piece`]s, tried in order,
+    /// and an optional [`MathNode::Otherwise`].
+    Piecewise {
+        #[serde(rename = "$value", default)]
+        children: Vec<MathNode>,
+    },
+    /// Value and condition of a [`MathNode::Piecewise`].
+    Piece {
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    /// Value of a [`MathNode::Piecewise`] when no condition holds.
+    Otherwise {
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    /// Degree of a [`MathNode::Root`], the first operand of its apply
+    /// (default to 2).
+    Degree {
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    /// Base of a [`MathNode::Log`], the first operand of its apply
+    /// (default to 10).
+    Logbase {
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    /// Expression followed by its [`MathNode::Annotation`]s.
+    Semantics {
+        #[serde(rename = "definitionURL")]
+        definition_url: Option<String>,
+        encoding: Option<String>,
+        #[serde(rename = "$value")]
+        children: Vec<MathNode>,
+    },
+    /// Textual annotation of a [`MathNode::Semantics`].
+    Annotation {
+        encoding: Option<String>,
+        #[serde(rename = "$value", default)]
+        content: String,
+    },
+    // constants
+    True,
+    False,
+    Exponentiale,
+    Infinity,
+    Pi,
+    // rest of operations
+    Factorial,
+    Minus,
+    Abs,
+    Conjugate,
+    Arg,
+    Real,
+    Imaginary,
+    Floor,
+    Ceiling,
+    Not,
+    Inverse,
+    Ident,
+    Domain,
+    Codomain,
+    Image,
+    Sin,
+    Cos,
+    Tan,
+    Sec,
+    Csc,
+    Cot,
+    Sinh,
+    Cosh,
+    Tanh,
+    Sech,
+    Csch,
+    Coth,
+    Arcsin,
+    Arccos,
+    Arctan,
+    Arccosh,
+    Arccot,
+    Arccoth,
+    Arccsc,
+    Arccsch,
+    Arcsec,
+    Arcsech,
+    Arcsinh,
+    Arctanh,
+    Exp,
+    Ln,
+    Log,
+    Determinant,
+    Transpose,
+    Divergence,
+    Grad,
+    Curl,
+    Laplacian,
+    Card,
+    Quotient,
+    Divide,
+    Power,
+    Rem,
+    Implies,
+    Equivalent,
+    Approx,
+    Setdiff,
+    Vectorproduct,
+    Scalarproduct,
+    Outerproduct,
+    Plus,
+    Times,
+    Max,
+    Min,
+    Gcd,
+    Lcm,
+    Mean,
+    Sdev,
+    Variance,
+    Median,
+    Mode,
+    And,
+    Or,
+    Xor,
+    Selector,
+    Union,
+    Intersect,
+    Cartesianproduct,
+    Compose,
+    #[serde(rename = "fn")]
+    Fun,
+    Int,
+    Sum,
+    Product,
+    Diff,
+    Partialdiff,
+    Forall,
+    Exists,
+    Eq,
+    Neq,
+    Gt,
+    Lt,
+    Geq,
+    Leq,
+    Root,
+}
+
+impl MathNode {
+    pub fn apply(x: Vec<MathNode>) -> Self {
+        MathNode::Apply(Box::new(Apply { content: x }))
+    }
+}
+
+// The derived implementations are generated as inherent functions
+// (`remote = "Self"`) so that the newtype variants, which would be written
+// wrapped in an extra element named after the inner type (`<apply><Apply>`),
+// can be written by hand.
+
+impl<'de> Deserialize<'de> for MathNode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        MathNode::deserialize(deserializer)
+    }
+}
+
+impl Serialize for MathNode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            MathNode::Apply(apply) => {
+                let mut node = serializer.serialize_struct_variant("MathNode", 0, "apply", 1)?;
+                node.serialize_field("$value", &apply.content)?;
+                node.end()
+            }
+            MathNode::Text(text) => serializer.serialize_str(text),
+            MathNode::Cn(cn) => {
+                let mut node = serializer.serialize_struct_variant("MathNode", 4, "cn", 6)?;
+                serialize_cn_fields!(node, cn)
+            }
+            _ => MathNode::serialize(self, serializer),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::algebra::{apply, as_number, div, neg, num, pow, product, sub, sum};
+use super::eval::apply_operator;
+use super::MathNode;
+use crate::FunctionDefinition;
+
+impl MathNode {
+    /// Algebraic simplification of the expression: numeric constants are
+    /// folded (relations into `true`/`false`), nested sums and products
+    /// flattened, identity elements (`+ 0`, `* 1`, `^ 1`...) dropped and the
+    /// pieces of a piecewise with a constant condition resolved. The value is
+    /// kept for infinite operands: powers are only folded to finite numbers,
+    /// and `0 * x`, `0 / x` or `x - x` are left as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::mathml::Math;
+    ///
+    /// let math = Math::from_formula("(1 * x + 0) * (2 * (y * 3)) + 2^3 - 1").unwrap();
+    /// assert_eq!(math.content.simplify().to_formula(), "6 * x * y + 7");
+    /// ```
+    pub fn simplify(&self) -> MathNode {
+        self.simplify_with_functions(&HashMap::new())
+    }
+
+    /// [`MathNode::simplify`], inlining the lambdas of `functions` where
+    /// they are called. The calls to functions that call themselves,
+    /// directly or through other functions, are left as they are.
+    pub fn simplify_with_functions(
+        &self,
+        functions: &HashMap<String, FunctionDefinition>,
+    ) -> MathNode {
+        let recursive = recursive_functions(functions);
+        let inlined: Inlined = functions
+            .iter()
+            .filter(|(id, _)| !recursive.contains(id))
+            .map(|(id, function)| (id.as_str(), function))
+            .collect();
+        simplify_node(self, &inlined)
+    }
+
+    /// Replace the identifiers found in `bindings` by their expressions.
+    /// The bound variables of a lambda are not replaced inside of it.
+    pub fn substitute(&self, bindings: &HashMap<String, MathNode>) -> MathNode {
+        match self {
+            MathNode::Ci { content, .. } => bindings
+                .get(content.trim())
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            MathNode::Lambda { .. } => self.clone(),
+            // the operator is a function, not a variable
+            MathNode::Apply(node) => match node.content.split_first() {
+                Some((operator, args)) => apply(
+                    operator.clone(),
+                    args.iter().map(|arg| arg.substitute(bindings)).collect(),
+                ),
+                None => self.clone(),
+            },
+            _ => map_children(self, &|child| child.substitute(bindings)),
+        }
+    }
+
+    /// Identifiers that the expression reads: the `ci`s that are not the
+    /// function of an apply.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::mathml::Math;
+    ///
+    /// let math = Math::from_formula("f(k1 * S) + k1").unwrap();
+    /// let mut identifiers: Vec<String> = math.content.identifiers().into_iter().collect();
+    /// identifiers.sort();
+    /// assert_eq!(identifiers, vec!["S", "k1"]);
+    /// ```
+    pub fn identifiers(&self) -> HashSet<String> {
+        let mut identifiers = HashSet::new();
+        collect_identifiers(self, &mut identifiers);
+        identifiers
+    }
+}
+
+fn collect_identifiers(node: &MathNode, identifiers: &mut HashSet<String>) {
+    match node {
+        MathNode::Ci { content, .. } => {
+            identifiers.insert(content.trim().to_string());
+        }
+        MathNode::Apply(apply) => {
+            for (i, child) in apply.content.iter().enumerate() {
+                if i > 0 || !matches!(child, MathNode::Ci { .. }) {
+                    collect_identifiers(child, identifiers);
+                }
+            }
+        }
+        // the variables of a lambda are bound
+        MathNode::Piecewise { children }
+        | MathNode::Piece { children }
+        | MathNode::Otherwise { children }
+        | MathNode::Degree { children }
+        | MathNode::Logbase { children }
+        | MathNode::Semantics { children, .. } => {
+            for child in children {
+                collect_identifiers(child, identifiers);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Function definitions that can be inlined, by id.
+type Inlined<'a> = HashMap<&'a str, &'a FunctionDefinition>;
+
+fn simplify_node(node: &MathNode, functions: &Inlined) -> MathNode {
+    match node {
+        MathNode::Apply(apply) => match apply.content.split_first() {
+            Some((operator, args)) => simplify_apply(
+                operator,
+                args.iter()
+                    .map(|arg| simplify_node(arg, functions))
+                    .collect(),
+                functions,
+            ),
+            None => node.clone(),
+        },
+        MathNode::Piecewise { children } => simplify_piecewise(children, functions),
+        MathNode::Lambda { .. }
+        | MathNode::Semantics { .. }
+        | MathNode::Degree { .. }
+        | MathNode::Logbase { .. } => map_children(node, &|child| simplify_node(child, functions)),
+        _ => node.clone(),
+    }
+}
+
+/// Identifiers called as functions in `node`: the `ci`s that are the
+/// function of an apply.
+pub(crate) fn called_functions(node: &MathNode) -> BTreeSet<String> {
+    let mut calls = BTreeSet::new();
+    collect_calls(node, &mut calls);
+    calls
+}
+
+fn collect_calls(node: &MathNode, calls: &mut BTreeSet<String>) {
+    let children = match node {
+        MathNode::Apply(apply) => {
+            if let Some(MathNode::Ci { content, .. }) = apply.content.first() {
+                calls.insert(content.trim().to_string());
+            }
+            &apply.content
+        }
+        MathNode::Lambda { children }
+        | MathNode::Bvar { children }
+        | MathNode::Piecewise { children }
+        | MathNode::Piece { children }
+        | MathNode::Otherwise { children }
+        | MathNode::Degree { children }
+        | MathNode::Logbase { children }
+        | MathNode::Semantics { children, .. } => children,
+        _ => return,
+    };
+    for child in children {
+        collect_calls(child, calls);
+    }
+}
+
+/// Ids of the function definitions that call themselves, directly or
+/// through other function definitions, sorted. Inlining or evaluating them
+/// would never end.
+pub(crate) fn recursive_functions(functions: &HashMap<String, FunctionDefinition>) -> Vec<String> {
+    let calls: HashMap<&str, BTreeSet<String>> = functions
+        .iter()
+        .map(|(id, function)| (id.as_str(), called_functions(&function.math.content)))
+        .collect();
+    let calls_itself = |id: &str| {
+        let mut seen = HashSet::new();
+        let mut stack: Vec<&str> = calls[id].iter().map(String::as_str).collect();
+        while let Some(callee) = stack.pop() {
+            if callee == id {
+                return true;
+            }
+            if seen.insert(callee) {
+                if let Some(next) = calls.get(callee) {
+                    stack.extend(next.iter().map(String::as_str));
+                }
+            }
+        }
+        false
+    };
+    let mut recursive: Vec<String> = functions
+        .keys()
+        .filter(|id| calls_itself(id))
+        .cloned()
+        .collect();
+    recursive.sort();
+    recursive
+}
+
+/// Copy of `node` with `f` applied to its children.
+fn map_children(node: &MathNode, f: &dyn Fn(&MathNode) -> MathNode) -> MathNode {
+    let map = |children: &[MathNode]| children.iter().map(f).collect();
+    match node {
+        MathNode::Apply(apply) => MathNode::apply(map(&apply.content)),
+        MathNode::Lambda { children } => MathNode::Lambda {
+            children: map(children),
+        },
+        MathNode::Bvar { children } => MathNode::Bvar {
+            children: map(children),
+        },
+        MathNode::Piecewise { children } => MathNode::Piecewise {
+            children: map(children),
+        },
+        MathNode::Piece { children } => MathNode::Piece {
+            children: map(children),
+        },
+        MathNode::Otherwise { children } => MathNode::Otherwise {
+            children: map(children),
+        },
+        MathNode::Degree { children } => MathNode::Degree {
+            children: map(children),
+        },
+        MathNode::Logbase { children } => MathNode::Logbase {
+            children: map(children),
+        },
+        MathNode::Semantics {
+            definition_url,
+            encoding,
+            children,
+        } => MathNode::Semantics {
+            definition_url: definition_url.clone(),
+            encoding: encoding.clone(),
+            children: map(children),
+        },
+        _ => node.clone(),
+    }
+}
+
+fn boolean(value: bool) -> MathNode {
+    if value {
+        MathNode::True
+    } else {
+        MathNode::False
+    }
+}
+
+/// Value of a node that can be folded.
+fn constant_value(node: &MathNode) -> Option<f64> {
+    match node {
+        MathNode::True => Some(1.),
+        MathNode::False => Some(0.),
+        MathNode::Degree { children } | MathNode::Logbase { children } if children.len() == 1 => {
+            constant_value(&children[0])
+        }
+        _ => as_number(node),
+    }
+}
+
+fn simplify_apply(operator: &MathNode, mut args: Vec<MathNode>, functions: &Inlined) -> MathNode {
+    match (operator, args.len()) {
+        (MathNode::Ci { content, .. }, _) => {
+            match functions
+                .get(content.trim())
+                .and_then(|function| inline(&function.math.content, &args))
+            {
+                Some(body) => simplify_node(&body, functions),
+                None => apply(operator.clone(), args),
+            }
+        }
+        (MathNode::Lambda { .. }, _) => match inline(operator, &args) {
+            Some(body) => simplify_node(&body, functions),
+            None => apply(operator.clone(), args),
+        },
+        (MathNode::Plus, _) => sum(args),
+        (MathNode::Times, _) => product(args),
+        (MathNode::Minus, 1) => neg(args.pop().unwrap()),
+        (MathNode::Minus, 2) => {
+            let b = args.pop().unwrap();
+            sub(args.pop().unwrap(), b)
+        }
+        (MathNode::Divide, 2) => {
+            let b = args.pop().unwrap();
+            div(args.pop().unwrap(), b)
+        }
+        (MathNode::Power, 2) => {
+            let b = args.pop().unwrap();
+            pow(args.pop().unwrap(), b)
+        }
+        (MathNode::And, _) => logical(MathNode::And, args, MathNode::True, MathNode::False),
+        (MathNode::Or, _) => logical(MathNode::Or, args, MathNode::False, MathNode::True),
+        _ => {
+            let values: Option<Vec<f64>> = args.iter().map(constant_value).collect();
+            match values.map(|values| apply_operator(operator, &values)) {
+                Some(Ok(value)) if returns_boolean(operator) => boolean(value != 0.),
+                Some(Ok(value)) if value.is_finite() => num(value),
+                _ => apply(operator.clone(), args),
+            }
+        }
+    }
+}
+
+fn returns_boolean(operator: &MathNode) -> bool {
+    matches!(
+        operator,
+        MathNode::Eq
+            | MathNode::Neq
+            | MathNode::Gt
+            | MathNode::Lt
+            | MathNode::Geq
+            | MathNode::Leq
+            | MathNode::Xor
+            | MathNode::Not
+            | MathNode::Implies
+    )
+}
+
+/// `and`/`or` without their `identity` operands, or `absorbing` if any
+/// operand is.
+fn logical(
+    operator: MathNode,
+    args: Vec<MathNode>,
+    identity: MathNode,
+    absorbing: MathNode,
+) -> MathNode {
+    let mut rest = Vec::new();
+    for arg in args {
+        if arg == absorbing {
+            return absorbing;
+        }
+        if arg != identity {
+            rest.push(arg);
+        }
+    }
+    match rest.len() {
+        0 => identity,
+        1 => rest.pop().unwrap(),
+        _ => apply(operator, rest),
+    }
+}
+
+/// Body of `lambda` with its bound variables replaced by `args`.
+fn inline(lambda: &MathNode, args: &[MathNode]) -> Option<MathNode> {
+    let children = match lambda {
+        MathNode::Lambda { children } => children,
+        _ => return None,
+    };
+    let (body, bvars) = children.split_last()?;
+    if bvars.len() != args.len() {
+        return None;
+    }
+    let bindings = bvars
+        .iter()
+        .zip(args)
+        .map(|(bvar, arg)| match bvar {
+            MathNode::Bvar { children } => match children.as_slice() {
+                [MathNode::Ci { content, .. }] => Some((content.trim().to_string(), arg.clone())),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Option<HashMap<String, MathNode>>>()?;
+    Some(body.substitute(&bindings))
+}
+
+/// Pieces with a false condition are dropped and the first piece with a
+/// true condition becomes the otherwise.
+fn simplify_piecewise(children: &[MathNode], functions: &Inlined) -> MathNode {
+    let mut pieces = Vec::new();
+    for child in children {
+        let (value, condition) = match child {
+            MathNode::Piece { children } if children.len() == 2 => (
+                simplify_node(&children[0], functions),
+                Some(simplify_node(&children[1], functions)),
+            ),
+            MathNode::Otherwise { children } if children.len() == 1 => {
+                (simplify_node(&children[0], functions), None)
+            }
+            _ => {
+                pieces.push(child.clone());
+                continue;
+            }
+        };
+        match condition {
+            Some(MathNode::False) => continue,
+            Some(condition) if condition != MathNode::True => {
+                pieces.push(MathNode::Piece {
+                    children: vec![value, condition],
+                });
+                continue;
+            }
+            _ if pieces.is_empty() => return value,
+            _ => {
+                pieces.push(MathNode::Otherwise {
+                    children: vec![value],
+                });
+                break;
+            }
+        }
+    }
+    MathNode::Piecewise { children: pieces }
+}
@@ -1,12 +1,18 @@
 #[cfg(feature = "default")]
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 
 use super::annotation::Annotation;
-use super::base_types::{Compartment, Constraint, InitialAssignment, Parameter, Reaction, Species};
+use super::base_types::{
+    Compartment, Constraint, Event, FunctionDefinition, GeneProduct, InitialAssignment, Parameter,
+    Reaction, Rule, Species,
+};
+use super::document::SbmlDocument;
+use super::error::{Error, Location, Result};
 use super::list_of::*;
-use super::{Unit, UnitSIdRef};
+use super::{UnitDefinition, UnitSIdRef};
 
 /// SBML model as defined in the [SBML Level 3 Version 2 core](http://sbml.org/Documents/Specifications).
 ///
@@ -52,34 +58,50 @@ pub struct ModelRaw {
     pub list_of_reactions: ListOfReactions,
     #[serde(default)]
     pub list_of_constraints: ListOfConstraints,
-    #[serde(default)]
+    #[serde(rename = "fbc:listOfObjectives", alias = "listOfObjectives", default)]
     pub list_of_objectives: Option<ListOfObjectives>,
+    #[serde(rename = "fbc:listOfGeneProducts", alias = "listOfGeneProducts")]
+    pub list_of_gene_products: Option<ListOfGeneProducts>,
     pub list_of_rules: Option<ListOfRules>,
     pub list_of_function_definitions: Option<ListOfFunctionDefinitions>,
+    pub list_of_events: Option<ListOfEvents>,
     pub annotation: Option<Annotation>,
 }
 
 impl ModelRaw {
-    pub fn parse(doc: &str) -> Result<Self, quick_xml::DeError> {
-        let raw_model: Sbml = quick_xml::de::from_str(doc)?;
-        Ok(raw_model.model)
+    pub fn parse(doc: &str) -> Result<Self> {
+        Ok(SbmlDocument::parse(doc)?.model)
     }
 
-    pub fn to_string(&self) -> Result<String, quick_xml::DeError> {
-        quick_xml::se::to_string(&Sbml {
-            model: (*self).clone(),
-        })
+    /// Serialize the model inside a default Level 3 Version 2
+    /// [`SbmlDocument`]. Use the document directly to keep the level, version
+    /// and packages of a parsed file.
+    pub fn to_string(&self) -> Result<String> {
+        SbmlDocument::from(self.clone()).to_string()
     }
-}
 
-#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
-struct Sbml {
-    model: ModelRaw,
+    /// Mark the FBC objective `objective_id` as `fbc:activeObjective`, which
+    /// is written by [`ModelRaw::to_string`].
+    pub fn set_active_objective(&mut self, objective_id: &str) -> Result<()> {
+        match self.list_of_objectives.as_mut() {
+            Some(list) if list.objectives.iter().any(|o| o.id == objective_id) => {
+                list.active_objective = Some(objective_id.to_string());
+                Ok(())
+            }
+            _ => Err(Error::UnknownId {
+                id: objective_id.to_string(),
+                location: Location {
+                    path: "model/fbc:listOfObjectives".to_string(),
+                    position: None,
+                },
+            }),
+        }
+    }
 }
 
 /// Bucket struct to hold all units defined on the top level of
 /// [`ModelRaw`].
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct ModelUnits {
     pub substance_units: Option<UnitSIdRef>,
     pub time_units: Option<UnitSIdRef>,
@@ -104,6 +126,24 @@ impl From<&ModelRaw> for ModelUnits {
     }
 }
 
+/// Optimization sense of an FBC objective (`fbc:type`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectiveSense {
+    Maximize,
+    Minimize,
+}
+
+/// View of an FBC [`Objective`](crate::Objective) with its parsed sense and
+/// the `(reaction, coefficient)` pairs of its flux objectives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectiveFunction {
+    pub id: String,
+    pub sense: ObjectiveSense,
+    /// Whether this is the `fbc:activeObjective` of the model.
+    pub active: bool,
+    pub coefficients: Vec<(String, f64)>,
+}
+
 type Hl<T> = HashMap<String, T>;
 /// Abstraction over the SBML specification. It traverses each top-level
 /// listOF_ and provides `HashMaps<id, object>` instead. In addition the model
@@ -129,7 +169,7 @@ type Hl<T> = HashMap<String, T>;
 /// );
 /// ```
 #[cfg_attr(feature = "default", pyclass)]
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct Model {
     pub id: Option<String>,
     pub metaid: Option<String>,
@@ -140,9 +180,18 @@ pub struct Model {
     pub species: Hl<Species>,
     pub reactions: Hl<Reaction>,
     pub compartments: Hl<Compartment>,
-    pub unit_definitions: Hl<HashMap<UnitSIdRef, Unit>>,
+    pub gene_products: Hl<GeneProduct>,
+    pub function_definitions: Hl<FunctionDefinition>,
+    /// Unit definitions, in document order; see [`Model::unit_definition`].
+    pub unit_definitions: Vec<UnitDefinition>,
     pub constraints: Vec<Constraint>,
+    /// Reaction ids of the flux objectives of all the FBC objectives.
     pub objectives: Option<Vec<String>>,
+    /// FBC objectives, in document order.
+    pub objective_functions: Vec<ObjectiveFunction>,
+    pub events: Vec<Event>,
+    /// Algebraic, assignment and rate rules, in document order.
+    pub rules: Vec<Rule>,
     pub annotation: Option<Annotation>,
 }
 
@@ -160,7 +209,7 @@ impl Model {
     /// println!("{:?}", document.get_list_of_compartments())
     /// ```
     pub fn get_list_of_compartments(&self) -> Vec<&Compartment> {
-        self.compartments.iter().map(|(_key, val)| val).collect()
+        self.compartments.values().collect()
     }
     /// Emulating the API of libSBML
     ///
@@ -175,7 +224,7 @@ impl Model {
     /// println!("{:?}", document.get_list_of_species())
     /// ```
     pub fn get_list_of_species(&self) -> Vec<&Species> {
-        self.species.iter().map(|(_key, val)| val).collect()
+        self.species.values().collect()
     }
     /// Emulating the API of libSBML
     ///
@@ -190,32 +239,296 @@ impl Model {
     /// println!("{:?}", document.get_list_of_reactions())
     /// ```
     pub fn get_list_of_reactions(&self) -> Vec<&Reaction> {
-        self.reactions.iter().map(|(_key, val)| val).collect()
+        self.reactions.values().collect()
+    }
+    /// The FBC objective marked as active, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::{Model, ObjectiveSense};
+    /// use std::fs;
+    ///
+    /// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+    /// let document = Model::parse(&ecoli).unwrap();
+    /// let objective = document.active_objective().unwrap();
+    /// assert_eq!(objective.sense, ObjectiveSense::Maximize);
+    /// assert_eq!(
+    ///     objective.coefficients,
+    ///     vec![("R_BIOMASS_Ecoli_core_w_GAM".to_string(), 1.)]
+    /// );
+    /// ```
+    pub fn active_objective(&self) -> Option<&ObjectiveFunction> {
+        self.objective_functions.iter().find(|o| o.active)
+    }
+
+    /// Unit definition with id `unit_id`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::{Model, UnitSId, UnitSIdRef};
+    /// use std::fs;
+    ///
+    /// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+    /// let document = Model::parse(&ecoli).unwrap();
+    /// let flux = document.unit_definition("mmol_per_gDW_per_hr").unwrap();
+    /// assert_eq!(
+    ///     flux.name.as_deref(),
+    ///     Some("Millimoles per gram (dry weight) per hour")
+    /// );
+    /// let kinds: Vec<&UnitSIdRef> = flux.list_of_units.units.iter().map(|u| &u.kind).collect();
+    /// assert_eq!(
+    ///     kinds,
+    ///     vec![
+    ///         &UnitSIdRef::SIUnit(UnitSId::mole),
+    ///         &UnitSIdRef::SIUnit(UnitSId::gram),
+    ///         &UnitSIdRef::SIUnit(UnitSId::second)
+    ///     ]
+    /// );
+    /// ```
+    pub fn unit_definition(&self, unit_id: &str) -> Option<&UnitDefinition> {
+        self.unit_definitions
+            .iter()
+            .find(|def| def.id.as_deref() == Some(unit_id))
+    }
+
+    /// The unit definitions as the `listOfUnitDefinitions` of a
+    /// [`ModelRaw`], in document order, so that writing them recreates the
+    /// list of the parsed document.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::{Model, ModelRaw};
+    /// use std::fs;
+    ///
+    /// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+    /// let model = Model::parse(&ecoli).unwrap();
+    /// let raw_model = ModelRaw {
+    ///     list_of_unit_definitions: model.list_of_unit_definitions(),
+    ///     ..Default::default()
+    /// };
+    /// let written = Model::parse(&raw_model.to_string().unwrap()).unwrap();
+    /// assert_eq!(written.unit_definitions, model.unit_definitions);
+    /// ```
+    pub fn list_of_unit_definitions(&self) -> ListOfUnitDefinitions {
+        ListOfUnitDefinitions {
+            unit_definitions: self.unit_definitions.clone(),
+        }
+    }
+
+    /// Make `objective_id` the only active FBC objective of this view, as
+    /// used by [`Model::active_objective`] and the FBA functions. `Model`
+    /// is not written back to SBML: to change the `fbc:activeObjective` of a
+    /// document, use [`ModelRaw::set_active_objective`] before writing it.
+    pub fn set_active_objective(&mut self, objective_id: &str) -> Result<()> {
+        if !self
+            .objective_functions
+            .iter()
+            .any(|o| o.id == objective_id)
+        {
+            return Err(Error::UnknownId {
+                id: objective_id.to_string(),
+                location: Location {
+                    path: "model/fbc:listOfObjectives".to_string(),
+                    position: None,
+                },
+            });
+        }
+        for objective in self.objective_functions.iter_mut() {
+            objective.active = objective.id == objective_id;
+        }
+        Ok(())
+    }
+
+    /// FBC objectives in which `reaction_id` has a flux objective.
+    pub fn objectives_of_reaction(&self, reaction_id: &str) -> Vec<&ObjectiveFunction> {
+        self.objective_functions
+            .iter()
+            .filter(|o| o.coefficients.iter().any(|(r, _)| r == reaction_id))
+            .collect()
+    }
+
+    /// Resolve the FBC flux bounds of a reaction to the values of their
+    /// [`Parameter`]s. Missing bounds default to $(-\infty, \infty)$.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::Model;
+    /// use std::fs;
+    ///
+    /// let ecoli = fs::read_to_string("examples/EcoliCore.xml").unwrap();
+    /// let document = Model::parse(&ecoli).unwrap();
+    /// assert_eq!(document.flux_bounds("R_EX_glc__D_e").unwrap(), (-10., 1000.));
+    /// ```
+    pub fn flux_bounds(&self, reaction_id: &str) -> Result<(f64, f64)> {
+        let reaction = self
+            .reactions
+            .get(reaction_id)
+            .ok_or_else(|| Error::UnknownId {
+                id: reaction_id.to_string(),
+                location: Location {
+                    path: "model/listOfReactions".to_string(),
+                    position: None,
+                },
+            })?;
+        Ok((
+            self.bound_value(reaction, reaction.lower_bound.as_deref(), f64::NEG_INFINITY)?,
+            self.bound_value(reaction, reaction.upper_bound.as_deref(), f64::INFINITY)?,
+        ))
+    }
+
+    /// [`Model::flux_bounds`] of all the reactions, by reaction id.
+    pub fn all_flux_bounds(&self) -> Result<HashMap<String, (f64, f64)>> {
+        self.reactions
+            .keys()
+            .map(|id| Ok((id.to_owned(), self.flux_bounds(id)?)))
+            .collect()
+    }
+
+    /// Reactions whose gene-product association evaluates to false when
+    /// the gene products `genes` are knocked out. Reactions without an
+    /// association are never inactivated.
+    pub fn inactive_reactions(&self, genes: &[&str]) -> Result<Vec<&str>> {
+        if let Some(gene) = genes
+            .iter()
+            .find(|gene| !self.gene_products.contains_key(**gene))
+        {
+            return Err(Error::UnknownId {
+                id: gene.to_string(),
+                location: Location {
+                    path: "model/fbc:listOfGeneProducts".to_string(),
+                    position: None,
+                },
+            });
+        }
+        let knocked_out: HashSet<&str> = genes.iter().copied().collect();
+        let is_active = |gene: &str| !knocked_out.contains(gene);
+        Ok(self
+            .reactions
+            .values()
+            .filter(|reaction| {
+                reaction
+                    .gene_product_association
+                    .as_ref()
+                    .map(|gpa| !gpa.association.evaluate(&is_active))
+                    .unwrap_or(false)
+            })
+            .map(|reaction| reaction.id.as_str())
+            .collect())
+    }
+
+    /// Copy of the model with the gene products `genes` knocked out: the
+    /// flux bounds of the [inactive reactions](Model::inactive_reactions)
+    /// point to a constant parameter with value 0, which is added to the
+    /// model if it has none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_sbml::Model;
+    /// use std::fs;
+    ///
+    /// let ecoli = Model::parse(&fs::read_to_string("examples/EcoliCore.xml").unwrap()).unwrap();
+    /// // the only gene of the glyceraldehyde-3-phosphate dehydrogenase
+    /// let knockout = ecoli.knockout_genes(&["G_b1779"]).unwrap();
+    /// assert_eq!(knockout.flux_bounds("R_GAPD").unwrap(), (0., 0.));
+    /// assert_eq!(knockout.flux_bounds("R_PGK").unwrap(), ecoli.flux_bounds("R_PGK").unwrap());
+    /// ```
+    pub fn knockout_genes(&self, genes: &[&str]) -> Result<Model> {
+        let inactive: Vec<String> = self
+            .inactive_reactions(genes)?
+            .into_iter()
+            .map(|id| id.to_owned())
+            .collect();
+        let mut model = self.clone();
+        if inactive.is_empty() {
+            return Ok(model);
+        }
+        let zero = match self
+            .parameters
+            .values()
+            .find(|p| p.constant && p.value == Some(0.))
+        {
+            Some(parameter) => parameter.id.to_owned(),
+            None => {
+                let mut id = "knockout_bound".to_string();
+                while model.parameters.contains_key(&id) {
+                    id.push('_');
+                }
+                model.parameters.insert(
+                    id.clone(),
+                    Parameter {
+                        id: id.clone(),
+                        value: Some(0.),
+                        units: None,
+                        constant: true,
+                    },
+                );
+                id
+            }
+        };
+        for id in inactive {
+            let reaction = model
+                .reactions
+                .get_mut(&id)
+                .expect("reaction of this model");
+            reaction.lower_bound = Some(zero.clone());
+            reaction.upper_bound = Some(zero.clone());
+        }
+        Ok(model)
+    }
+
+    fn bound_value(&self, reaction: &Reaction, bound: Option<&str>, default: f64) -> Result<f64> {
+        let bound = match bound {
+            Some(bound) => bound,
+            None => return Ok(default),
+        };
+        let parameter = self.parameters.get(bound).ok_or_else(|| Error::UnknownId {
+            id: bound.to_string(),
+            location: Location {
+                path: format!("model/listOfReactions/reaction[id={}]", reaction.id),
+                position: None,
+            },
+        })?;
+        parameter.value.ok_or_else(|| {
+            missing_attribute(
+                "value",
+                &format!("model/listOfParameters/parameter[id={}]", parameter.id),
+            )
+        })
     }
     /// Use [`ModelRaw`] to parse the SBML document
     /// and then format it into `Model`.
-    pub fn parse(doc: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let raw_model = ModelRaw::parse(doc)?;
+    pub fn parse(doc: &str) -> Result<Self> {
+        Self::try_from(ModelRaw::parse(doc)?)
+    }
+}
+
+impl TryFrom<ModelRaw> for Model {
+    type Error = Error;
+
+    /// Collect the lists of `raw_model` by id. Of the components of a kind
+    /// that share an id, only the last one is kept.
+    fn try_from(raw_model: ModelRaw) -> Result<Self> {
         // Units used by the model itself
         let model_units: ModelUnits = ModelUnits::from(&raw_model);
 
         // Unit definitions
-        let unit_definitions: HashMap<String, HashMap<UnitSIdRef, Unit>> = raw_model
+        let unit_definitions: Vec<UnitDefinition> = raw_model
             .list_of_unit_definitions
             .unit_definitions
             .iter()
-            .map(|unit_def| {
-                (
-                    (*unit_def).to_owned().id.unwrap(),
-                    unit_def
-                        .list_of_units
-                        .units
-                        .iter()
-                        .map(|unit| (unit.kind.to_owned(), unit.to_owned()))
-                        .collect(),
-                )
+            .map(|unit_def| match unit_def.id {
+                Some(_) => Ok(unit_def.to_owned()),
+                None => Err(missing_attribute(
+                    "id",
+                    "model/listOfUnitDefinitions/unitDefinition",
+                )),
             })
-            .collect();
+            .collect::<Result<_>>()?;
         // Compartments
         let compartments: HashMap<String, Compartment> = raw_model
             .list_of_compartments
@@ -237,12 +550,17 @@ impl Model {
             .iter()
             .map(|n| (n.id.to_owned(), n.to_owned()))
             .collect();
-        // Initial assignments
+        // Initial assignments, keyed by their symbol if they lack an id
         let initial_assignments: HashMap<String, InitialAssignment> = raw_model
             .list_of_initial_assignments
             .initial_assignments
             .iter()
-            .map(|n| (n.id.to_owned().unwrap(), (*n).to_owned()))
+            .map(|n| {
+                (
+                    n.id.to_owned().unwrap_or_else(|| n.symbol.to_owned()),
+                    (*n).to_owned(),
+                )
+            })
             .collect();
         // Reactions
         let reactions: HashMap<String, Reaction> = raw_model
@@ -252,22 +570,89 @@ impl Model {
             .map(|n| (n.id.to_owned(), n.to_owned()))
             .collect();
 
+        // Gene products
+        let gene_products: HashMap<String, GeneProduct> = raw_model
+            .list_of_gene_products
+            .map(|list| {
+                list.gene_products
+                    .into_iter()
+                    .map(|n| (n.id.to_owned(), n))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let function_definitions: HashMap<String, FunctionDefinition> = raw_model
+            .list_of_function_definitions
+            .map(|list| {
+                list.function_definitions
+                    .into_iter()
+                    .map(|n| (n.id.to_owned(), n))
+                    .collect()
+            })
+            .unwrap_or_default();
         // Constraints
         let constraints: Vec<Constraint> = raw_model.list_of_constraints.constraints;
-        let objectives: Option<Vec<String>> = match raw_model.list_of_objectives {
-            Some(objs) => Some(
-                objs.objectives
-                    .iter()
-                    .flat_map(|n| {
-                        (*n).list_of_flux_objectives
+        // Events
+        let events: Vec<Event> = raw_model
+            .list_of_events
+            .map(|list| list.events)
+            .unwrap_or_default();
+        // Rules
+        let rules: Vec<Rule> = raw_model
+            .list_of_rules
+            .map(|list| list.rules)
+            .unwrap_or_default();
+        // Objectives
+        let objective_functions: Vec<ObjectiveFunction> = match &raw_model.list_of_objectives {
+            Some(objs) => objs
+                .objectives
+                .iter()
+                .map(|n| {
+                    let path = format!("model/fbc:listOfObjectives/fbc:objective[id={}]", n.id);
+                    Ok(ObjectiveFunction {
+                        id: n.id.to_owned(),
+                        sense: match n.sense.as_str() {
+                            "maximize" => ObjectiveSense::Maximize,
+                            "minimize" => ObjectiveSense::Minimize,
+                            sense => {
+                                return Err(Error::BadValue {
+                                    message: format!("unknown objective type `{}`", sense),
+                                    location: Location {
+                                        path: path.to_owned(),
+                                        position: None,
+                                    },
+                                })
+                            }
+                        },
+                        active: objs.active_objective.as_ref() == Some(&n.id),
+                        coefficients: n
+                            .list_of_flux_objectives
                             .flux_objectives
                             .iter()
-                            .map(|fr| fr.reaction.to_owned().unwrap())
+                            .map(|fr| {
+                                let path =
+                                    format!("{}/fbc:listOfFluxObjectives/fbc:fluxObjective", path);
+                                Ok((
+                                    fr.reaction
+                                        .to_owned()
+                                        .ok_or_else(|| missing_attribute("fbc:reaction", &path))?,
+                                    fr.coefficient.ok_or_else(|| {
+                                        missing_attribute("fbc:coefficient", &path)
+                                    })?,
+                                ))
+                            })
+                            .collect::<Result<_>>()?,
                     })
-                    .collect(),
-            ),
-            None => None,
+                })
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
         };
+        let objectives: Option<Vec<String>> = raw_model.list_of_objectives.map(|_| {
+            objective_functions
+                .iter()
+                .flat_map(|o| o.coefficients.iter().map(|(r, _)| r.to_owned()))
+                .collect()
+        });
         Ok(Model {
             id: raw_model.id,
             metaid: raw_model.metaid,
@@ -278,15 +663,30 @@ impl Model {
             species,
             reactions,
             compartments,
+            gene_products,
+            function_definitions,
             unit_definitions,
             constraints,
             objectives,
+            objective_functions,
+            events,
+            rules,
             annotation: raw_model.annotation,
         })
     }
 }
 
+fn missing_attribute(attribute: &str, path: &str) -> Error {
+    Error::MissingAttribute {
+        attribute: attribute.to_string(),
+        location: Location {
+            path: path.to_string(),
+            position: None,
+        },
+    }
+}
+
 /// Shortcut to [`Model::parse`](Model::parse).
-pub fn parse_document(doc: &str) -> Result<Model, Box<dyn std::error::Error>> {
+pub fn parse_document(doc: &str) -> Result<Model> {
     Model::parse(doc)
 }
@@ -1,7 +1,11 @@
 #![allow(non_snake_case)]
 
-use super::{Compartment, Model, Parameter, Reaction, Species, SpeciesReference};
+use super::{
+    Compartment, Model, ModifierSpeciesReference, Parameter, Reaction, Species, SpeciesReference,
+};
+use pyo3::exceptions::{PyIOError, PyValueError};
 use pyo3::prelude::*;
+use std::collections::HashMap;
 
 #[pymethods]
 impl Species {
@@ -48,6 +52,11 @@ impl Reaction {
     fn getListOfProducts(&self) -> Vec<SpeciesReference> {
         self.list_of_products.species_references.to_owned()
     }
+    fn getListOfModifiers(&self) -> Vec<ModifierSpeciesReference> {
+        self.list_of_modifiers
+            .modifier_species_references
+            .to_owned()
+    }
     fn getLowerFluxBound(&self) -> &str {
         match &self.lower_bound {
             Some(s) => s,
@@ -73,6 +82,17 @@ impl SpeciesReference {
     }
 }
 
+#[pymethods]
+impl ModifierSpeciesReference {
+    #[getter]
+    fn id(&self) -> &str {
+        self.species.as_str()
+    }
+    fn getSBOTerm(&self) -> Option<&str> {
+        self.sbo_term.as_deref()
+    }
+}
+
 #[pymethods]
 impl Parameter {
     fn getValue(&self) -> Option<f64> {
@@ -86,28 +106,58 @@ impl Parameter {
 #[pymethods]
 impl Model {
     #[new]
-    fn new(doc: &str) -> Self {
-        let file_str = std::fs::read_to_string(doc).unwrap();
-        match Model::parse(&file_str) {
-            Ok(m) => m,
-            Err(e) => panic!("kai_sbml Couldn't parse {}. Error: {:?}", doc, e),
-        }
+    fn new(doc: &str) -> PyResult<Self> {
+        let file_str = std::fs::read_to_string(doc)
+            .map_err(|e| PyIOError::new_err(format!("Couldn't read {}: {}", doc, e)))?;
+        Model::parse(&file_str)
+            .map_err(|e| PyValueError::new_err(format!("Couldn't parse {}: {}", doc, e)))
     }
     fn getListOfCompartments(&self) -> Vec<Compartment> {
-        self.compartments
-            .iter()
-            .map(|(_, n)| n.to_owned())
-            .collect()
+        self.compartments.values().map(|n| n.to_owned()).collect()
     }
     fn getListOfSpecies(&self) -> Vec<Species> {
-        self.species.iter().map(|(_, n)| n.to_owned()).collect()
+        self.species.values().map(|n| n.to_owned()).collect()
     }
     fn getListOfReactions(&self) -> Vec<Reaction> {
-        self.reactions.iter().map(|(_, n)| n.to_owned()).collect()
+        self.reactions.values().map(|n| n.to_owned()).collect()
     }
     fn getParameter(&self, query: String) -> Option<Parameter> {
         self.parameters.get(&query).cloned()
     }
+    fn getFluxBounds(&self, reaction_id: &str) -> PyResult<(f64, f64)> {
+        self.flux_bounds(reaction_id)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+    /// Stoichiometric matrix as `((data, indices, indptr), shape, species, reactions)`,
+    /// to be loaded with `scipy.sparse.csc_matrix((data, indices, indptr), shape=shape)`.
+    #[args(exclude_boundary = "false")]
+    fn getStoichiometricMatrix(&self, exclude_boundary: bool) -> PyObject {
+        let s = self.stoichiometric_matrix(exclude_boundary);
+        let shape = s.shape();
+        Python::with_gil(|py| {
+            (
+                (s.values, s.row_indices, s.col_ptr),
+                shape,
+                s.species,
+                s.reactions,
+            )
+                .into_py(py)
+        })
+    }
+    /// Minimum and maximum flux of `reactions` (all by default), by reaction
+    /// id, keeping the objective within `fraction_of_optimum` of its optimum.
+    #[args(reactions = "None", fraction_of_optimum = "1.0")]
+    fn fluxVariability(
+        &self,
+        reactions: Option<Vec<String>>,
+        fraction_of_optimum: f64,
+    ) -> PyResult<HashMap<String, (f64, f64)>> {
+        let reactions: Option<Vec<&str>> = reactions
+            .as_ref()
+            .map(|r| r.iter().map(|id| id.as_str()).collect());
+        crate::fba::flux_variability(self, reactions.as_deref(), fraction_of_optimum)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
     fn getObjectives(&self) -> Vec<String> {
         self.objectives.to_owned().unwrap_or_default()
     }
@@ -131,6 +181,7 @@ fn rust_sbml(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Reaction>()?;
     m.add_class::<Species>()?;
     m.add_class::<SpeciesReference>()?;
+    m.add_class::<ModifierSpeciesReference>()?;
     m.add_class::<Compartment>()?;
     Ok(())
 }
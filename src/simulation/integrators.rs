@@ -0,0 +1,347 @@
+//! Adaptive integrators of an [`OdeSystem`] from the first to the last of a
+//! set of output times, which are interpolated with cubic Hermite
+//! polynomials between the accepted steps.
+use super::linalg::Lu;
+use super::{OdeSystem, SimulationError, SimulationOptions};
+
+/// Dormand–Prince nodes, coefficients and 5th order weights, the last
+/// stage being evaluated at the new state (first same as last).
+const C: [f64; 7] = [0., 1. / 5., 3. / 10., 4. / 5., 8. / 9., 1., 1.];
+const A: [[f64; 6]; 7] = [
+    [0., 0., 0., 0., 0., 0.],
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [
+        19372. / 6561.,
+        -25360. / 2187.,
+        64448. / 6561.,
+        -212. / 729.,
+        0.,
+        0.,
+    ],
+    [
+        9017. / 3168.,
+        -355. / 33.,
+        46732. / 5247.,
+        49. / 176.,
+        -5103. / 18656.,
+        0.,
+    ],
+    [
+        35. / 384.,
+        0.,
+        500. / 1113.,
+        125. / 192.,
+        -2187. / 6784.,
+        11. / 84.,
+    ],
+];
+/// Difference between the 5th and the embedded 4th order weights.
+const E: [f64; 7] = [
+    71. / 57600.,
+    0.,
+    -71. / 16695.,
+    71. / 1920.,
+    -17253. / 339200.,
+    22. / 525.,
+    -1. / 40.,
+];
+
+/// Integrate `system` with the explicit Dormand–Prince RK45 method,
+/// from `y0` at the first of the `times`, returning the state at
+/// each of them.
+pub(super) fn rk45(
+    system: &OdeSystem,
+    y0: &[f64],
+    times: &[f64],
+    options: &SimulationOptions,
+) -> Result<Vec<Vec<f64>>, SimulationError> {
+    let mut output = Output::new(times, y0);
+    let (mut t, t_end) = (times[0], times[times.len() - 1]);
+    let mut y = y0.to_vec();
+    let mut f = system.rhs(t, &y)?;
+    let mut h = initial_step(&y, &f, t_end - t, options);
+    let n = y.len();
+    let mut steps = 0;
+    while t < t_end {
+        let (h_step, last) = check_step(t, t_end, h, &mut steps, options)?;
+        let mut k = vec![f.clone()];
+        let mut y_new = y.clone();
+        for (stage, (a, c)) in A.iter().zip(&C).enumerate().skip(1) {
+            let mut y_stage = y.clone();
+            for (j, k_j) in k.iter().enumerate().take(stage) {
+                for i in 0..n {
+                    y_stage[i] += h_step * a[j] * k_j[i];
+                }
+            }
+            if stage == 6 {
+                y_new = y_stage.clone();
+            }
+            k.push(system.rhs(t + c * h_step, &y_stage)?);
+        }
+        let error: Vec<f64> = (0..n)
+            .map(|i| h_step * E.iter().zip(&k).map(|(e, k)| e * k[i]).sum::<f64>())
+            .collect();
+        let error = norm(&error, &y, &y_new, options);
+        let factor = 0.9 * error.powf(-0.2);
+        if error <= 1. {
+            let t_new = if last { t_end } else { t + h_step };
+            let f_new = k.pop().expect("last stage");
+            output.record(t, &y, &f, t_new, &y_new, &f_new);
+            t = t_new;
+            y = y_new;
+            f = f_new;
+            h = h_step * factor.min(5.);
+        } else {
+            h = h_step * factor.max(0.1);
+        }
+    }
+    Ok(output.states)
+}
+
+/// Integrate `system` with the implicit variable-step BDF method of order
+/// 2, started with a backward Euler step, from `y0` at the first of the
+/// `times`, returning the state at each of them.
+///
+/// The implicit equations are solved with Newton iterations that reuse the
+/// Jacobian of the system until they fail to converge, and the local error
+/// is estimated from the difference between the solution and the
+/// extrapolation of the previous steps.
+pub(super) fn bdf(
+    system: &OdeSystem,
+    y0: &[f64],
+    times: &[f64],
+    options: &SimulationOptions,
+) -> Result<Vec<Vec<f64>>, SimulationError> {
+    let mut output = Output::new(times, y0);
+    let (mut t, t_end) = (times[0], times[times.len() - 1]);
+    let mut y = y0.to_vec();
+    let mut f = system.rhs(t, &y)?;
+    let mut h = initial_step(&y, &f, t_end - t, options);
+    let n = y.len();
+    // previous accepted points, the most recent first
+    let mut history: Vec<(f64, Vec<f64>)> = Vec::new();
+    let mut jacobian: Option<Vec<Vec<f64>>> = None;
+    let mut fresh_jacobian = false;
+    let mut steps = 0;
+    while t < t_end {
+        let (h_step, last) = check_step(t, t_end, h, &mut steps, options)?;
+        let t_new = if last { t_end } else { t + h_step };
+        let order = if history.len() == 2 { 2 } else { 1 };
+        // y_new = a1 y + a2 y_prev + beta h f(t_new, y_new)
+        let (a1, a2, beta, predicted) = if order == 2 {
+            let (t1, y1) = &history[0];
+            let (t2, y2) = &history[1];
+            let omega = h_step / (t - t1);
+            let d = 1. + 2. * omega;
+            let l0 = (t_new - t1) * (t_new - t2) / ((t - t1) * (t - t2));
+            let l1 = (t_new - t) * (t_new - t2) / ((t1 - t) * (t1 - t2));
+            let l2 = (t_new - t) * (t_new - t1) / ((t2 - t) * (t2 - t1));
+            let predicted: Vec<f64> = (0..n)
+                .map(|i| l0 * y[i] + l1 * y1[i] + l2 * y2[i])
+                .collect();
+            (
+                (1. + omega).powi(2) / d,
+                -omega.powi(2) / d,
+                (1. + omega) / d,
+                predicted,
+            )
+        } else {
+            let predicted = y.iter().zip(&f).map(|(y, f)| y + h_step * f).collect();
+            (1., 0., 1., predicted)
+        };
+        let base: Vec<f64> = match history.first() {
+            Some((_, y1)) if order == 2 => (0..n).map(|i| a1 * y[i] + a2 * y1[i]).collect(),
+            _ => y.clone(),
+        };
+
+        if jacobian.is_none() {
+            jacobian = Some(system.jacobian(t, &y)?);
+            fresh_jacobian = true;
+        }
+        let matrix: Vec<Vec<f64>> = jacobian
+            .as_ref()
+            .expect("jacobian")
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, a)| if i == j { 1. } else { 0. } - beta * h_step * a)
+                    .collect()
+            })
+            .collect();
+        let solution = match Lu::new(matrix) {
+            Some(lu) => newton(
+                system,
+                &lu,
+                t_new,
+                beta * h_step,
+                &base,
+                &predicted,
+                options,
+            )?,
+            None => None,
+        };
+        let y_new = match solution {
+            Some(y_new) => y_new,
+            None if !fresh_jacobian => {
+                jacobian = None;
+                continue;
+            }
+            None => {
+                h = h_step / 4.;
+                continue;
+            }
+        };
+
+        let constant = if order == 2 { 2. / 11. } else { 0.5 };
+        let difference: Vec<f64> = y_new
+            .iter()
+            .zip(&predicted)
+            .map(|(a, b)| constant * (a - b))
+            .collect();
+        let error = norm(&difference, &y, &y_new, options);
+        let factor = 0.9 * error.powf(-1. / (order as f64 + 1.));
+        if error <= 1. {
+            let f_new = system.rhs(t_new, &y_new)?;
+            output.record(t, &y, &f, t_new, &y_new, &f_new);
+            history.insert(0, (t, y));
+            history.truncate(2);
+            t = t_new;
+            y = y_new;
+            f = f_new;
+            fresh_jacobian = false;
+            // larger ratios make the variable-step BDF2 unstable
+            h = h_step * factor.min(2.);
+        } else {
+            h = h_step * factor.max(0.2);
+        }
+    }
+    Ok(output.states)
+}
+
+/// Solve $y - \gamma f(t, y) = base$ starting from `predicted`, or `None` if
+/// the iterations do not converge.
+fn newton(
+    system: &OdeSystem,
+    lu: &Lu,
+    t: f64,
+    gamma: f64,
+    base: &[f64],
+    predicted: &[f64],
+    options: &SimulationOptions,
+) -> Result<Option<Vec<f64>>, SimulationError> {
+    let mut y = predicted.to_vec();
+    let mut previous = f64::INFINITY;
+    for _ in 0..6 {
+        let f = system.rhs(t, &y)?;
+        let residual: Vec<f64> = (0..y.len())
+            .map(|i| base[i] + gamma * f[i] - y[i])
+            .collect();
+        let delta = lu.solve(&residual);
+        for (y, d) in y.iter_mut().zip(&delta) {
+            *y += d;
+        }
+        let size = norm(&delta, predicted, &y, options);
+        if !size.is_finite() || size > 2. * previous {
+            return Ok(None);
+        }
+        if size < 0.01 {
+            return Ok(Some(y));
+        }
+        previous = size;
+    }
+    Ok(None)
+}
+
+/// Step to take from `t`, shortened to end at `t_end`, and whether it is
+/// the last one.
+fn check_step(
+    t: f64,
+    t_end: f64,
+    h: f64,
+    steps: &mut usize,
+    options: &SimulationOptions,
+) -> Result<(f64, bool), SimulationError> {
+    if *steps >= options.max_steps {
+        return Err(SimulationError::MaxSteps(t));
+    }
+    *steps += 1;
+    if h.is_nan() || h <= 4. * f64::EPSILON * t.abs().max(t_end - t) {
+        return Err(SimulationError::StepSizeTooSmall(t));
+    }
+    Ok(if t + h >= t_end {
+        (t_end - t, true)
+    } else {
+        (h, false)
+    })
+}
+
+/// Weighted root mean square of `error`, with the tolerances relative to
+/// the largest magnitude between the states `y0` and `y1`.
+fn norm(error: &[f64], y0: &[f64], y1: &[f64], options: &SimulationOptions) -> f64 {
+    if error.is_empty() {
+        return 0.;
+    }
+    let sum: f64 = error
+        .iter()
+        .zip(y0.iter().zip(y1))
+        .map(|(e, (a, b))| {
+            let scale =
+                options.absolute_tolerance + options.relative_tolerance * a.abs().max(b.abs());
+            (e / scale).powi(2)
+        })
+        .sum();
+    (sum / error.len() as f64).sqrt()
+}
+
+/// First step, of about a hundredth of the time scale of the initial
+/// derivatives.
+fn initial_step(y: &[f64], f: &[f64], span: f64, options: &SimulationOptions) -> f64 {
+    let (d0, d1) = (norm(y, y, y, options), norm(f, y, y, options));
+    let h = if d0 < 1e-5 || d1 < 1e-5 {
+        1e-6 * span
+    } else {
+        0.01 * d0 / d1
+    };
+    h.min(span)
+}
+
+/// States interpolated at the output times.
+struct Output<'a> {
+    times: &'a [f64],
+    states: Vec<Vec<f64>>,
+}
+
+impl<'a> Output<'a> {
+    fn new(times: &'a [f64], y0: &[f64]) -> Self {
+        Self {
+            times,
+            states: vec![y0.to_vec()],
+        }
+    }
+
+    /// Interpolate the output times within the accepted step from `t0` to
+    /// `t1`.
+    fn record(&mut self, t0: f64, y0: &[f64], f0: &[f64], t1: f64, y1: &[f64], f1: &[f64]) {
+        let h = t1 - t0;
+        while let Some(&t) = self.times.get(self.states.len()) {
+            if t > t1 {
+                break;
+            }
+            let s = (t - t0) / h;
+            let (s2, s3) = (s * s, s * s * s);
+            let h00 = 2. * s3 - 3. * s2 + 1.;
+            let h10 = s3 - 2. * s2 + s;
+            let h01 = -2. * s3 + 3. * s2;
+            let h11 = s3 - s2;
+            self.states.push(
+                (0..y0.len())
+                    .map(|i| h00 * y0[i] + h10 * h * f0[i] + h01 * y1[i] + h11 * h * f1[i])
+                    .collect(),
+            );
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::cmp::Ordering;
+
+/// LU decomposition with partial pivoting of a square matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Lu {
+    /// L below the diagonal, with an implicit unit diagonal, and U above.
+    lu: Vec<Vec<f64>>,
+    /// Row of the original matrix at each row of the decomposition.
+    rows: Vec<usize>,
+}
+
+impl Lu {
+    /// Decompose `matrix`, or `None` if it is singular.
+    pub(crate) fn new(mut matrix: Vec<Vec<f64>>) -> Option<Self> {
+        let n = matrix.len();
+        let mut rows: Vec<usize> = (0..n).collect();
+        for k in 0..n {
+            let pivot = (k..n).max_by(|&i, &j| {
+                matrix[i][k]
+                    .abs()
+                    .partial_cmp(&matrix[j][k].abs())
+                    .unwrap_or(Ordering::Equal)
+            })?;
+            if matrix[pivot][k] == 0. || !matrix[pivot][k].is_finite() {
+                return None;
+            }
+            matrix.swap(k, pivot);
+            rows.swap(k, pivot);
+            let (top, bottom) = matrix.split_at_mut(k + 1);
+            let pivot_row = &top[k];
+            for row in bottom {
+                let factor = row[k] / pivot_row[k];
+                row[k] = factor;
+                for (a, b) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                    *a -= factor * b;
+                }
+            }
+        }
+        Some(Self { lu: matrix, rows })
+    }
+
+    /// Solution $x$ of $A x = b$.
+    pub(crate) fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let mut x: Vec<f64> = self.rows.iter().map(|&i| b[i]).collect();
+        for i in 0..x.len() {
+            let sum: f64 = self.lu[i][..i]
+                .iter()
+                .zip(&x[..i])
+                .map(|(a, b)| a * b)
+                .sum();
+            x[i] -= sum;
+        }
+        for i in (0..x.len()).rev() {
+            let sum: f64 = self.lu[i][i + 1..]
+                .iter()
+                .zip(&x[i + 1..])
+                .map(|(a, b)| a * b)
+                .sum();
+            x[i] = (x[i] - sum) / self.lu[i][i];
+        }
+        x
+    }
+}
+
+/// Basis of the null space $\{x : A x = 0\}$ of the matrix with `rows` and
+/// `n` columns, from its reduced row echelon form. Each vector comes with
+/// its free column, where it is 1 and the others are 0.
+pub(crate) fn null_space(mut rows: Vec<Vec<f64>>, n: usize) -> Vec<(usize, Vec<f64>)> {
+    let scale = rows.iter().flatten().fold(1f64, |max, a| max.max(a.abs()));
+    let tolerance = 1e-10 * scale;
+    let mut pivots = Vec::new();
+    for col in 0..n {
+        let row = pivots.len();
+        if row == rows.len() {
+            break;
+        }
+        let best = (row..rows.len())
+            .max_by(|&i, &j| {
+                rows[i][col]
+                    .abs()
+                    .partial_cmp(&rows[j][col].abs())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .expect("remaining rows");
+        if rows[best][col].abs() <= tolerance {
+            continue;
+        }
+        rows.swap(row, best);
+        let pivot = rows[row][col];
+        for a in rows[row].iter_mut() {
+            *a /= pivot;
+        }
+        let pivot_row = rows[row].clone();
+        for (i, other) in rows.iter_mut().enumerate() {
+            let factor = other[col];
+            if i != row && factor != 0. {
+                for (a, b) in other.iter_mut().zip(&pivot_row) {
+                    *a -= factor * b;
+                }
+            }
+        }
+        pivots.push(col);
+    }
+    (0..n)
+        .filter(|col| !pivots.contains(col))
+        .map(|free| {
+            let mut vector = vec![0.; n];
+            vector[free] = 1.;
+            for (row, &col) in pivots.iter().enumerate() {
+                if rows[row][free].abs() > tolerance {
+                    vector[col] = -rows[row][free];
+                }
+            }
+            (free, vector)
+        })
+        .collect()
+}
+
+/// Eigenvalues `(real, imaginary)` of a square matrix, from its reduction
+/// to Hessenberg form and the Francis double-shift QR algorithm (`elmhes`
+/// and `hqr` of Numerical Recipes), or `None` if the iterations do not
+/// converge.
+#[allow(clippy::needless_range_loop)]
+pub(crate) fn eigenvalues(mut a: Vec<Vec<f64>>) -> Option<Vec<(f64, f64)>> {
+    let n = a.len();
+    hessenberg(&mut a);
+    let anorm: f64 = (0..n)
+        .flat_map(|i| (i.saturating_sub(1)..n).map(move |j| (i, j)))
+        .map(|(i, j)| a[i][j].abs())
+        .sum();
+    let mut values = vec![(0., 0.); n];
+    let mut nn = n as isize - 1;
+    let mut t = 0.;
+    while nn >= 0 {
+        let top = nn as usize;
+        let mut its = 0;
+        loop {
+            // look for a single small subdiagonal element
+            let mut l = top;
+            while l >= 1 {
+                let mut s = a[l - 1][l - 1].abs() + a[l][l].abs();
+                if s == 0. {
+                    s = anorm;
+                }
+                if a[l][l - 1].abs() + s == s {
+                    a[l][l - 1] = 0.;
+                    break;
+                }
+                l -= 1;
+            }
+            let mut x = a[top][top];
+            if l == top {
+                values[top] = (x + t, 0.);
+                nn -= 1;
+                break;
+            }
+            let mut y = a[top - 1][top - 1];
+            let mut w = a[top][top - 1] * a[top - 1][top];
+            if l == top - 1 {
+                let p = 0.5 * (y - x);
+                let q = p * p + w;
+                let z = q.abs().sqrt();
+                x += t;
+                if q >= 0. {
+                    let z = p + z.copysign(p);
+                    values[top - 1] = (x + z, 0.);
+                    values[top] = (if z != 0. { x - w / z } else { x + z }, 0.);
+                } else {
+                    values[top - 1] = (x + p, -z);
+                    values[top] = (x + p, z);
+                }
+                nn -= 2;
+                break;
+            }
+            if its == 30 {
+                return None;
+            }
+            if its == 10 || its == 20 {
+                // exceptional shift
+                t += x;
+                for (i, row) in a.iter_mut().enumerate().take(top + 1) {
+                    row[i] -= x;
+                }
+                let s = a[top][top - 1].abs() + a[top - 1][top - 2].abs();
+                x = 0.75 * s;
+                y = x;
+                w = -0.4375 * s * s;
+            }
+            its += 1;
+            let mut m = top - 2;
+            let (mut p, mut q, mut r);
+            loop {
+                let z = a[m][m];
+                let rr = x - z;
+                let ss = y - z;
+                p = (rr * ss - w) / a[m + 1][m] + a[m][m + 1];
+                q = a[m + 1][m + 1] - z - rr - ss;
+                r = a[m + 2][m + 1];
+                let s = p.abs() + q.abs() + r.abs();
+                p /= s;
+                q /= s;
+                r /= s;
+                if m == l {
+                    break;
+                }
+                let u = a[m][m - 1].abs() * (q.abs() + r.abs());
+                let v = p.abs() * (a[m - 1][m - 1].abs() + z.abs() + a[m + 1][m + 1].abs());
+                if u + v == v {
+                    break;
+                }
+                m -= 1;
+            }
+            for i in m + 2..=top {
+                a[i][i - 2] = 0.;
+                if i != m + 2 {
+                    a[i][i - 3] = 0.;
+                }
+            }
+            // double QR step on rows l..=top and columns m..=top
+            for k in m..top {
+                if k != m {
+                    p = a[k][k - 1];
+                    q = a[k + 1][k - 1];
+                    r = if k != top - 1 { a[k + 2][k - 1] } else { 0. };
+                    x = p.abs() + q.abs() + r.abs();
+                    if x != 0. {
+                        p /= x;
+                        q /= x;
+                        r /= x;
+                    }
+                }
+                let s = (p * p + q * q + r * r).sqrt().copysign(p);
+                if s == 0. {
+                    continue;
+                }
+                if k == m {
+                    if l != m {
+                        a[k][k - 1] = -a[k][k - 1];
+                    }
+                } else {
+                    a[k][k - 1] = -s * x;
+                }
+                p += s;
+                x = p / s;
+                y = q / s;
+                let z = r / s;
+                q /= p;
+                r /= p;
+                for j in k..=top {
+                    p = a[k][j] + q * a[k + 1][j];
+                    if k != top - 1 {
+                        p += r * a[k + 2][j];
+                        a[k + 2][j] -= p * z;
+                    }
+                    a[k + 1][j] -= p * y;
+                    a[k][j] -= p * x;
+                }
+                for row in a.iter_mut().take(top.min(k + 3) + 1).skip(l) {
+                    p = x * row[k] + y * row[k + 1];
+                    if k != top - 1 {
+                        p += z * row[k + 2];
+                        row[k + 2] -= p * r;
+                    }
+                    row[k + 1] -= p * q;
+                    row[k] -= p;
+                }
+            }
+        }
+    }
+    Some(values)
+}
+
+/// Reduce `a` to upper Hessenberg form by similarity transformations
+/// (Gaussian elimination with pivoting).
+#[allow(clippy::needless_range_loop)]
+fn hessenberg(a: &mut [Vec<f64>]) {
+    let n = a.len();
+    for m in 1..n.saturating_sub(1) {
+        let pivot = (m..n)
+            .max_by(|&i, &j| {
+                a[i][m - 1]
+                    .abs()
+                    .partial_cmp(&a[j][m - 1].abs())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .expect("rows below the diagonal");
+        let x = a[pivot][m - 1];
+        if pivot != m {
+            a.swap(pivot, m);
+            for row in a.iter_mut() {
+                row.swap(pivot, m);
+            }
+        }
+        if x == 0. {
+            continue;
+        }
+        for i in m + 1..n {
+            let y = a[i][m - 1] / x;
+            if y == 0. {
+                continue;
+            }
+            for j in m..n {
+                a[i][j] -= y * a[m][j];
+            }
+            for row in a.iter_mut() {
+                row[m] += y * row[i];
+            }
+        }
+    }
+    for (i, row) in a.iter_mut().enumerate() {
+        for a in row.iter_mut().take(i.saturating_sub(1)) {
+            *a = 0.;
+        }
+    }
+}
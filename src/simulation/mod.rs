@@ -0,0 +1,215 @@
+//! Deterministic and stochastic simulation of kinetic models.
+//!
+//! The [`OdeSystem`] of a [`Model`] has as state variables the amounts of its
+//! species, changed by the rates of the [`KineticLaw`](crate::KineticLaw)s
+//! times their stoichiometry, and the symbols with a
+//! [`Rule::RateRule`](crate::Rule::RateRule). Assignment rules and the
+//! concentrations of the species are recomputed from the state, with the
+//! current compartment sizes, whenever the right-hand side is evaluated, and
+//! the initial state honours the initial assignments.
+//!
+//! [`simulate`] integrates it with an adaptive Dormand–Prince RK45 or, for
+//! stiff systems, an implicit variable-step BDF. For low copy numbers,
+//! [`simulate_stochastic`] samples trajectories of the discrete amounts with
+//! Gillespie's algorithm. [`steady_state`] finds the roots of the system
+//! and their stability. Requires the `simulation` feature (enabled by
+//! default).
+mod integrators;
+mod linalg;
+mod random;
+mod steady_state;
+mod stochastic;
+mod system;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::mathml::EvalError;
+use crate::Model;
+pub use random::{RandomSource, Xoshiro256};
+pub use steady_state::{
+    steady_state, steady_state_with_options, ConservedMoiety, Stability, SteadyState,
+    SteadyStateOptions,
+};
+pub use stochastic::{simulate_stochastic, StochasticOptions};
+pub use system::OdeSystem;
+
+/// Error raised while building or integrating an [`OdeSystem`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    /// An expression of the model could not be evaluated.
+    Eval(EvalError),
+    /// A reaction without a kinetic law.
+    MissingKineticLaw(String),
+    /// A symbol without a value, initial assignment nor assignment rule.
+    MissingValue(String),
+    /// The assignments of this symbol depend on its own value.
+    AlgebraicLoop(String),
+    /// This function definition calls itself, directly or through other
+    /// function definitions.
+    RecursiveFunction(String),
+    /// A construct of the model that cannot be simulated, such as events
+    /// or algebraic rules.
+    Unsupported(String),
+    /// A time span or number of output points that cannot be simulated.
+    BadTimeSpan,
+    /// The step size became too small at this time.
+    StepSizeTooSmall(f64),
+    /// The integrator took the maximum number of steps at this time.
+    MaxSteps(f64),
+    /// The kinetic law of this reaction is negative or not a number, so it
+    /// is not a propensity.
+    NegativePropensity(String),
+    /// Neither the Newton iterations nor the integration of the model
+    /// reached a steady state.
+    NoSteadyState,
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulationError::Eval(e) => write!(f, "{}", e),
+            SimulationError::MissingKineticLaw(id) => {
+                write!(f, "reaction `{}` has no kinetic law", id)
+            }
+            SimulationError::MissingValue(id) => write!(f, "`{}` has no value", id),
+            SimulationError::AlgebraicLoop(id) => {
+                write!(f, "the assignment of `{}` depends on itself", id)
+            }
+            SimulationError::RecursiveFunction(id) => {
+                write!(f, "the function definition `{}` calls itself", id)
+            }
+            SimulationError::Unsupported(construct) => {
+                write!(f, "{} are not supported", construct)
+            }
+            SimulationError::BadTimeSpan => write!(f, "bad time span"),
+            SimulationError::StepSizeTooSmall(t) => {
+                write!(f, "the step size became too small at t = {}", t)
+            }
+            SimulationError::MaxSteps(t) => {
+                write!(f, "the maximum number of steps was reached at t = {}", t)
+            }
+            SimulationError::NegativePropensity(id) => {
+                write!(f, "the propensity of reaction `{}` is negative", id)
+            }
+            SimulationError::NoSteadyState => write!(f, "no steady state was found"),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl From<EvalError> for SimulationError {
+    fn from(e: EvalError) -> Self {
+        SimulationError::Eval(e)
+    }
+}
+
+/// Integration method of [`simulate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Explicit Dormand–Prince 5(4) Runge-Kutta, for non-stiff systems.
+    Rk45,
+    /// Implicit variable-step backward differentiation formula of order 2,
+    /// for stiff systems.
+    Bdf,
+}
+
+/// Options of [`simulate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationOptions {
+    pub method: Method,
+    /// Local error allowed per step, relative to the magnitude of the state.
+    pub relative_tolerance: f64,
+    /// Local error allowed per step for state variables close to zero.
+    pub absolute_tolerance: f64,
+    /// Steps that the integrator may take, accepted or not.
+    pub max_steps: usize,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            method: Method::Rk45,
+            relative_tolerance: 1e-6,
+            absolute_tolerance: 1e-9,
+            max_steps: 100_000,
+        }
+    }
+}
+
+/// Result of [`simulate`] and [`simulate_stochastic`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeCourse {
+    /// Output times.
+    pub time: Vec<f64>,
+    /// Value of each species (its concentration, or its amount if it has
+    /// only substance units) and of each parameter and compartment that is
+    /// not constant at the output times, by identifier.
+    pub values: HashMap<String, Vec<f64>>,
+}
+
+/// Integrate the kinetic `model` from time 0 to `t_end`, reporting the
+/// values of its symbols at `n_points` evenly spaced times.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::simulation::{simulate, SimulationOptions};
+/// use rust_sbml::Model;
+///
+/// let model = Model::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model>
+///       <listOfCompartments>
+///         <compartment id="c" size="1" constant="true"/>
+///       </listOfCompartments>
+///       <listOfSpecies>
+///         <species id="S" compartment="c" initialConcentration="1"
+///           hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+///       </listOfSpecies>
+///       <listOfReactions>
+///         <reaction id="decay" reversible="false">
+///           <listOfReactants>
+///             <speciesReference species="S" stoichiometry="1" constant="true"/>
+///           </listOfReactants>
+///           <kineticLaw>
+///             <math xmlns="http://www.w3.org/1998/Math/MathML">
+///               <apply><times/><cn> 0.5 </cn><ci> S </ci></apply>
+///             </math>
+///           </kineticLaw>
+///         </reaction>
+///       </listOfReactions>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let course = simulate(&model, 2., 11, &SimulationOptions::default()).unwrap();
+/// assert_eq!(course.time[10], 2.);
+/// assert!((course.values["S"][10] - (-1f64).exp()).abs() < 1e-5);
+/// ```
+pub fn simulate(
+    model: &Model,
+    t_end: f64,
+    n_points: usize,
+    options: &SimulationOptions,
+) -> Result<TimeCourse, SimulationError> {
+    let time = output_times(t_end, n_points)?;
+    let system = OdeSystem::new(model)?;
+    let states = match options.method {
+        Method::Rk45 => integrators::rk45(&system, &system.initial_state, &time, options)?,
+        Method::Bdf => integrators::bdf(&system, &system.initial_state, &time, options)?,
+    };
+    let values = system.time_course(&time, &states)?;
+    Ok(TimeCourse { time, values })
+}
+
+/// `n_points` evenly spaced times from 0 to `t_end`.
+fn output_times(t_end: f64, n_points: usize) -> Result<Vec<f64>, SimulationError> {
+    if !(t_end > 0. && t_end.is_finite()) || n_points < 2 {
+        return Err(SimulationError::BadTimeSpan);
+    }
+    Ok((0..n_points)
+        .map(|i| t_end * i as f64 / (n_points - 1) as f64)
+        .collect())
+}
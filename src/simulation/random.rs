@@ -0,0 +1,89 @@
+/// Source of uniform random numbers for the stochastic simulations.
+pub trait RandomSource {
+    /// Uniform sample in $[0, 1)$.
+    fn next_f64(&mut self) -> f64;
+}
+
+/// The xoshiro256** generator of Blackman and Vigna, a fast and
+/// reproducible source for [`RandomSource`]. It is not cryptographically
+/// secure.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::simulation::{RandomSource, Xoshiro256};
+///
+/// let mut rng = Xoshiro256::seed_from_u64(42);
+/// let sample = rng.next_f64();
+/// assert!((0. ..1.).contains(&sample));
+/// assert_eq!(Xoshiro256::seed_from_u64(42).next_f64(), sample);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    /// Generator whose state is expanded from `seed` with SplitMix64.
+    pub fn seed_from_u64(seed: u64) -> Self {
+        let mut z = seed;
+        let mut next = || {
+            z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut x = z;
+            x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            x ^ (x >> 31)
+        };
+        Self {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+}
+
+impl RandomSource for Xoshiro256 {
+    fn next_f64(&mut self) -> f64 {
+        // the 53 high bits, as many as the mantissa holds
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Exponential sample of rate `rate`.
+pub(super) fn exponential(rate: f64, rng: &mut impl RandomSource) -> f64 {
+    -(1. - rng.next_f64()).ln() / rate
+}
+
+/// Poisson sample of mean `mean`: by inversion for small means and from a
+/// rounded normal approximation for large ones.
+pub(super) fn poisson(mean: f64, rng: &mut impl RandomSource) -> f64 {
+    if mean <= 0. {
+        return 0.;
+    }
+    if mean < 30. {
+        let limit = (-mean).exp();
+        let mut k = 0.;
+        let mut p = rng.next_f64();
+        while p > limit {
+            k += 1.;
+            p *= rng.next_f64();
+        }
+        k
+    } else {
+        // Box-Muller
+        let z = (-2. * (1. - rng.next_f64()).ln()).sqrt()
+            * (2. * std::f64::consts::PI * rng.next_f64()).cos();
+        (mean + mean.sqrt() * z).round().max(0.)
+    }
+}
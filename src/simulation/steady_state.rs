@@ -0,0 +1,350 @@
+//! Steady states of kinetic models.
+use std::collections::HashMap;
+
+use super::linalg::{eigenvalues, null_space, Lu};
+use super::system::forward_differences;
+use super::{integrators, Method, OdeSystem, SimulationError, SimulationOptions};
+use crate::Model;
+
+/// Options of [`steady_state_with_options`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteadyStateOptions {
+    /// Largest rate of change of the state variables, relative to their
+    /// magnitude (or one unit), at a steady state.
+    pub tolerance: f64,
+    /// Newton iterations from each starting point.
+    pub max_iterations: usize,
+    /// Longest time that the model is integrated for when the Newton
+    /// iterations do not converge.
+    pub max_time: f64,
+    /// Options of the integration.
+    pub integration: SimulationOptions,
+}
+
+impl Default for SteadyStateOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-9,
+            max_iterations: 50,
+            max_time: 1e6,
+            integration: SimulationOptions {
+                method: Method::Bdf,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Linear stability of a steady state, from the eigenvalues of the Jacobian
+/// of the system without its conserved moieties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stability {
+    /// All the eigenvalues have a negative real part.
+    Stable,
+    /// Some eigenvalue has a positive real part.
+    Unstable,
+    /// The largest real part of the eigenvalues is zero, so the linear
+    /// approximation does not decide.
+    Marginal,
+}
+
+/// Linear combination of the state variables that no reaction changes:
+/// $\sum_i c_i y_i$ stays at `total`, the value in the initial state. The
+/// species take part with their amounts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConservedMoiety {
+    pub coefficients: Vec<(String, f64)>,
+    pub total: f64,
+}
+
+/// Result of [`steady_state`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteadyState {
+    /// Value of each species (its concentration, or its amount if it has
+    /// only substance units) and of each parameter and compartment that is
+    /// not constant, by identifier.
+    pub values: HashMap<String, f64>,
+    /// Rate of each reaction, by identifier.
+    pub fluxes: HashMap<String, f64>,
+    /// Eigenvalues `(real, imaginary)` of the reduced Jacobian.
+    pub eigenvalues: Vec<(f64, f64)>,
+    pub stability: Stability,
+    /// Conserved moieties, whose dependent variables were removed from the
+    /// Newton iterations.
+    pub conserved_moieties: Vec<ConservedMoiety>,
+    /// Whether the model had to be integrated in time to find a starting
+    /// point from which the Newton iterations converge.
+    pub integrated: bool,
+}
+
+/// [`steady_state_with_options`] with the default options.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::simulation::{steady_state, Stability};
+/// use rust_sbml::Model;
+///
+/// let model = Model::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model>
+///       <listOfCompartments>
+///         <compartment id="c" size="1" constant="true"/>
+///       </listOfCompartments>
+///       <listOfSpecies>
+///         <species id="S" compartment="c" initialConcentration="0"
+///           hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+///       </listOfSpecies>
+///       <listOfReactions>
+///         <reaction id="synthesis" reversible="false">
+///           <listOfProducts>
+///             <speciesReference species="S" stoichiometry="1" constant="true"/>
+///           </listOfProducts>
+///           <kineticLaw>
+///             <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 2 </cn></math>
+///           </kineticLaw>
+///         </reaction>
+///         <reaction id="degradation" reversible="false">
+///           <listOfReactants>
+///             <speciesReference species="S" stoichiometry="1" constant="true"/>
+///           </listOfReactants>
+///           <kineticLaw>
+///             <math xmlns="http://www.w3.org/1998/Math/MathML">
+///               <apply><times/><cn> 0.5 </cn><ci> S </ci></apply>
+///             </math>
+///           </kineticLaw>
+///         </reaction>
+///       </listOfReactions>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let steady = steady_state(&model).unwrap();
+/// assert!((steady.values["S"] - 4.).abs() < 1e-8);
+/// assert!((steady.fluxes["degradation"] - 2.).abs() < 1e-8);
+/// assert_eq!(steady.stability, Stability::Stable);
+/// ```
+pub fn steady_state(model: &Model) -> Result<SteadyState, SimulationError> {
+    steady_state_with_options(model, &SteadyStateOptions::default())
+}
+
+/// Find a root of the right-hand side of the [`OdeSystem`] of `model`.
+///
+/// The conserved moieties of the stoichiometry make the Jacobian singular,
+/// so one variable of each of them is computed from the others and its
+/// total. The remaining variables are solved with damped Newton iterations
+/// from the initial state and, if they do not converge to a state with
+/// non-negative species amounts, from the states reached after integrating
+/// the model for increasing spans of time, up to
+/// [`SteadyStateOptions::max_time`]. Time-dependent expressions are taken
+/// at time 0.
+pub fn steady_state_with_options(
+    model: &Model,
+    options: &SteadyStateOptions,
+) -> Result<SteadyState, SimulationError> {
+    let system = OdeSystem::new(model)?;
+    let reduced = Reduced::new(&system);
+    let is_species: Vec<bool> = system
+        .variables
+        .iter()
+        .map(|id| model.species.contains_key(id))
+        .collect();
+
+    let mut y = system.initial_state.clone();
+    let mut span = 1.;
+    let mut integrated = false;
+    let solution = loop {
+        let z: Vec<f64> = reduced.independent.iter().map(|&i| y[i]).collect();
+        if let Some(z) = reduced.newton(z, options)? {
+            let y = reduced.expand(&z);
+            let scale = y.iter().fold(1f64, |max, y| max.max(y.abs()));
+            if y.iter()
+                .zip(&is_species)
+                .all(|(y, species)| !species || *y >= -options.tolerance * scale)
+            {
+                break y;
+            }
+        }
+        if span > options.max_time {
+            return Err(SimulationError::NoSteadyState);
+        }
+        let states = match options.integration.method {
+            Method::Rk45 => integrators::rk45(&system, &y, &[0., span], &options.integration)?,
+            Method::Bdf => integrators::bdf(&system, &y, &[0., span], &options.integration)?,
+        };
+        y = states.into_iter().last().expect("final state");
+        integrated = true;
+        span *= 10.;
+    };
+
+    let jacobian = reduced.jacobian(&reduced.reduce(&solution))?;
+    let eigenvalues = eigenvalues(jacobian).ok_or(SimulationError::NoSteadyState)?;
+    let scale = eigenvalues
+        .iter()
+        .fold(1f64, |max, (re, im)| max.max(re.hypot(*im)));
+    let largest = eigenvalues
+        .iter()
+        .fold(f64::NEG_INFINITY, |max, (re, _)| max.max(*re));
+    let stability = if largest < -1e-6 * scale {
+        Stability::Stable
+    } else if largest > 1e-6 * scale {
+        Stability::Unstable
+    } else {
+        Stability::Marginal
+    };
+    let rates = system.reaction_rates(0., &solution)?;
+    Ok(SteadyState {
+        values: system.observe(0., &solution)?,
+        fluxes: system
+            .reaction_changes()
+            .iter()
+            .map(|(id, _)| id.to_string())
+            .zip(rates)
+            .collect(),
+        eigenvalues,
+        stability,
+        conserved_moieties: reduced
+            .laws
+            .iter()
+            .map(|law| ConservedMoiety {
+                coefficients: std::iter::once((law.dependent, 1.))
+                    .chain(law.terms.iter().copied())
+                    .map(|(i, c)| (system.variables[i].clone(), c))
+                    .collect(),
+                total: law.total,
+            })
+            .collect(),
+        integrated,
+    })
+}
+
+/// The dependent variable of a conserved moiety is
+/// $y_d = total - \sum_j c_j y_j$.
+#[derive(Debug, Clone, PartialEq)]
+struct Law {
+    dependent: usize,
+    terms: Vec<(usize, f64)>,
+    total: f64,
+}
+
+/// Right-hand side of the independent variables of a system, as a function
+/// of themselves.
+struct Reduced<'a> {
+    system: &'a OdeSystem,
+    independent: Vec<usize>,
+    laws: Vec<Law>,
+}
+
+impl<'a> Reduced<'a> {
+    fn new(system: &'a OdeSystem) -> Self {
+        let n = system.variables.len();
+        let changes = system.reaction_changes();
+        // the conserved moieties are the left null space of the
+        // stoichiometry; the variables with a rate rule are never conserved
+        let mut rows: Vec<Vec<f64>> = changes
+            .iter()
+            .map(|(_, change)| {
+                let mut row = vec![0.; n];
+                for &(variable, coefficient) in change.iter() {
+                    row[variable] += coefficient;
+                }
+                row
+            })
+            .collect();
+        rows.extend(system.rate_rule_variables().into_iter().map(|variable| {
+            let mut row = vec![0.; n];
+            row[variable] = 1.;
+            row
+        }));
+        let laws: Vec<Law> = null_space(rows, n)
+            .into_iter()
+            .map(|(dependent, vector)| Law {
+                dependent,
+                terms: vector
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, c)| i != dependent && *c != 0.)
+                    .map(|(i, c)| (i, *c))
+                    .collect(),
+                total: vector
+                    .iter()
+                    .zip(&system.initial_state)
+                    .map(|(c, y)| c * y)
+                    .sum(),
+            })
+            .collect();
+        let independent = (0..n)
+            .filter(|i| laws.iter().all(|law| law.dependent != *i))
+            .collect();
+        Self {
+            system,
+            independent,
+            laws,
+        }
+    }
+
+    fn reduce(&self, y: &[f64]) -> Vec<f64> {
+        self.independent.iter().map(|&i| y[i]).collect()
+    }
+
+    fn expand(&self, z: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.; self.system.variables.len()];
+        for (&i, value) in self.independent.iter().zip(z) {
+            y[i] = *value;
+        }
+        for law in &self.laws {
+            y[law.dependent] = law.total - law.terms.iter().map(|(j, c)| c * y[*j]).sum::<f64>();
+        }
+        y
+    }
+
+    fn residual(&self, z: &[f64]) -> Result<Vec<f64>, SimulationError> {
+        let f = self.system.rhs(0., &self.expand(z))?;
+        Ok(self.independent.iter().map(|&i| f[i]).collect())
+    }
+
+    fn jacobian(&self, z: &[f64]) -> Result<Vec<Vec<f64>>, SimulationError> {
+        forward_differences(|z| self.residual(z), z)
+    }
+
+    /// Damped Newton iterations from `z`, or `None` if they do not converge.
+    fn newton(
+        &self,
+        mut z: Vec<f64>,
+        options: &SteadyStateOptions,
+    ) -> Result<Option<Vec<f64>>, SimulationError> {
+        let converged = |z: &[f64], f: &[f64]| {
+            z.iter()
+                .zip(f)
+                .all(|(z, f)| f.abs() <= options.tolerance * z.abs().max(1.))
+        };
+        let size = |f: &[f64]| f.iter().map(|f| f * f).sum::<f64>();
+        let mut f = self.residual(&z)?;
+        for _ in 0..options.max_iterations {
+            if converged(&z, &f) {
+                return Ok(Some(z));
+            }
+            let lu = match Lu::new(self.jacobian(&z)?) {
+                Some(lu) => lu,
+                None => return Ok(None),
+            };
+            let delta = lu.solve(&f);
+            let mut damping = 1.;
+            loop {
+                let candidate: Vec<f64> =
+                    z.iter().zip(&delta).map(|(z, d)| z - damping * d).collect();
+                let f_candidate = self.residual(&candidate)?;
+                if size(&f_candidate) < size(&f) {
+                    z = candidate;
+                    f = f_candidate;
+                    break;
+                }
+                damping /= 2.;
+                if damping < 1e-4 {
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(if converged(&z, &f) { Some(z) } else { None })
+    }
+}
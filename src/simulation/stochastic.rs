@@ -0,0 +1,216 @@
+//! Stochastic simulation of kinetic models, in discrete amounts of
+//! substance.
+use super::random::{exponential, poisson, RandomSource};
+use super::{output_times, OdeSystem, SimulationError, TimeCourse};
+use crate::Model;
+
+/// Options of [`simulate_stochastic`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StochasticOptions {
+    /// Leap over several reactions at a time (tau-leaping), bounding the
+    /// relative change of the species amounts in a leap by this value.
+    /// `None` simulates every reaction with the exact direct method.
+    pub tau_leaping: Option<f64>,
+    /// Reactions or leaps that may be simulated.
+    pub max_steps: usize,
+}
+
+impl Default for StochasticOptions {
+    fn default() -> Self {
+        Self {
+            tau_leaping: None,
+            max_steps: 10_000_000,
+        }
+    }
+}
+
+/// Direct steps taken when a leap would be too short to pay off.
+const DIRECT_STEPS: usize = 100;
+
+/// Simulate one trajectory of `model` from time 0 to `t_end` with
+/// Gillespie's stochastic simulation algorithm, reporting the values of its
+/// symbols at `n_points` evenly spaced times.
+///
+/// The state is the amount of each species, from its `initialAmount` (or
+/// initial concentration times the size of its compartment) rounded to an
+/// integer, and the [`KineticLaw`](crate::KineticLaw) of each reaction is
+/// its propensity: the probability per unit of time that it occurs once.
+/// Species with `hasOnlySubstanceUnits` take part in the laws as amounts
+/// (and are reported so), the others as concentrations. The propensities
+/// must not depend on time between reactions, and rate rules are not
+/// supported.
+///
+/// With [`StochasticOptions::tau_leaping`] the number of occurrences of each
+/// reaction in a leap is drawn from a Poisson distribution, as in the
+/// tau-selection of Cao, Gillespie and Petzold (2006), falling back to the
+/// direct method when the leap would be shorter than a few reactions.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::simulation::{simulate_stochastic, StochasticOptions, Xoshiro256};
+/// use rust_sbml::Model;
+///
+/// let model = Model::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model>
+///       <listOfCompartments>
+///         <compartment id="cell" size="1" constant="true"/>
+///       </listOfCompartments>
+///       <listOfSpecies>
+///         <species id="mRNA" compartment="cell" initialAmount="10"
+///           hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+///       </listOfSpecies>
+///       <listOfReactions>
+///         <reaction id="degradation" reversible="false">
+///           <listOfReactants>
+///             <speciesReference species="mRNA" stoichiometry="1" constant="true"/>
+///           </listOfReactants>
+///           <kineticLaw>
+///             <math xmlns="http://www.w3.org/1998/Math/MathML">
+///               <apply><times/><cn> 0.1 </cn><ci> mRNA </ci></apply>
+///             </math>
+///           </kineticLaw>
+///         </reaction>
+///       </listOfReactions>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let mut rng = Xoshiro256::seed_from_u64(1);
+/// let course = simulate_stochastic(&model, 20., 5, &StochasticOptions::default(), &mut rng)
+///     .unwrap();
+/// let mrna = &course.values["mRNA"];
+/// assert_eq!(mrna[0], 10.);
+/// assert!(mrna.windows(2).all(|w| w[1] <= w[0] && w[1].fract() == 0.));
+/// ```
+pub fn simulate_stochastic(
+    model: &Model,
+    t_end: f64,
+    n_points: usize,
+    options: &StochasticOptions,
+    rng: &mut impl RandomSource,
+) -> Result<TimeCourse, SimulationError> {
+    let time = output_times(t_end, n_points)?;
+    let system = OdeSystem::new(model)?;
+    if system.has_rate_rules() {
+        return Err(SimulationError::Unsupported(
+            "rate rules in stochastic simulations".to_string(),
+        ));
+    }
+    let changes = system.reaction_changes();
+    let mut y: Vec<f64> = system.initial_state.iter().map(|x| x.round()).collect();
+    let mut t = 0.;
+    let mut states = Vec::with_capacity(n_points);
+    let mut steps = 0;
+    let mut direct_steps = 0;
+    loop {
+        if steps >= options.max_steps {
+            return Err(SimulationError::MaxSteps(t));
+        }
+        steps += 1;
+        let rates = system.reaction_rates(t, &y)?;
+        if let Some(i) = rates.iter().position(|a| a.is_nan() || *a < 0.) {
+            return Err(SimulationError::NegativePropensity(
+                changes[i].0.to_string(),
+            ));
+        }
+        let total: f64 = rates.iter().sum();
+        let leap = match options.tau_leaping {
+            Some(epsilon) if direct_steps == 0 => {
+                let tau = leap_size(epsilon, &y, &rates, &changes);
+                if tau * total < 10. {
+                    direct_steps = DIRECT_STEPS;
+                    None
+                } else {
+                    Some(tau.min(t_end - t))
+                }
+            }
+            _ => None,
+        };
+        match leap {
+            Some(mut tau) => {
+                let y_new = loop {
+                    let mut y_new = y.clone();
+                    for ((_, change), rate) in changes.iter().zip(&rates) {
+                        apply(&mut y_new, change, poisson(rate * tau, rng));
+                    }
+                    if y_new.iter().all(|x| *x >= 0.) {
+                        break y_new;
+                    }
+                    tau /= 2.;
+                };
+                record(&mut states, &time, t + tau, &y);
+                t += tau;
+                y = y_new;
+                if t >= t_end {
+                    break;
+                }
+            }
+            None => {
+                let t_next = if total > 0. {
+                    t + exponential(total, rng)
+                } else {
+                    f64::INFINITY
+                };
+                record(&mut states, &time, t_next, &y);
+                if t_next > t_end {
+                    break;
+                }
+                let reaction = choose(&rates, total * rng.next_f64());
+                apply(&mut y, changes[reaction].1, 1.);
+                t = t_next;
+                direct_steps = direct_steps.saturating_sub(1);
+            }
+        }
+    }
+    record(&mut states, &time, f64::INFINITY, &y);
+    let values = system.time_course(&time, &states)?;
+    Ok(TimeCourse { time, values })
+}
+
+/// Keep `y` as the state of the output times before `until`.
+fn record(states: &mut Vec<Vec<f64>>, times: &[f64], until: f64, y: &[f64]) {
+    while states.len() < times.len() && times[states.len()] < until {
+        states.push(y.to_vec());
+    }
+}
+
+fn apply(y: &mut [f64], change: &[(usize, f64)], occurrences: f64) {
+    for &(variable, coefficient) in change {
+        y[variable] += coefficient * occurrences;
+    }
+}
+
+/// Reaction in which the cumulative sum of the `rates` reaches `target`.
+fn choose(rates: &[f64], target: f64) -> usize {
+    let mut sum = 0.;
+    for (i, rate) in rates.iter().enumerate() {
+        sum += rate;
+        if target < sum {
+            return i;
+        }
+    }
+    // rounding errors in the sum
+    rates.iter().rposition(|a| *a > 0.).unwrap_or(0)
+}
+
+/// Largest leap whose expected change, and its standard deviation, of each
+/// species amount is below `epsilon` times the amount (or one unit).
+fn leap_size(epsilon: f64, y: &[f64], rates: &[f64], changes: &[(&str, &[(usize, f64)])]) -> f64 {
+    let mut mean = vec![0.; y.len()];
+    let mut variance = vec![0.; y.len()];
+    for ((_, change), rate) in changes.iter().zip(rates) {
+        for &(variable, coefficient) in change.iter() {
+            mean[variable] += coefficient * rate;
+            variance[variable] += coefficient * coefficient * rate;
+        }
+    }
+    y.iter()
+        .zip(mean.iter().zip(&variance))
+        .map(|(y, (mean, variance))| {
+            let bound = (epsilon * y).max(1.);
+            (bound / mean.abs()).min(bound * bound / variance)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
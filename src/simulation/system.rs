@@ -0,0 +1,586 @@
+use std::collections::HashMap;
+
+use super::SimulationError;
+use crate::mathml::{recursive_functions, EvalError, MathNode, TIME_URL};
+use crate::{Model, Rule, Species};
+
+/// How the value of a symbol is computed from the state.
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    /// Amount of a species, divided by the size of the compartment in this
+    /// slot if the species is in concentration.
+    Species {
+        amount: Amount,
+        compartment: Option<usize>,
+    },
+    /// Initial concentration of a species with only substance units, times
+    /// the size of the compartment in this slot.
+    Amount {
+        concentration: f64,
+        compartment: usize,
+    },
+    /// Assignment rule, initial assignment or rate of a reaction.
+    Expression(MathNode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Amount {
+    State(usize),
+    Fixed(f64),
+}
+
+/// Changes of the state variables per unit of the rate of a reaction.
+#[derive(Debug, Clone, PartialEq)]
+struct Stoichiometry {
+    /// Slot of the reaction, whose value is its rate.
+    slot: usize,
+    coefficients: Vec<(usize, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RateRule {
+    variable: usize,
+    rate: MathNode,
+    /// Slot of the compartment of a species in concentration, whose
+    /// amount changes with the rate times its size.
+    compartment: Option<usize>,
+}
+
+/// Ordinary differential equations $\frac{dy}{dt} = f(t, y)$ of a kinetic
+/// [`Model`].
+///
+/// Every symbol of the model (compartments, species, parameters and
+/// reactions, whose value is their rate) has a slot with its value, which is
+/// either constant, a state variable or derived from them in dependency
+/// order. Function definitions and local parameters are inlined in the
+/// expressions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OdeSystem {
+    /// Identifiers of the state variables: the species whose amount
+    /// changes and the parameters and compartments with a rate rule.
+    pub variables: Vec<String>,
+    /// Value of the state variables at time 0.
+    pub initial_state: Vec<f64>,
+    symbols: Vec<String>,
+    slots: HashMap<String, usize>,
+    constants: Vec<f64>,
+    /// Slot of each state variable.
+    states: Vec<usize>,
+    derived: Vec<(usize, Source)>,
+    reactions: Vec<Stoichiometry>,
+    rate_rules: Vec<RateRule>,
+    /// Slots reported in the time courses.
+    outputs: Vec<usize>,
+}
+
+impl OdeSystem {
+    /// Build the system of `model`, computing the initial state from the
+    /// attributes, initial assignments and assignment rules.
+    pub fn new(model: &Model) -> Result<Self, SimulationError> {
+        if !model.events.is_empty() {
+            return Err(SimulationError::Unsupported("events".to_string()));
+        }
+        let functions = &model.function_definitions;
+        // inlining a recursive function would never end
+        if let Some(id) = recursive_functions(functions).into_iter().next() {
+            return Err(SimulationError::RecursiveFunction(id));
+        }
+        let mut assignment_rules = HashMap::new();
+        let mut rate_rules = HashMap::new();
+        for rule in &model.rules {
+            match rule {
+                Rule::AlgebraicRule { .. } => {
+                    return Err(SimulationError::Unsupported("algebraic rules".to_string()))
+                }
+                Rule::AssignmentRule { math, variable } => {
+                    assignment_rules.insert(
+                        variable.as_str(),
+                        math.content.simplify_with_functions(functions),
+                    );
+                }
+                Rule::RateRule { math, variable } => {
+                    rate_rules.insert(
+                        variable.as_str(),
+                        math.content.simplify_with_functions(functions),
+                    );
+                }
+            }
+        }
+
+        let mut symbols: Vec<String> = model
+            .compartments
+            .keys()
+            .chain(model.species.keys())
+            .chain(model.parameters.keys())
+            .chain(model.reactions.keys())
+            .cloned()
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        let slots: HashMap<String, usize> = symbols
+            .iter()
+            .enumerate()
+            .map(|(slot, id)| (id.clone(), slot))
+            .collect();
+        let compartment_of = |species: &Species| {
+            slots
+                .get(&species.compartment)
+                .copied()
+                .ok_or_else(|| EvalError::UnknownSymbol(species.compartment.clone()))
+        };
+        let in_concentration = |species: &Species| {
+            !species.has_only_substance_units
+                && model
+                    .compartments
+                    .get(&species.compartment)
+                    .map(|c| c.spatial_dimensions != Some(0.))
+                    .unwrap_or(true)
+        };
+        let mut rates = HashMap::new();
+        for reaction in model.reactions.values() {
+            let law = reaction
+                .kinetic_law
+                .as_ref()
+                .ok_or_else(|| SimulationError::MissingKineticLaw(reaction.id.clone()))?;
+            rates.insert(reaction.id.as_str(), law.simplified_math(functions));
+        }
+
+        // initial values
+        let mut initial = HashMap::new();
+        for assignment in model.initial_assignments.values() {
+            if let Some(math) = &assignment.math {
+                initial.insert(
+                    slot(&slots, &assignment.symbol)?,
+                    Source::Expression(math.content.simplify_with_functions(functions)),
+                );
+            }
+        }
+        for (variable, expression) in &assignment_rules {
+            initial.insert(
+                slot(&slots, variable)?,
+                Source::Expression(expression.clone()),
+            );
+        }
+        for (reaction, rate) in &rates {
+            initial.insert(slots[*reaction], Source::Expression(rate.clone()));
+        }
+        let mut constants = vec![f64::NAN; symbols.len()];
+        for (slot, id) in symbols.iter().enumerate() {
+            if initial.contains_key(&slot) {
+                continue;
+            }
+            if let Some(compartment) = model.compartments.get(id) {
+                constants[slot] = compartment
+                    .size
+                    .ok_or_else(|| SimulationError::MissingValue(id.clone()))?;
+            } else if let Some(parameter) = model.parameters.get(id) {
+                constants[slot] = parameter
+                    .value
+                    .ok_or_else(|| SimulationError::MissingValue(id.clone()))?;
+            } else if let Some(species) = model.species.get(id) {
+                let concentration = in_concentration(species);
+                match (species.initial_amount, species.initial_concentration) {
+                    (Some(amount), _) if !concentration => constants[slot] = amount,
+                    (_, Some(value)) if concentration => constants[slot] = value,
+                    (Some(amount), None) => {
+                        initial.insert(
+                            slot,
+                            Source::Species {
+                                amount: Amount::Fixed(amount),
+                                compartment: Some(compartment_of(species)?),
+                            },
+                        );
+                    }
+                    (_, Some(concentration)) => {
+                        initial.insert(
+                            slot,
+                            Source::Amount {
+                                concentration,
+                                compartment: compartment_of(species)?,
+                            },
+                        );
+                    }
+                    (None, None) => return Err(SimulationError::MissingValue(id.clone())),
+                }
+            }
+        }
+        let mut values = constants.clone();
+        for (slot, source) in sort(initial, &symbols, &slots)? {
+            values[slot] = resolve(&source, 0., &[], &values, &slots)?;
+        }
+
+        // state variables and the symbols derived from them
+        let constants = values;
+        let mut variables = Vec::new();
+        let mut initial_state = Vec::new();
+        let mut states = Vec::new();
+        let mut derived = HashMap::new();
+        let mut outputs = Vec::new();
+        let mut rules = Vec::new();
+        for (slot, id) in symbols.iter().enumerate() {
+            if let Some(expression) = assignment_rules.get(id.as_str()) {
+                derived.insert(slot, Source::Expression(expression.clone()));
+            } else if let Some(rate) = rates.get(id.as_str()) {
+                derived.insert(slot, Source::Expression(rate.clone()));
+                continue;
+            } else if let Some(species) = model.species.get(id) {
+                let compartment = if in_concentration(species) {
+                    Some(compartment_of(species)?)
+                } else {
+                    None
+                };
+                let amount = constants[slot] * compartment.map(|c| constants[c]).unwrap_or(1.);
+                let changes = rate_rules.contains_key(id.as_str()) || !species.boundary_condition;
+                let amount = if species.constant {
+                    None
+                } else if changes {
+                    variables.push(id.clone());
+                    initial_state.push(amount);
+                    states.push(slot);
+                    Some(Amount::State(states.len() - 1))
+                } else {
+                    Some(Amount::Fixed(amount))
+                };
+                if let Some(amount) = amount {
+                    derived.insert(
+                        slot,
+                        Source::Species {
+                            amount,
+                            compartment,
+                        },
+                    );
+                }
+                if let (Some(Amount::State(variable)), Some(rate)) =
+                    (amount, rate_rules.get(id.as_str()))
+                {
+                    rules.push(RateRule {
+                        variable,
+                        rate: rate.clone(),
+                        compartment,
+                    });
+                }
+                outputs.push(slot);
+                continue;
+            } else if let Some(rate) = rate_rules.get(id.as_str()) {
+                variables.push(id.clone());
+                initial_state.push(constants[slot]);
+                states.push(slot);
+                rules.push(RateRule {
+                    variable: states.len() - 1,
+                    rate: rate.clone(),
+                    compartment: None,
+                });
+            } else {
+                continue;
+            }
+            outputs.push(slot);
+        }
+        let derived = sort(derived, &symbols, &slots)?;
+
+        let mut reactions = Vec::new();
+        let mut ids: Vec<&String> = model.reactions.keys().collect();
+        ids.sort();
+        for id in ids {
+            let reaction = &model.reactions[id];
+            let mut coefficients: Vec<(usize, f64)> = Vec::new();
+            let references = reaction
+                .list_of_reactants
+                .species_references
+                .iter()
+                .map(|r| (r, -1.))
+                .chain(
+                    reaction
+                        .list_of_products
+                        .species_references
+                        .iter()
+                        .map(|r| (r, 1.)),
+                );
+            for (reference, sign) in references {
+                let species = model
+                    .species
+                    .get(&reference.species)
+                    .ok_or_else(|| EvalError::UnknownSymbol(reference.species.clone()))?;
+                if species.boundary_condition || species.constant {
+                    continue;
+                }
+                let variable = match states.iter().position(|&s| s == slots[&species.id]) {
+                    Some(variable) => variable,
+                    None => continue,
+                };
+                let factor = match species.conversion_factor.clone().or_else(|| {
+                    model
+                        .model_units
+                        .conversion_factor
+                        .as_ref()
+                        .map(|c| c.to_string())
+                }) {
+                    Some(factor) => constants[slot(&slots, &factor)?],
+                    None => 1.,
+                };
+                let coefficient = sign * reference.stoichiometry.unwrap_or(1.) * factor;
+                match coefficients.iter_mut().find(|(v, _)| *v == variable) {
+                    Some((_, c)) => *c += coefficient,
+                    None => coefficients.push((variable, coefficient)),
+                }
+            }
+            reactions.push(Stoichiometry {
+                slot: slots[id],
+                coefficients,
+            });
+        }
+
+        Ok(Self {
+            variables,
+            initial_state,
+            symbols,
+            slots,
+            constants,
+            states,
+            derived,
+            reactions,
+            rate_rules: rules,
+            outputs,
+        })
+    }
+
+    /// Right-hand side $f(t, y)$: the rate of change of each state variable.
+    pub fn rhs(&self, t: f64, y: &[f64]) -> Result<Vec<f64>, SimulationError> {
+        let values = self.values(t, y)?;
+        let mut dydt = vec![0.; y.len()];
+        for reaction in &self.reactions {
+            let rate = values[reaction.slot];
+            for &(variable, coefficient) in &reaction.coefficients {
+                dydt[variable] += coefficient * rate;
+            }
+        }
+        for rule in &self.rate_rules {
+            let rate = evaluate(&rule.rate, t, &values, &self.slots)?;
+            dydt[rule.variable] += rate * rule.compartment.map(|c| values[c]).unwrap_or(1.);
+        }
+        Ok(dydt)
+    }
+
+    /// Jacobian $\frac{\partial f_i}{\partial y_j}$ of the right-hand side,
+    /// by forward differences.
+    pub fn jacobian(&self, t: f64, y: &[f64]) -> Result<Vec<Vec<f64>>, SimulationError> {
+        forward_differences(|y| self.rhs(t, y), y)
+    }
+
+    /// Rate of each reaction at time `t` and state `y`, in the order of
+    /// [`OdeSystem::reaction_changes`].
+    pub(super) fn reaction_rates(&self, t: f64, y: &[f64]) -> Result<Vec<f64>, SimulationError> {
+        let values = self.values(t, y)?;
+        Ok(self.reactions.iter().map(|r| values[r.slot]).collect())
+    }
+
+    /// Identifier of each reaction and the `(state variable, coefficient)`
+    /// changes of one occurrence of it.
+    pub(super) fn reaction_changes(&self) -> Vec<(&str, &[(usize, f64)])> {
+        self.reactions
+            .iter()
+            .map(|r| (self.symbols[r.slot].as_str(), r.coefficients.as_slice()))
+            .collect()
+    }
+
+    pub(super) fn has_rate_rules(&self) -> bool {
+        !self.rate_rules.is_empty()
+    }
+
+    /// State variables with a rate rule.
+    pub(super) fn rate_rule_variables(&self) -> Vec<usize> {
+        self.rate_rules.iter().map(|rule| rule.variable).collect()
+    }
+
+    /// Values of the symbols of the model, by identifier, at time `t` and
+    /// state `y`.
+    pub fn symbol_values(
+        &self,
+        t: f64,
+        y: &[f64],
+    ) -> Result<HashMap<String, f64>, SimulationError> {
+        let values = self.values(t, y)?;
+        Ok(self.symbols.iter().cloned().zip(values).collect())
+    }
+
+    /// Values of the output symbols at time `t` and state `y`.
+    pub(super) fn observe(
+        &self,
+        t: f64,
+        y: &[f64],
+    ) -> Result<HashMap<String, f64>, SimulationError> {
+        let values = self.values(t, y)?;
+        Ok(self
+            .outputs
+            .iter()
+            .map(|&slot| (self.symbols[slot].clone(), values[slot]))
+            .collect())
+    }
+
+    /// Values of the output symbols at each time of a trajectory.
+    pub(super) fn time_course(
+        &self,
+        times: &[f64],
+        states: &[Vec<f64>],
+    ) -> Result<HashMap<String, Vec<f64>>, SimulationError> {
+        let mut courses: Vec<Vec<f64>> = vec![Vec::with_capacity(times.len()); self.outputs.len()];
+        for (&t, y) in times.iter().zip(states) {
+            let values = self.values(t, y)?;
+            for (course, &slot) in courses.iter_mut().zip(&self.outputs) {
+                course.push(values[slot]);
+            }
+        }
+        Ok(self
+            .outputs
+            .iter()
+            .map(|&slot| self.symbols[slot].clone())
+            .zip(courses)
+            .collect())
+    }
+
+    fn values(&self, t: f64, y: &[f64]) -> Result<Vec<f64>, SimulationError> {
+        let mut values = self.constants.clone();
+        for (&slot, &value) in self.states.iter().zip(y) {
+            values[slot] = value;
+        }
+        for (slot, source) in &self.derived {
+            values[*slot] = resolve(source, t, y, &values, &self.slots)?;
+        }
+        Ok(values)
+    }
+}
+
+/// Jacobian of `f` at `y` by forward differences.
+pub(super) fn forward_differences<F>(f: F, y: &[f64]) -> Result<Vec<Vec<f64>>, SimulationError>
+where
+    F: Fn(&[f64]) -> Result<Vec<f64>, SimulationError>,
+{
+    let f0 = f(y)?;
+    let mut jacobian = vec![vec![0.; y.len()]; f0.len()];
+    let mut y = y.to_vec();
+    for j in 0..y.len() {
+        let yj = y[j];
+        let h = f64::EPSILON.sqrt() * yj.abs().max(1e-6);
+        y[j] = yj + h;
+        let f1 = f(&y)?;
+        y[j] = yj;
+        for (row, (a, b)) in jacobian.iter_mut().zip(f1.iter().zip(&f0)) {
+            row[j] = (a - b) / h;
+        }
+    }
+    Ok(jacobian)
+}
+
+fn slot(slots: &HashMap<String, usize>, id: &str) -> Result<usize, EvalError> {
+    slots
+        .get(id)
+        .copied()
+        .ok_or_else(|| EvalError::UnknownSymbol(id.to_string()))
+}
+
+fn evaluate(
+    expression: &MathNode,
+    t: f64,
+    values: &[f64],
+    slots: &HashMap<String, usize>,
+) -> Result<f64, EvalError> {
+    expression.evaluate(&|id: &str| {
+        if id == TIME_URL {
+            Some(t)
+        } else {
+            slots.get(id).map(|&slot| values[slot])
+        }
+    })
+}
+
+fn resolve(
+    source: &Source,
+    t: f64,
+    y: &[f64],
+    values: &[f64],
+    slots: &HashMap<String, usize>,
+) -> Result<f64, EvalError> {
+    Ok(match source {
+        Source::Species {
+            amount,
+            compartment,
+        } => {
+            let amount = match amount {
+                Amount::State(i) => y[*i],
+                Amount::Fixed(amount) => *amount,
+            };
+            match compartment {
+                Some(c) => amount / values[*c],
+                None => amount,
+            }
+        }
+        Source::Amount {
+            concentration,
+            compartment,
+        } => concentration * values[*compartment],
+        Source::Expression(expression) => evaluate(expression, t, values, slots)?,
+    })
+}
+
+/// `sources` ordered so that each one comes after those of its
+/// dependencies.
+fn sort(
+    mut sources: HashMap<usize, Source>,
+    symbols: &[String],
+    slots: &HashMap<String, usize>,
+) -> Result<Vec<(usize, Source)>, SimulationError> {
+    let dependencies: HashMap<usize, Vec<usize>> = sources
+        .iter()
+        .map(|(&slot, source)| {
+            let dependencies = match source {
+                Source::Species {
+                    compartment: Some(c),
+                    ..
+                }
+                | Source::Amount { compartment: c, .. } => vec![*c],
+                Source::Species { .. } => Vec::new(),
+                Source::Expression(expression) => expression
+                    .identifiers()
+                    .iter()
+                    .filter_map(|id| slots.get(id).copied())
+                    .collect(),
+            };
+            (slot, dependencies)
+        })
+        .collect();
+    let mut keys: Vec<usize> = sources.keys().copied().collect();
+    keys.sort_unstable();
+    let mut visited = HashMap::new();
+    let mut sorted = Vec::new();
+    for slot in keys {
+        visit(slot, &dependencies, &mut visited, &mut sorted, symbols)?;
+    }
+    Ok(sorted
+        .into_iter()
+        .map(|slot| (slot, sources.remove(&slot).expect("sorted source")))
+        .collect())
+}
+
+/// Depth-first topological sort; `visited` is false while the dependencies
+/// of a slot are being visited.
+fn visit(
+    slot: usize,
+    dependencies: &HashMap<usize, Vec<usize>>,
+    visited: &mut HashMap<usize, bool>,
+    sorted: &mut Vec<usize>,
+    symbols: &[String],
+) -> Result<(), SimulationError> {
+    match visited.get(&slot) {
+        Some(true) => return Ok(()),
+        Some(false) => return Err(SimulationError::AlgebraicLoop(symbols[slot].clone())),
+        None => {}
+    }
+    visited.insert(slot, false);
+    for &dependency in &dependencies[&slot] {
+        if dependencies.contains_key(&dependency) {
+            visit(dependency, dependencies, visited, sorted, symbols)?;
+        }
+    }
+    visited.insert(slot, true);
+    sorted.push(slot);
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Model;
+
+/// Sparse stoichiometric matrix $S$ in compressed sparse column (CSC) format,
+/// with one row per species and one column per reaction.
+///
+/// Rows and columns are sorted by identifier, so the indices are stable
+/// across runs. Reactants contribute negative coefficients and products
+/// positive ones; a missing `stoichiometry` counts as 1.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::Model;
+///
+/// let model = Model::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model>
+///     <listOfSpecies>
+///         <species id="A" compartment="c" hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+///         <species id="B" compartment="c" hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+///     </listOfSpecies>
+///     <listOfReactions>
+///         <reaction id="R1" reversible="false">
+///             <listOfReactants><speciesReference species="A" stoichiometry="2" constant="true"/></listOfReactants>
+///             <listOfProducts><speciesReference species="B" constant="true"/></listOfProducts>
+///         </reaction>
+///     </listOfReactions>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let s = model.stoichiometric_matrix(false);
+/// assert_eq!(s.species, vec!["A", "B"]);
+/// assert_eq!(s.to_dense(), vec![vec![-2.], vec![1.]]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StoichiometricMatrix {
+    /// Species identifiers, by row index.
+    pub species: Vec<String>,
+    /// Reaction identifiers, by column index.
+    pub reactions: Vec<String>,
+    /// For each column, the range `col_ptr[j]..col_ptr[j + 1]` of its
+    /// entries in `row_indices` and `values`.
+    pub col_ptr: Vec<usize>,
+    pub row_indices: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl StoichiometricMatrix {
+    /// `(rows, columns)` of the matrix.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.species.len(), self.reactions.len())
+    }
+
+    /// Non-zero entries as `(row, column, value)` triplets, column by column.
+    pub fn triplets(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        (0..self.reactions.len()).flat_map(move |j| {
+            (self.col_ptr[j]..self.col_ptr[j + 1])
+                .map(move |k| (self.row_indices[k], j, self.values[k]))
+        })
+    }
+
+    /// Non-zero entries of a column, as `(row, value)`.
+    pub fn column(&self, j: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        (self.col_ptr[j]..self.col_ptr[j + 1]).map(move |k| (self.row_indices[k], self.values[k]))
+    }
+
+    /// Coefficient of species `row` in reaction `column`.
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.column(column)
+            .find(|(i, _)| *i == row)
+            .map(|(_, v)| v)
+            .unwrap_or(0.)
+    }
+
+    pub fn to_dense(&self) -> Vec<Vec<f64>> {
+        let (rows, columns) = self.shape();
+        let mut dense = vec![vec![0.; columns]; rows];
+        for (i, j, v) in self.triplets() {
+            dense[i][j] = v;
+        }
+        dense
+    }
+}
+
+impl Model {
+    /// Build the [`StoichiometricMatrix`] of the model. With
+    /// `exclude_boundary`, species with `boundaryCondition="true"` are left
+    /// out, as they are not balanced by the reactions.
+    ///
+    /// Species referenced by a reaction but not declared in the model are
+    /// kept as rows.
+    pub fn stoichiometric_matrix(&self, exclude_boundary: bool) -> StoichiometricMatrix {
+        let is_excluded = |id: &str| {
+            exclude_boundary
+                && self
+                    .species
+                    .get(id)
+                    .map(|sp| sp.boundary_condition)
+                    .unwrap_or(false)
+        };
+        let reactions: Vec<String> = self
+            .reactions
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+        let species: Vec<String> = self
+            .species
+            .keys()
+            .map(|id| id.as_str())
+            .chain(self.reactions.values().flat_map(|reac| {
+                reac.list_of_reactants
+                    .species_references
+                    .iter()
+                    .chain(reac.list_of_products.species_references.iter())
+                    .map(|sref| sref.species.as_str())
+            }))
+            .filter(|id| !is_excluded(id))
+            .collect::<BTreeSet<&str>>()
+            .into_iter()
+            .map(|id| id.to_owned())
+            .collect();
+        let row_of: BTreeMap<&str, usize> = species
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.as_str(), i))
+            .collect();
+
+        let mut col_ptr = vec![0];
+        let mut row_indices = Vec::new();
+        let mut values = Vec::new();
+        for reac_id in reactions.iter() {
+            let reaction = &self.reactions[reac_id];
+            let mut column: BTreeMap<usize, f64> = BTreeMap::new();
+            let reactants = reaction.list_of_reactants.species_references.iter();
+            let products = reaction.list_of_products.species_references.iter();
+            for (sign, sref) in reactants.map(|r| (-1., r)).chain(products.map(|r| (1., r))) {
+                if let Some(&i) = row_of.get(sref.species.as_str()) {
+                    *column.entry(i).or_insert(0.) += sign * sref.stoichiometry.unwrap_or(1.);
+                }
+            }
+            for (i, v) in column.into_iter().filter(|(_, v)| *v != 0.) {
+                row_indices.push(i);
+                values.push(v);
+            }
+            col_ptr.push(row_indices.len());
+        }
+        StoichiometricMatrix {
+            species,
+            reactions,
+            col_ptr,
+            row_indices,
+            values,
+        }
+    }
+}
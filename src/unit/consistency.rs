@@ -0,0 +1,604 @@
+//! Consistency of the units of the math of a model, after the unit rules of
+//! SBML Level 3.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{SiUnit, UnitError, UnitSId, UnitSIdRef};
+use crate::mathml::{operator_name, MathNode, AVOGADRO_URL, TIME_URL};
+use crate::{Model, Rule};
+
+/// Element of a model whose math is checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MathElement {
+    /// The kinetic law of the reaction with this id.
+    KineticLaw(String),
+    /// The initial assignment of this symbol.
+    InitialAssignment(String),
+    /// The assignment rule of this variable.
+    AssignmentRule(String),
+    /// The rate rule of this variable.
+    RateRule(String),
+    /// The algebraic rule at this position of [`Model::rules`].
+    AlgebraicRule(usize),
+}
+
+impl fmt::Display for MathElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MathElement::KineticLaw(id) => write!(f, "kinetic law of `{}`", id),
+            MathElement::InitialAssignment(id) => write!(f, "initial assignment of `{}`", id),
+            MathElement::AssignmentRule(id) => write!(f, "assignment rule of `{}`", id),
+            MathElement::RateRule(id) => write!(f, "rate rule of `{}`", id),
+            MathElement::AlgebraicRule(i) => write!(f, "algebraic rule {}", i),
+        }
+    }
+}
+
+/// Problem with the units of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnitProblem {
+    /// Operands of this operator, which must have the same units, that do
+    /// not.
+    Mismatch {
+        operator: String,
+        left: SiUnit,
+        right: SiUnit,
+    },
+    /// An argument of this operator that is not dimensionless.
+    NotDimensionless { operator: String, found: SiUnit },
+    /// The expression does not have the units of the element it defines.
+    Inconsistent { expected: SiUnit, found: SiUnit },
+    /// The units of the expression cannot be determined, because it has
+    /// numbers or symbols without declared units.
+    Undetermined,
+    /// A reference to a unit that is not defined, or whose definition is
+    /// not made of base units.
+    UnknownUnit(String),
+    /// A call to this function definition from its own body, directly or
+    /// through other function definitions.
+    RecursiveFunction(String),
+}
+
+impl fmt::Display for UnitProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnitProblem::Mismatch {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "the operands of `{}` have different units, `{}` and `{}`",
+                operator, left, right
+            ),
+            UnitProblem::NotDimensionless { operator, found } => write!(
+                f,
+                "the argument of `{}` has units `{}` instead of being dimensionless",
+                operator, found
+            ),
+            UnitProblem::Inconsistent { expected, found } => {
+                write!(f, "expected units `{}`, found `{}`", expected, found)
+            }
+            UnitProblem::Undetermined => write!(f, "the units cannot be determined"),
+            UnitProblem::UnknownUnit(id) => write!(f, "unknown unit `{}`", id),
+            UnitProblem::RecursiveFunction(id) => {
+                write!(f, "the function definition `{}` calls itself", id)
+            }
+        }
+    }
+}
+
+/// A [`UnitProblem`] in the math of an element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitIssue {
+    pub element: MathElement,
+    pub problem: UnitProblem,
+}
+
+impl fmt::Display for UnitIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.element, self.problem)
+    }
+}
+
+/// Infer the units of the kinetic laws, rules and initial assignments of
+/// `model` and report where they are not consistent.
+///
+/// The units of the symbols come from their `units` attributes, those of
+/// the species being their substance units divided by the units of their
+/// compartment unless they have only substance units, and from the units
+/// of the model when not given. Numbers carry the units of their
+/// `sbml:units` attribute. The operands of sums, differences, comparisons
+/// and pieces must have the same units, the arguments of exponentials,
+/// logarithms and trigonometric functions must be dimensionless, and the
+/// expressions must have the units of the element they define: extent per
+/// time for the kinetic laws, the units of the variable (per time for the
+/// rate rules) otherwise. Numbers and symbols without units only take the
+/// units of the expressions they are compared to, so an expression that
+/// depends on them for its units is reported as
+/// [`UnitProblem::Undetermined`]. The unknown units found while resolving
+/// the units of the defined element are reported for the element too.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::unit::{check_consistency, MathElement, UnitProblem};
+/// use rust_sbml::Model;
+///
+/// let model = Model::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model substanceUnits="mole" timeUnits="second" extentUnits="mole">
+///       <listOfCompartments>
+///         <compartment id="c" size="1" units="litre" constant="true"/>
+///       </listOfCompartments>
+///       <listOfSpecies>
+///         <species id="S" compartment="c" initialAmount="1"
+///           hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+///       </listOfSpecies>
+///       <listOfParameters>
+///         <parameter id="V" value="1" units="litre" constant="true"/>
+///       </listOfParameters>
+///       <listOfRules>
+///         <assignmentRule variable="V">
+///           <math xmlns="http://www.w3.org/1998/Math/MathML">
+///             <apply><plus/><ci> c </ci><ci> S </ci></apply>
+///           </math>
+///         </assignmentRule>
+///       </listOfRules>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let issues = check_consistency(&model);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].element, MathElement::AssignmentRule("V".to_string()));
+/// assert!(matches!(issues[0].problem, UnitProblem::Mismatch { .. }));
+/// ```
+pub fn check_consistency(model: &Model) -> Vec<UnitIssue> {
+    let checker = Checker {
+        model,
+        expanding: RefCell::new(Vec::new()),
+    };
+    let mut issues = Vec::new();
+    // `problems` holds those found while resolving the `expected` units
+    let mut check = |element: MathElement,
+                     math: &MathNode,
+                     scope: &Scope,
+                     expected: Option<SiUnit>,
+                     mut problems: Vec<UnitProblem>| {
+        let found = checker.infer(math, scope, &mut problems);
+        match (found, expected) {
+            (None, _) => problems.push(UnitProblem::Undetermined),
+            (Some(found), Some(expected)) if !found.is_equivalent(&expected) => {
+                problems.push(UnitProblem::Inconsistent { expected, found })
+            }
+            _ => {}
+        }
+        let mut seen = Vec::new();
+        for problem in problems {
+            if !seen.contains(&problem) {
+                seen.push(problem.clone());
+                issues.push(UnitIssue {
+                    element: element.clone(),
+                    problem,
+                });
+            }
+        }
+    };
+    let mut reaction_problems = Vec::new();
+    let reaction_units = checker.reaction_units(&mut reaction_problems);
+
+    let mut reactions: Vec<_> = model.reactions.iter().collect();
+    reactions.sort_by_key(|(id, _)| *id);
+    for (id, reaction) in reactions {
+        let law = match &reaction.kinetic_law {
+            Some(law) => law,
+            None => continue,
+        };
+        let mut problems = reaction_problems.clone();
+        let scope: Scope = law
+            .list_of_local_parameters
+            .local_parameter
+            .iter()
+            .map(|parameter| {
+                let units = parameter
+                    .units
+                    .as_ref()
+                    .and_then(|units| checker.resolve(units, &mut problems));
+                (parameter.id.clone(), units)
+            })
+            .collect();
+        check(
+            MathElement::KineticLaw(id.clone()),
+            &law.math.content,
+            &scope,
+            reaction_units.clone(),
+            problems,
+        );
+    }
+
+    let mut assignments: Vec<_> = model.initial_assignments.values().collect();
+    assignments.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    for assignment in assignments {
+        if let Some(math) = &assignment.math {
+            let mut problems = Vec::new();
+            let units = checker.symbol_units(&assignment.symbol, &mut problems);
+            check(
+                MathElement::InitialAssignment(assignment.symbol.clone()),
+                &math.content,
+                &Scope::new(),
+                units,
+                problems,
+            );
+        }
+    }
+
+    for (i, rule) in model.rules.iter().enumerate() {
+        let mut problems = Vec::new();
+        match rule {
+            Rule::AssignmentRule { math, variable } => {
+                let units = checker.symbol_units(variable, &mut problems);
+                check(
+                    MathElement::AssignmentRule(variable.clone()),
+                    &math.content,
+                    &Scope::new(),
+                    units,
+                    problems,
+                )
+            }
+            Rule::RateRule { math, variable } => {
+                let units = checker
+                    .symbol_units(variable, &mut problems)
+                    .zip(checker.time_units(&mut problems))
+                    .map(|(units, time)| units.times(&time, -1.));
+                check(
+                    MathElement::RateRule(variable.clone()),
+                    &math.content,
+                    &Scope::new(),
+                    units,
+                    problems,
+                )
+            }
+            Rule::AlgebraicRule { math } => check(
+                MathElement::AlgebraicRule(i),
+                &math.content,
+                &Scope::new(),
+                None,
+                problems,
+            ),
+        }
+    }
+    issues
+}
+
+/// Units of the local symbols, such as local parameters and the bound
+/// variables of functions, which shadow those of the model.
+type Scope = HashMap<String, Option<SiUnit>>;
+
+struct Checker<'a> {
+    model: &'a Model,
+    /// Function definitions whose body is being inferred.
+    expanding: RefCell<Vec<String>>,
+}
+
+impl<'a> Checker<'a> {
+    fn resolve(&self, units: &UnitSIdRef, problems: &mut Vec<UnitProblem>) -> Option<SiUnit> {
+        let result = match units {
+            UnitSIdRef::SIUnit(kind) => Ok(kind.to_si()),
+            UnitSIdRef::CustomUnit(id) => match self.model.unit_definition(id) {
+                Some(definition) => definition.to_si(),
+                None => Err(UnitError::UnknownUnit(id.clone())),
+            },
+        };
+        match result {
+            Ok(units) => Some(units),
+            Err(_) => {
+                problems.push(UnitProblem::UnknownUnit(units.to_string()));
+                None
+            }
+        }
+    }
+
+    fn model_units(
+        &self,
+        units: &Option<UnitSIdRef>,
+        problems: &mut Vec<UnitProblem>,
+    ) -> Option<SiUnit> {
+        units
+            .as_ref()
+            .and_then(|units| self.resolve(units, problems))
+    }
+
+    fn time_units(&self, problems: &mut Vec<UnitProblem>) -> Option<SiUnit> {
+        self.model_units(&self.model.model_units.time_units, problems)
+    }
+
+    /// Extent per time.
+    fn reaction_units(&self, problems: &mut Vec<UnitProblem>) -> Option<SiUnit> {
+        let extent = self.model_units(&self.model.model_units.extent_units, problems)?;
+        Some(extent.times(&self.time_units(problems)?, -1.))
+    }
+
+    fn compartment_units(&self, id: &str, problems: &mut Vec<UnitProblem>) -> Option<SiUnit> {
+        let compartment = self.model.compartments.get(id)?;
+        if let Some(units) = &compartment.units {
+            return self.resolve(units, problems);
+        }
+        let units = &self.model.model_units;
+        match compartment.spatial_dimensions.map(|d| d as i32) {
+            Some(3) => self.model_units(&units.volume_units, problems),
+            Some(2) => self.model_units(&units.area_units, problems),
+            Some(1) => self.model_units(&units.length_units, problems),
+            _ => None,
+        }
+    }
+
+    /// Declared units of a symbol of the model.
+    fn symbol_units(&self, id: &str, problems: &mut Vec<UnitProblem>) -> Option<SiUnit> {
+        let model = self.model;
+        if let Some(species) = model.species.get(id) {
+            let substance = match &species.substance_units {
+                Some(units) => self.resolve(units, problems),
+                None => self.model_units(&model.model_units.substance_units, problems),
+            }?;
+            let dimensionless = model
+                .compartments
+                .get(&species.compartment)
+                .map(|c| c.spatial_dimensions == Some(0.))
+                .unwrap_or(false);
+            if species.has_only_substance_units || dimensionless {
+                Some(substance)
+            } else {
+                let size = self.compartment_units(&species.compartment, problems)?;
+                Some(substance.times(&size, -1.))
+            }
+        } else if model.compartments.contains_key(id) {
+            self.compartment_units(id, problems)
+        } else if let Some(parameter) = model.parameters.get(id) {
+            self.model_units(&parameter.units, problems)
+        } else if model.reactions.contains_key(id) {
+            self.reaction_units(problems)
+        } else {
+            None
+        }
+    }
+
+    /// Units of `node`, or `None` if they cannot be determined, pushing the
+    /// inconsistencies found on the way to `problems`.
+    fn infer(
+        &self,
+        node: &MathNode,
+        scope: &Scope,
+        problems: &mut Vec<UnitProblem>,
+    ) -> Option<SiUnit> {
+        match node {
+            MathNode::Cn(cn) => cn
+                .unit
+                .as_ref()
+                .and_then(|units| self.resolve(units, problems)),
+            MathNode::Ci { content, .. } => {
+                let id = content.trim();
+                match scope.get(id) {
+                    Some(units) => units.clone(),
+                    None => self.symbol_units(id, problems),
+                }
+            }
+            MathNode::Csymbol {
+                definition_url: Some(url),
+                ..
+            } if url == TIME_URL => self.time_units(problems),
+            MathNode::Csymbol {
+                definition_url: Some(url),
+                ..
+            } if url == AVOGADRO_URL => Some(SiUnit::default().times(&UnitSId::mole.to_si(), -1.)),
+            MathNode::True | MathNode::False | MathNode::Pi | MathNode::Exponentiale => {
+                Some(SiUnit::default())
+            }
+            MathNode::Semantics { children, .. } => children
+                .first()
+                .and_then(|child| self.infer(child, scope, problems)),
+            MathNode::Piecewise { children } => {
+                let mut values = Vec::new();
+                for child in children {
+                    match child {
+                        MathNode::Piece { children } | MathNode::Otherwise { children } => {
+                            if let Some((value, conditions)) = children.split_first() {
+                                values.push(self.infer(value, scope, problems));
+                                for condition in conditions {
+                                    self.infer(condition, scope, problems);
+                                }
+                            }
+                        }
+                        _ => values.push(None),
+                    }
+                }
+                same_units("piecewise", values, problems)
+            }
+            MathNode::Apply(apply) => match apply.content.split_first() {
+                Some((operator, operands)) => self.infer_apply(operator, operands, scope, problems),
+                None => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn infer_apply(
+        &self,
+        operator: &MathNode,
+        operands: &[MathNode],
+        scope: &Scope,
+        problems: &mut Vec<UnitProblem>,
+    ) -> Option<SiUnit> {
+        let args = |problems: &mut Vec<UnitProblem>| -> Vec<Option<SiUnit>> {
+            operands
+                .iter()
+                .filter(|operand| {
+                    !matches!(operand, MathNode::Degree { .. } | MathNode::Logbase { .. })
+                })
+                .map(|operand| self.infer(operand, scope, problems))
+                .collect()
+        };
+        let name = operator_name(operator);
+        match operator {
+            MathNode::Ci { content, .. } => {
+                let id = content.trim();
+                let lambda = &self.model.function_definitions.get(id)?.math.content;
+                if self.expanding.borrow().iter().any(|f| f == id) {
+                    problems.push(UnitProblem::RecursiveFunction(id.to_string()));
+                    return None;
+                }
+                let args = args(problems);
+                self.expanding.borrow_mut().push(id.to_string());
+                let units = self.call(lambda, args, problems);
+                self.expanding.borrow_mut().pop();
+                units
+            }
+            MathNode::Lambda { .. } => self.call(operator, args(problems), problems),
+            MathNode::Plus | MathNode::Minus | MathNode::Max | MathNode::Min | MathNode::Rem => {
+                let args = args(problems);
+                same_units(&name, args, problems)
+            }
+            MathNode::Eq
+            | MathNode::Neq
+            | MathNode::Gt
+            | MathNode::Lt
+            | MathNode::Geq
+            | MathNode::Leq => {
+                let args = args(problems);
+                same_units(&name, args, problems);
+                Some(SiUnit::default())
+            }
+            MathNode::And | MathNode::Or | MathNode::Xor | MathNode::Not | MathNode::Implies => {
+                args(problems);
+                Some(SiUnit::default())
+            }
+            MathNode::Times => args(problems)
+                .into_iter()
+                .try_fold(SiUnit::default(), |product, units| {
+                    Some(product.times(&units?, 1.))
+                }),
+            MathNode::Divide | MathNode::Quotient => match args(problems).as_slice() {
+                [Some(a), Some(b)] => Some(a.clone().times(b, -1.)),
+                _ => None,
+            },
+            MathNode::Abs | MathNode::Floor | MathNode::Ceiling => {
+                args(problems).into_iter().next().flatten()
+            }
+            MathNode::Power | MathNode::Root => {
+                let (base, exponent) = match (operator, operands) {
+                    (MathNode::Power, [base, exponent]) => {
+                        let units = self.infer(exponent, scope, problems);
+                        dimensionless(&name, units, problems);
+                        (base, exponent.evaluate(&|_| None).ok())
+                    }
+                    (MathNode::Root, [MathNode::Degree { children }, base]) => (
+                        base,
+                        children
+                            .first()
+                            .and_then(|degree| degree.evaluate(&|_| None).ok())
+                            .map(|degree| 1. / degree),
+                    ),
+                    (MathNode::Root, [base]) => (base, Some(0.5)),
+                    _ => return None,
+                };
+                let units = self.infer(base, scope, problems)?;
+                match exponent {
+                    Some(exponent) => Some(SiUnit::default().times(&units, exponent)),
+                    None if units.is_dimensionless() => Some(SiUnit::default()),
+                    None => None,
+                }
+            }
+            MathNode::Exp
+            | MathNode::Ln
+            | MathNode::Log
+            | MathNode::Factorial
+            | MathNode::Sin
+            | MathNode::Cos
+            | MathNode::Tan
+            | MathNode::Sec
+            | MathNode::Csc
+            | MathNode::Cot
+            | MathNode::Sinh
+            | MathNode::Cosh
+            | MathNode::Tanh
+            | MathNode::Sech
+            | MathNode::Csch
+            | MathNode::Coth
+            | MathNode::Arcsin
+            | MathNode::Arccos
+            | MathNode::Arctan
+            | MathNode::Arcsec
+            | MathNode::Arccsc
+            | MathNode::Arccot
+            | MathNode::Arcsinh
+            | MathNode::Arccosh
+            | MathNode::Arctanh
+            | MathNode::Arcsech
+            | MathNode::Arccsch
+            | MathNode::Arccoth => {
+                for units in args(problems) {
+                    dimensionless(&name, units, problems);
+                }
+                Some(SiUnit::default())
+            }
+            _ => None,
+        }
+    }
+
+    /// Units of the body of `lambda` with its bound variables in the units
+    /// of the arguments.
+    fn call(
+        &self,
+        lambda: &MathNode,
+        args: Vec<Option<SiUnit>>,
+        problems: &mut Vec<UnitProblem>,
+    ) -> Option<SiUnit> {
+        let children = match lambda {
+            MathNode::Lambda { children } => children,
+            _ => return None,
+        };
+        let (body, bvars) = children.split_last()?;
+        let scope: Scope = bvars
+            .iter()
+            .filter_map(|bvar| match bvar {
+                MathNode::Bvar { children } => children.iter().find_map(|child| match child {
+                    MathNode::Ci { content, .. } => Some(content.trim().to_string()),
+                    _ => None,
+                }),
+                _ => None,
+            })
+            .zip(args)
+            .collect();
+        self.infer(body, &scope, problems)
+    }
+}
+
+/// The units shared by all the operands of `operator` that have them.
+fn same_units(
+    operator: &str,
+    units: Vec<Option<SiUnit>>,
+    problems: &mut Vec<UnitProblem>,
+) -> Option<SiUnit> {
+    let mut known = units.into_iter().flatten();
+    let first = known.next()?;
+    for other in known {
+        if !other.is_equivalent(&first) {
+            problems.push(UnitProblem::Mismatch {
+                operator: operator.to_string(),
+                left: first.clone(),
+                right: other,
+            });
+        }
+    }
+    Some(first)
+}
+
+fn dimensionless(operator: &str, units: Option<SiUnit>, problems: &mut Vec<UnitProblem>) {
+    if let Some(found) = units.filter(|units| !units.is_dimensionless()) {
+        problems.push(UnitProblem::NotDimensionless {
+            operator: operator.to_string(),
+            found,
+        });
+    }
+}
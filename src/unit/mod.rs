@@ -0,0 +1,478 @@
+//! Units of measurement: the SBML base units, their definitions, the
+//! conversion of values between them and the [`check_consistency`] of the
+//! units of the math of a model.
+mod consistency;
+
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Div, Mul};
+
+use crate::mathml::AVOGADRO;
+
+pub use consistency::{check_consistency, MathElement, UnitIssue, UnitProblem};
+
+/// Define an enum (harcoded as pub) with a method `name()` to serialize it as
+/// a string representing its variant; e.g., A::B.name() == "B".
+macro_rules! enum_str {
+    ($(#[$outer:meta])*  // capture the docstring
+        enum $name:ident {
+        $($variant:ident),*,
+    }) => {
+        #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, Clone)]
+        #[allow(non_camel_case_types)]
+        #[serde(rename_all="camelCase")]
+        pub enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            const fn name(&self) -> &'static str {
+                match self {
+                    $($name::$variant => stringify!($variant)),*
+                }
+            }
+        }
+    };
+}
+
+/// Combination of [`Unit`].
+///
+/// The approach to defining units in SBML is compositional; for example,
+/// metre second −2 is constructed by combining
+/// an Unit object representing metre with another Unit object representing
+/// second −2.
+#[derive(Deserialize, Serialize, PartialEq, Debug, Default, Clone)]
+pub struct UnitDefinition {
+    pub id: Option<String>,
+    pub metaid: Option<String>,
+    pub name: Option<String>,
+    #[serde(rename = "listOfUnits", default)]
+    pub list_of_units: ListOfUnits,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Default, Clone)]
+pub struct ListOfUnits {
+    #[serde(rename = "unit")]
+    pub units: Vec<Unit>,
+}
+
+/// A Unit object represents a reference to a (possibly transformed) base unit
+/// (see [UnitSIdRef]).
+///
+/// The attribute kind indicates the base unit, whereas the attributes
+/// exponent, scale and multiplier define how the base unit is being transformed.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct Unit {
+    pub kind: UnitSIdRef,
+    pub exponent: f64,
+    pub scale: i64,
+    pub multiplier: f64,
+}
+
+/// SBML provides predefined base units, gathered in [`UnitSId`].
+/// Alternatively, one can use arbitrary `CustomUnit`s.
+#[derive(Debug, Deserialize, Hash, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum UnitSIdRef {
+    #[allow(clippy::upper_case_acronyms)]
+    SIUnit(UnitSId),
+    CustomUnit(String),
+}
+
+impl Serialize for UnitSIdRef {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::SIUnit(ref unit) => serializer.serialize_str(unit.name()),
+            Self::CustomUnit(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+impl fmt::Display for UnitSIdRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SIUnit(unit) => write!(f, "{}", unit.name()),
+            Self::CustomUnit(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<&str> for UnitSIdRef {
+    /// Base unit named `id`, or a custom unit if there is none.
+    fn from(id: &str) -> Self {
+        let deserializer: StrDeserializer<ValueError> = id.into_deserializer();
+        match UnitSId::deserialize(deserializer) {
+            Ok(unit) => Self::SIUnit(unit),
+            Err(_) => Self::CustomUnit(id.to_string()),
+        }
+    }
+}
+
+enum_str! {
+/// One of the predefined values of a base unit by SBML level 3.
+enum UnitSId {
+    ampere,
+    avogadro,
+    coulomb,
+    gray,
+    joule,
+    litre,
+    mole,
+    radian,
+    steradian,
+    weber,
+    dimensionless,
+    henry,
+    katal,
+    lumen,
+    newton,
+    tesla,
+    becquerel,
+    farad,
+    hertz,
+    kelvin,
+    lux,
+    ohm,
+    siemens,
+    volt,
+    candela,
+    gram,
+    item,
+    kilogram,
+    metre,
+    pascal,
+    sievert,
+    watt,
+    second,
+}}
+
+impl UnitSId {
+    /// This unit as a multiple of the [`SiUnit`] base units.
+    pub fn to_si(&self) -> SiUnit {
+        use UnitSId::*;
+        let (multiplier, exponents): (f64, &[(UnitSId, f64)]) = match self {
+            ampere => (1., &[(ampere, 1.)]),
+            candela => (1., &[(candela, 1.)]),
+            item => (1., &[(item, 1.)]),
+            kelvin => (1., &[(kelvin, 1.)]),
+            kilogram => (1., &[(kilogram, 1.)]),
+            metre => (1., &[(metre, 1.)]),
+            mole => (1., &[(mole, 1.)]),
+            second => (1., &[(second, 1.)]),
+            dimensionless | radian | steradian => (1., &[]),
+            avogadro => (AVOGADRO, &[]),
+            becquerel | hertz => (1., &[(second, -1.)]),
+            coulomb => (1., &[(ampere, 1.), (second, 1.)]),
+            farad => (
+                1.,
+                &[(ampere, 2.), (kilogram, -1.), (metre, -2.), (second, 4.)],
+            ),
+            gram => (1e-3, &[(kilogram, 1.)]),
+            gray | sievert => (1., &[(metre, 2.), (second, -2.)]),
+            henry => (
+                1.,
+                &[(ampere, -2.), (kilogram, 1.), (metre, 2.), (second, -2.)],
+            ),
+            joule => (1., &[(kilogram, 1.), (metre, 2.), (second, -2.)]),
+            katal => (1., &[(mole, 1.), (second, -1.)]),
+            litre => (1e-3, &[(metre, 3.)]),
+            lumen => (1., &[(candela, 1.)]),
+            lux => (1., &[(candela, 1.), (metre, -2.)]),
+            newton => (1., &[(kilogram, 1.), (metre, 1.), (second, -2.)]),
+            ohm => (
+                1.,
+                &[(ampere, -2.), (kilogram, 1.), (metre, 2.), (second, -3.)],
+            ),
+            pascal => (1., &[(kilogram, 1.), (metre, -1.), (second, -2.)]),
+            siemens => (
+                1.,
+                &[(ampere, 2.), (kilogram, -1.), (metre, -2.), (second, 3.)],
+            ),
+            tesla => (1., &[(ampere, -1.), (kilogram, 1.), (second, -2.)]),
+            volt => (
+                1.,
+                &[(ampere, -1.), (kilogram, 1.), (metre, 2.), (second, -3.)],
+            ),
+            watt => (1., &[(kilogram, 1.), (metre, 2.), (second, -3.)]),
+            weber => (
+                1.,
+                &[(ampere, -1.), (kilogram, 1.), (metre, 2.), (second, -2.)],
+            ),
+        };
+        SiUnit {
+            multiplier,
+            exponents: exponents.iter().cloned().collect(),
+        }
+    }
+}
+
+impl From<UnitSId> for Unit {
+    /// The base unit `kind`, untransformed.
+    fn from(kind: UnitSId) -> Self {
+        Unit {
+            kind: UnitSIdRef::SIUnit(kind),
+            exponent: 1.,
+            scale: 0,
+            multiplier: 1.,
+        }
+    }
+}
+
+impl Unit {
+    /// $(multiplier \cdot 10^{scale} \cdot kind)^{exponent}$ as a multiple of
+    /// the [`SiUnit`] base units.
+    pub fn to_si(&self) -> Result<SiUnit, UnitError> {
+        match &self.kind {
+            UnitSIdRef::SIUnit(kind) => {
+                let base = kind.to_si();
+                let multiplier = base.multiplier * self.multiplier * 10f64.powi(self.scale as i32);
+                Ok(SiUnit::default().times(
+                    &SiUnit {
+                        multiplier,
+                        exponents: base.exponents,
+                    },
+                    self.exponent,
+                ))
+            }
+            UnitSIdRef::CustomUnit(id) => Err(UnitError::UnknownUnit(id.to_owned())),
+        }
+    }
+}
+
+impl From<UnitSId> for UnitDefinition {
+    /// Anonymous definition of the base unit `kind`.
+    fn from(kind: UnitSId) -> Self {
+        UnitDefinition {
+            list_of_units: ListOfUnits {
+                units: vec![Unit::from(kind)],
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl UnitDefinition {
+    /// The product of the units of this definition as a multiple of the
+    /// [`SiUnit`] base units.
+    pub fn to_si(&self) -> Result<SiUnit, UnitError> {
+        self.list_of_units
+            .units
+            .iter()
+            .try_fold(SiUnit::default(), |product, unit| {
+                Ok(product.times(&unit.to_si()?, 1.))
+            })
+    }
+
+    /// Anonymous definition of this unit raised to `exponent`.
+    pub fn pow(&self, exponent: f64) -> UnitDefinition {
+        UnitDefinition {
+            list_of_units: ListOfUnits {
+                units: self
+                    .list_of_units
+                    .units
+                    .iter()
+                    .map(|unit| Unit {
+                        exponent: unit.exponent * exponent,
+                        ..unit.clone()
+                    })
+                    .collect(),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Whether both units measure the same quantity, so that values can be
+    /// [`convert`]ed between them.
+    pub fn is_compatible(&self, other: &UnitDefinition) -> Result<bool, UnitError> {
+        Ok(self.to_si()?.same_dimensions(&other.to_si()?))
+    }
+
+    /// Whether both units are the same, however they are written; e.g.,
+    /// `litre` and `metre^3` with a scale of -1.
+    pub fn is_equivalent(&self, other: &UnitDefinition) -> Result<bool, UnitError> {
+        Ok(self.to_si()?.is_equivalent(&other.to_si()?))
+    }
+}
+
+impl Mul<&UnitDefinition> for &UnitDefinition {
+    type Output = UnitDefinition;
+
+    fn mul(self, rhs: &UnitDefinition) -> UnitDefinition {
+        UnitDefinition {
+            list_of_units: ListOfUnits {
+                units: self
+                    .list_of_units
+                    .units
+                    .iter()
+                    .chain(&rhs.list_of_units.units)
+                    .cloned()
+                    .collect(),
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl Mul for UnitDefinition {
+    type Output = UnitDefinition;
+
+    fn mul(self, rhs: UnitDefinition) -> UnitDefinition {
+        &self * &rhs
+    }
+}
+
+impl Div<&UnitDefinition> for &UnitDefinition {
+    type Output = UnitDefinition;
+
+    fn div(self, rhs: &UnitDefinition) -> UnitDefinition {
+        self * &rhs.pow(-1.)
+    }
+}
+
+impl Div for UnitDefinition {
+    type Output = UnitDefinition;
+
+    fn div(self, rhs: UnitDefinition) -> UnitDefinition {
+        &self / &rhs
+    }
+}
+
+/// A unit as a multiple of a product of powers of the SI base units
+/// (`ampere`, `candela`, `kelvin`, `kilogram`, `metre`, `mole` and `second`)
+/// and of `item`, which SBML keeps as a base unit of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiUnit {
+    pub multiplier: f64,
+    /// Non-zero exponent of each base unit.
+    pub exponents: BTreeMap<UnitSId, f64>,
+}
+
+impl Default for SiUnit {
+    /// The dimensionless unit.
+    fn default() -> Self {
+        SiUnit {
+            multiplier: 1.,
+            exponents: BTreeMap::new(),
+        }
+    }
+}
+
+impl SiUnit {
+    /// This unit times `other` raised to `exponent`.
+    fn times(mut self, other: &SiUnit, exponent: f64) -> SiUnit {
+        self.multiplier *= other.multiplier.powf(exponent);
+        for (base, e) in &other.exponents {
+            let sum = self.exponents.get(base).unwrap_or(&0.) + e * exponent;
+            if close(sum, 0.) {
+                self.exponents.remove(base);
+            } else {
+                self.exponents.insert(base.clone(), sum);
+            }
+        }
+        self
+    }
+
+    /// Whether both units have the same exponents, regardless of their
+    /// multipliers.
+    pub fn same_dimensions(&self, other: &SiUnit) -> bool {
+        self.exponents.len() == other.exponents.len()
+            && self
+                .exponents
+                .iter()
+                .all(|(base, e)| matches!(other.exponents.get(base), Some(o) if close(*e, *o)))
+    }
+
+    /// Whether both units have the same exponents and multiplier.
+    pub fn is_equivalent(&self, other: &SiUnit) -> bool {
+        self.same_dimensions(other) && close(self.multiplier, other.multiplier)
+    }
+
+    /// Whether this unit has no dimensions, whatever its multiplier.
+    pub fn is_dimensionless(&self) -> bool {
+        self.exponents.is_empty()
+    }
+}
+
+impl fmt::Display for SiUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.multiplier)?;
+        for (base, exponent) in &self.exponents {
+            if (exponent - 1.).abs() < f64::EPSILON {
+                write!(f, " {}", base.name())?;
+            } else {
+                write!(f, " {}^{}", base.name(), exponent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Equality up to rounding errors.
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.)
+}
+
+/// Error raised while normalizing or converting units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnitError {
+    /// A unit whose kind is not a base unit of SBML.
+    UnknownUnit(String),
+    /// Units that measure different quantities.
+    Incompatible(SiUnit, SiUnit),
+}
+
+impl fmt::Display for UnitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnitError::UnknownUnit(id) => write!(f, "`{}` is not a base unit", id),
+            UnitError::Incompatible(from, to) => {
+                write!(f, "cannot convert `{}` to `{}`", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnitError {}
+
+/// Convert `value`, measured in `from`, to the unit `to`.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::unit::{convert, ListOfUnits, UnitDefinition};
+/// use rust_sbml::{Unit, UnitSId};
+///
+/// let millimole = Unit {
+///     scale: -3,
+///     ..Unit::from(UnitSId::mole)
+/// };
+/// let per_hour = Unit {
+///     exponent: -1.,
+///     multiplier: 3600.,
+///     ..Unit::from(UnitSId::second)
+/// };
+/// let mmol_per_hour = UnitDefinition {
+///     list_of_units: ListOfUnits {
+///         units: vec![millimole, per_hour],
+///     },
+///     ..Default::default()
+/// };
+/// let katal = UnitDefinition::from(UnitSId::katal);
+/// assert!(mmol_per_hour.is_compatible(&katal).unwrap());
+/// assert!((convert(3600., &mmol_per_hour, &katal).unwrap() - 1e-3).abs() < 1e-15);
+/// assert!(convert(1., &katal, &UnitDefinition::from(UnitSId::litre)).is_err());
+/// ```
+pub fn convert(value: f64, from: &UnitDefinition, to: &UnitDefinition) -> Result<f64, UnitError> {
+    let (from, to) = (from.to_si()?, to.to_si()?);
+    if from.same_dimensions(&to) {
+        Ok(value * from.multiplier / to.multiplier)
+    } else {
+        Err(UnitError::Incompatible(from, to))
+    }
+}
@@ -0,0 +1,547 @@
+//! Semantic validation of a [`Model`] against the identifier and reference
+//! consistency rules of SBML Level 3 and its FBC package.
+//!
+//! Each [`Diagnostic`] names the rule it breaks by its number in the
+//! specification (`fbc-` numbers for the FBC package), as libSBML does.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::mathml::{called_functions, recursive_functions};
+use crate::{Model, ModelRaw, Reaction, Rule, SpeciesReference};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The model breaks a rule of the specification.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A broken consistency rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Number of the validation rule, e.g. `20601` or `fbc-20705`, or
+    /// `model` when [`validate_raw`] cannot build the [`Model`] to check.
+    pub rule: String,
+    /// Identifier of the offending component.
+    pub id: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(rule: &str, id: &str, message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            rule: rule.to_string(),
+            id: id.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} (`{}`): {}",
+            self.severity, self.rule, self.id, self.message
+        )
+    }
+}
+
+/// Check the identifiers and references of `model`:
+///
+/// - 10301: the SIds of all the components are unique.
+/// - 20802: no symbol has more than one initial assignment.
+/// - 20303 and 10214: the function definitions do not call themselves,
+///   directly or through other function definitions, and only call
+///   function definitions that exist.
+/// - 20601: the compartment of each species exists.
+/// - 21111: the species of each species reference exists.
+/// - 20801, 20901, 20902 and 21211: the symbols of the initial
+///   assignments, rules and event assignments exist.
+/// - 20903, 20904 and 21212: the variables of the assignment rules, rate
+///   rules and event assignments are not constant.
+/// - 20803: no symbol has both an assignment rule and an initial
+///   assignment.
+/// - fbc-20705 and fbc-20706: the flux bounds are existing parameters, and
+///   fbc-20709 and fbc-20710: they are constant.
+///
+/// The components of each kind are collected by id when the model is
+/// parsed, so repeated ids are only seen across kinds, such as a species and
+/// a parameter; use [`validate_raw`] to find repetitions within one of them.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::validation::{validate, Severity};
+/// use rust_sbml::Model;
+///
+/// let model = Model::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model>
+///       <listOfSpecies>
+///         <species id="S" compartment="cytosol" initialAmount="1"
+///           hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+///       </listOfSpecies>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let diagnostics = validate(&model);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].severity, Severity::Error);
+/// assert_eq!(diagnostics[0].rule, "20601");
+/// assert_eq!(diagnostics[0].id, "S");
+/// ```
+pub fn validate(model: &Model) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let reactions = sorted(&model.reactions);
+
+    // 10301
+    let mut ids: Vec<(&str, &str)> = Vec::new();
+    ids.extend(model.id.as_deref().map(|id| (id, "model")));
+    ids.extend(
+        sorted(&model.function_definitions)
+            .iter()
+            .map(|(id, _)| (*id, "function definition")),
+    );
+    ids.extend(
+        sorted(&model.compartments)
+            .iter()
+            .map(|(id, _)| (*id, "compartment")),
+    );
+    ids.extend(
+        sorted(&model.species)
+            .iter()
+            .map(|(id, _)| (*id, "species")),
+    );
+    ids.extend(
+        sorted(&model.parameters)
+            .iter()
+            .map(|(id, _)| (*id, "parameter")),
+    );
+    ids.extend(
+        sorted(&model.initial_assignments)
+            .iter()
+            .filter_map(|(_, assignment)| assignment.id.as_deref())
+            .map(|id| (id, "initial assignment")),
+    );
+    ids.extend(
+        model
+            .constraints
+            .iter()
+            .filter_map(|c| c.id.as_deref())
+            .map(|id| (id, "constraint")),
+    );
+    ids.extend(reaction_ids(
+        reactions.iter().map(|(_, reaction)| *reaction),
+    ));
+    ids.extend(
+        model
+            .events
+            .iter()
+            .filter_map(|e| e.id.as_deref())
+            .map(|id| (id, "event")),
+    );
+    ids.extend(
+        sorted(&model.gene_products)
+            .iter()
+            .map(|(id, _)| (*id, "gene product")),
+    );
+    ids.extend(
+        model
+            .objective_functions
+            .iter()
+            .map(|o| (o.id.as_str(), "objective")),
+    );
+    unique_ids(ids, &mut diagnostics);
+    // 20802
+    unique_symbols(
+        sorted(&model.initial_assignments)
+            .iter()
+            .map(|(_, assignment)| assignment.symbol.as_str()),
+        &mut diagnostics,
+    );
+    check_references(model, &mut diagnostics);
+    diagnostics
+}
+
+/// [`validate`] the model of a parsed document, finding the repeated ids
+/// (10301) and initial assignment symbols (20802) in all its lists,
+/// including those within one kind of component, in document order.
+/// If the lists cannot be collected into a [`Model`], such as for a unit
+/// definition without id, the other rules are not checked and the error is
+/// reported as a `model` diagnostic.
+///
+/// # Example
+///
+/// ```
+/// use rust_sbml::validation::validate_raw;
+/// use rust_sbml::ModelRaw;
+///
+/// let raw_model = ModelRaw::parse(
+///     r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+///     <model>
+///       <listOfParameters>
+///         <parameter id="k" value="1" constant="true"/>
+///         <parameter id="k" value="2" constant="true"/>
+///       </listOfParameters>
+///     </model>
+///     </sbml>"#,
+/// )
+/// .unwrap();
+/// let diagnostics = validate_raw(&raw_model);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!((diagnostics[0].rule.as_str(), diagnostics[0].id.as_str()), ("10301", "k"));
+/// ```
+pub fn validate_raw(raw_model: &ModelRaw) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // 10301
+    let mut ids: Vec<(&str, &str)> = Vec::new();
+    ids.extend(raw_model.id.as_deref().map(|id| (id, "model")));
+    ids.extend(
+        raw_model
+            .list_of_function_definitions
+            .iter()
+            .flat_map(|list| &list.function_definitions)
+            .map(|f| (f.id.as_str(), "function definition")),
+    );
+    ids.extend(
+        raw_model
+            .list_of_compartments
+            .compartments
+            .iter()
+            .map(|c| (c.id.as_str(), "compartment")),
+    );
+    ids.extend(
+        raw_model
+            .list_of_species
+            .species
+            .iter()
+            .map(|s| (s.id.as_str(), "species")),
+    );
+    ids.extend(
+        raw_model
+            .list_of_parameters
+            .parameters
+            .iter()
+            .map(|p| (p.id.as_str(), "parameter")),
+    );
+    let initial_assignments = &raw_model.list_of_initial_assignments.initial_assignments;
+    ids.extend(
+        initial_assignments
+            .iter()
+            .filter_map(|assignment| assignment.id.as_deref())
+            .map(|id| (id, "initial assignment")),
+    );
+    ids.extend(
+        raw_model
+            .list_of_constraints
+            .constraints
+            .iter()
+            .filter_map(|c| c.id.as_deref())
+            .map(|id| (id, "constraint")),
+    );
+    ids.extend(reaction_ids(raw_model.list_of_reactions.reactions.iter()));
+    ids.extend(
+        raw_model
+            .list_of_events
+            .iter()
+            .flat_map(|list| &list.events)
+            .filter_map(|e| e.id.as_deref())
+            .map(|id| (id, "event")),
+    );
+    ids.extend(
+        raw_model
+            .list_of_gene_products
+            .iter()
+            .flat_map(|list| &list.gene_products)
+            .map(|g| (g.id.as_str(), "gene product")),
+    );
+    ids.extend(
+        raw_model
+            .list_of_objectives
+            .iter()
+            .flat_map(|list| &list.objectives)
+            .map(|o| (o.id.as_str(), "objective")),
+    );
+    unique_ids(ids, &mut diagnostics);
+    // 20802
+    unique_symbols(
+        initial_assignments
+            .iter()
+            .map(|assignment| assignment.symbol.as_str()),
+        &mut diagnostics,
+    );
+    // the references do not depend on the repeated components
+    match Model::try_from(raw_model.clone()) {
+        Ok(model) => check_references(&model, &mut diagnostics),
+        Err(e) => diagnostics.push(Diagnostic::error(
+            "model",
+            &e.location().path,
+            format!("the references were not checked: {}", e),
+        )),
+    }
+    diagnostics
+}
+
+/// Ids of the `reactions`, their modifier species references and then all
+/// their species references.
+fn reaction_ids<'a>(
+    reactions: impl Iterator<Item = &'a Reaction> + Clone,
+) -> Vec<(&'a str, &'static str)> {
+    let mut ids = Vec::new();
+    for reaction in reactions.clone() {
+        ids.push((reaction.id.as_str(), "reaction"));
+        ids.extend(
+            reaction
+                .list_of_modifiers
+                .modifier_species_references
+                .iter()
+                .filter_map(|reference| reference.id.as_deref())
+                .map(|id| (id, "modifier species reference")),
+        );
+    }
+    ids.extend(
+        reactions
+            .flat_map(species_references)
+            .filter_map(|reference| reference.id.as_deref())
+            .map(|id| (id, "species reference")),
+    );
+    ids
+}
+
+fn species_references(reaction: &Reaction) -> impl Iterator<Item = &SpeciesReference> {
+    reaction
+        .list_of_reactants
+        .species_references
+        .iter()
+        .chain(&reaction.list_of_products.species_references)
+}
+
+/// 10301: report the ids of `ids`, with the kind of their component, that
+/// were already used.
+fn unique_ids(ids: Vec<(&str, &str)>, diagnostics: &mut Vec<Diagnostic>) {
+    let mut first_use: HashMap<&str, &str> = HashMap::new();
+    for (id, kind) in ids {
+        match first_use.get(id) {
+            Some(first) => diagnostics.push(Diagnostic::error(
+                "10301",
+                id,
+                format!("the id of a {} is already the id of a {}", kind, first),
+            )),
+            None => {
+                first_use.insert(id, kind);
+            }
+        }
+    }
+}
+
+/// 20802: report the symbols that have more than one initial assignment.
+fn unique_symbols<'a>(symbols: impl Iterator<Item = &'a str>, diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = Vec::new();
+    for symbol in symbols {
+        if seen.contains(&symbol) {
+            diagnostics.push(Diagnostic::error(
+                "20802",
+                symbol,
+                "the symbol has more than one initial assignment".to_string(),
+            ));
+        } else {
+            seen.push(symbol);
+        }
+    }
+}
+
+/// The rules about the references between the components of `model`.
+fn check_references(model: &Model, diagnostics: &mut Vec<Diagnostic>) {
+    let reactions = sorted(&model.reactions);
+    let references: Vec<(&str, bool)> = reactions
+        .iter()
+        .flat_map(|(_, reaction)| species_references(reaction))
+        .filter_map(|reference| Some((reference.id.as_deref()?, reference.constant)))
+        .collect();
+
+    // 20303 and 10214
+    let recursive = recursive_functions(&model.function_definitions);
+    for (id, function) in sorted(&model.function_definitions) {
+        if recursive.iter().any(|r| r == id) {
+            diagnostics.push(Diagnostic::error(
+                "20303",
+                id,
+                "the function calls itself, directly or through other function definitions"
+                    .to_string(),
+            ));
+        }
+        for callee in called_functions(&function.math.content) {
+            if !model.function_definitions.contains_key(&callee) {
+                diagnostics.push(Diagnostic::error(
+                    "10214",
+                    id,
+                    format!("call to the undefined function `{}`", callee),
+                ));
+            }
+        }
+    }
+
+    // 20601
+    for (id, species) in sorted(&model.species) {
+        if !model.compartments.contains_key(&species.compartment) {
+            diagnostics.push(Diagnostic::error(
+                "20601",
+                id,
+                format!("unknown compartment `{}`", species.compartment),
+            ));
+        }
+    }
+
+    // 21111
+    for (id, reaction) in reactions.iter() {
+        let species = species_references(reaction)
+            .map(|reference| &reference.species)
+            .chain(
+                reaction
+                    .list_of_modifiers
+                    .modifier_species_references
+                    .iter()
+                    .map(|reference| &reference.species),
+            );
+        for species in species {
+            if !model.species.contains_key(species) {
+                diagnostics.push(Diagnostic::error(
+                    "21111",
+                    id,
+                    format!("unknown species `{}`", species),
+                ));
+            }
+        }
+    }
+
+    // whether a symbol that can be assigned exists, and if it is constant
+    let constant = |id: &str| -> Option<bool> {
+        if let Some(compartment) = model.compartments.get(id) {
+            Some(compartment.constant)
+        } else if let Some(species) = model.species.get(id) {
+            Some(species.constant)
+        } else if let Some(parameter) = model.parameters.get(id) {
+            Some(parameter.constant)
+        } else {
+            references
+                .iter()
+                .find(|(reference, _)| *reference == id)
+                .map(|(_, constant)| *constant)
+        }
+    };
+    let mut check_variable = |variable: &str, exists: &str, not_constant: Option<&str>| match (
+        constant(variable),
+        not_constant,
+    ) {
+        (None, _) => diagnostics.push(Diagnostic::error(
+            exists,
+            variable,
+            "no compartment, species, parameter or species reference has this id".to_string(),
+        )),
+        (Some(true), Some(rule)) => diagnostics.push(Diagnostic::error(
+            rule,
+            variable,
+            "a constant symbol cannot be assigned".to_string(),
+        )),
+        _ => {}
+    };
+
+    // 20801
+    let initial_assignments = sorted(&model.initial_assignments);
+    for (_, assignment) in initial_assignments.iter() {
+        check_variable(&assignment.symbol, "20801", None);
+    }
+    // 20901 to 20904
+    for rule in &model.rules {
+        match rule {
+            Rule::AssignmentRule { variable, .. } => {
+                check_variable(variable, "20901", Some("20903"))
+            }
+            Rule::RateRule { variable, .. } => check_variable(variable, "20902", Some("20904")),
+            Rule::AlgebraicRule { .. } => {}
+        }
+    }
+    // 21211 and 21212
+    for event in &model.events {
+        for assignment in &event.list_of_event_assignments.event_assignments {
+            check_variable(&assignment.variable, "21211", Some("21212"));
+        }
+    }
+
+    // 20803
+    for (_, assignment) in initial_assignments.iter() {
+        let symbol = &assignment.symbol;
+        if model
+            .rules
+            .iter()
+            .any(|rule| matches!(rule, Rule::AssignmentRule { variable, .. } if variable == symbol))
+        {
+            diagnostics.push(Diagnostic::error(
+                "20803",
+                symbol,
+                "the symbol has both an initial assignment and an assignment rule".to_string(),
+            ));
+        }
+    }
+
+    // fbc-20705 to fbc-20710
+    for (id, reaction) in reactions.iter() {
+        let bounds = [
+            (
+                reaction.lower_bound.as_deref(),
+                "lower",
+                "fbc-20705",
+                "fbc-20709",
+            ),
+            (
+                reaction.upper_bound.as_deref(),
+                "upper",
+                "fbc-20706",
+                "fbc-20710",
+            ),
+        ];
+        for (bound, side, exists, constant) in bounds.iter() {
+            let bound = match bound {
+                Some(bound) => bound,
+                None => continue,
+            };
+            match model.parameters.get(*bound) {
+                None => diagnostics.push(Diagnostic::error(
+                    exists,
+                    id,
+                    format!("unknown {} flux bound parameter `{}`", side, bound),
+                )),
+                Some(parameter) if !parameter.constant => diagnostics.push(Diagnostic::error(
+                    constant,
+                    id,
+                    format!(
+                        "the {} flux bound parameter `{}` is not constant",
+                        side, bound
+                    ),
+                )),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Entries of `map`, sorted by key so that the diagnostics are reproducible.
+fn sorted<T>(map: &HashMap<String, T>) -> Vec<(&str, &T)> {
+    let mut entries: Vec<(&str, &T)> = map.iter().map(|(id, value)| (id.as_str(), value)).collect();
+    entries.sort_by_key(|(id, _)| *id);
+    entries
+}
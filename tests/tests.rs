@@ -1,6 +1,9 @@
+use rust_sbml::unit::{self, MathElement, UnitError, UnitProblem};
+use rust_sbml::validation::{self, Severity};
 use rust_sbml::{
-    mathml, mathml::Math, mathml::MathNode, parse_document, Constraint, Message, Model, ModelRaw,
-    ModelUnits, UnitSId, UnitSIdRef,
+    mathml, mathml::Math, mathml::MathNode, parse_document, Association, ChemicalFormula,
+    Constraint, Error, MassBalanceError, Message, Model, ModelRaw, ModelUnits, ObjectiveSense,
+    SbmlDocument, Unit, UnitDefinition, UnitSId, UnitSIdRef,
 };
 
 #[test]
@@ -66,8 +69,8 @@ fn model_has_more_species_annotations_species() {
     let model = Model::parse(file_str).unwrap();
     let annot_len = model
         .species
-        .iter()
-        .map(|(_, sp)| match sp.annotation.as_ref() {
+        .values()
+        .map(|sp| match sp.annotation.as_ref() {
             Some(rdf) => rdf.into_iter().count(),
             None => 0,
         })
@@ -118,6 +121,7 @@ fn test_constraints() {
                         MathNode::Cn(mathml::Cn {
                             cn_type: mathml::NumberType::Real,
                             content: String::from("1"),
+                            sep_content: None,
                             base: mathml::Base::default(),
                             definition_url: None,
                             encoding: None,
@@ -137,6 +141,7 @@ fn test_constraints() {
                         MathNode::Cn(mathml::Cn {
                             cn_type: mathml::NumberType::Real,
                             content: String::from("100"),
+                            sep_content: None,
                             base: mathml::Base::default(),
                             definition_url: None,
                             encoding: None,
@@ -154,3 +159,1918 @@ fn test_constraints() {
     };
     assert_eq!(res, expect);
 }
+
+#[test]
+fn document_keeps_level_version_and_packages() {
+    let file_str = include_str!("EcoliCore.xml");
+    let document = SbmlDocument::parse(file_str).unwrap();
+    assert_eq!(document.sbo_term.as_deref(), Some("SBO:0000624"));
+    assert_eq!(
+        document.declared_packages().collect::<Vec<&str>>(),
+        vec!["fbc", "groups"]
+    );
+    assert_eq!(document.required_packages().count(), 0);
+
+    let document_again = SbmlDocument::parse(&document.to_string().unwrap()).unwrap();
+    assert_eq!(document_again.xmlns, document.xmlns);
+    assert_eq!((document_again.level, document_again.version), (3, 1));
+    assert_eq!(document_again.packages, document.packages);
+}
+
+#[test]
+fn parse_error_reports_element_path_and_position() {
+    let example = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+    <model>
+        <listOfReactions>
+            <reaction id="R_PGK" reversible="false">
+                <listOfReactants>
+                    <speciesReference species="M_3pg_c" stoichiometry="one" constant="true"/>
+                </listOfReactants>
+            </reaction>
+            <reaction id="R_PGI" reversible="true"/>
+        </listOfReactions>
+    </model>
+</sbml>"#;
+    match Model::parse(example).unwrap_err() {
+        Error::BadValue { location, .. } => {
+            assert_eq!(
+                location.path,
+                "model/listOfReactions/reaction[id=R_PGK]/listOfReactants/speciesReference"
+            );
+            let position = location.position.unwrap();
+            assert_eq!((position.line, position.column), (7, 21));
+        }
+        e => panic!("expected a bad value, got {:?}", e),
+    }
+
+    let missing = example.replace(r#"stoichiometry="one""#, "");
+    let missing = missing.replace(r#" reversible="false""#, "");
+    match Model::parse(&missing).unwrap_err() {
+        Error::MissingAttribute {
+            attribute,
+            location,
+        } => {
+            assert_eq!(attribute, "reversible");
+            assert_eq!(location.path, "model/listOfReactions/reaction[id=R_PGK]");
+            assert_eq!(location.position.unwrap().line, 5);
+        }
+        e => panic!("expected a missing attribute, got {:?}", e),
+    }
+
+    let truncated = example.replace(r#"stoichiometry="one""#, "");
+    let truncated = &truncated[..truncated.len() - 20];
+    match ModelRaw::parse(truncated).unwrap_err() {
+        Error::Xml { location, .. } => assert_eq!(location.path, "model"),
+        e => panic!("expected malformed XML, got {:?}", e),
+    }
+}
+
+#[test]
+fn optional_ids_do_not_panic() {
+    let example = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+    <model>
+        <listOfInitialAssignments>
+            <initialAssignment symbol="x">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <cn> 2 </cn>
+                </math>
+            </initialAssignment>
+        </listOfInitialAssignments>
+    </model>
+</sbml>"#;
+    let model = Model::parse(example).unwrap();
+    assert_eq!(model.initial_assignments["x"].symbol, "x");
+
+    let without_unit_id = example.replace(
+        "<listOfInitialAssignments>",
+        r#"<listOfUnitDefinitions>
+            <unitDefinition><listOfUnits>
+                <unit kind="mole" exponent="1" scale="-3" multiplier="1"/>
+            </listOfUnits></unitDefinition>
+        </listOfUnitDefinitions>
+        <listOfInitialAssignments>"#,
+    );
+    assert!(matches!(
+        Model::parse(&without_unit_id).unwrap_err(),
+        Error::MissingAttribute { attribute, .. } if attribute == "id"
+    ));
+}
+
+#[test]
+fn gene_product_associations_round_trip() {
+    let file_str = include_str!("EcoliCore.xml");
+    let model = Model::parse(file_str).unwrap();
+    assert_eq!(model.gene_products.len(), 137);
+    assert_eq!(model.gene_products["G_b0351"].label, "b0351");
+    let gpr = &model.reactions["R_ACALD"]
+        .gene_product_association
+        .as_ref()
+        .unwrap()
+        .association;
+    assert!(matches!(gpr, Association::Or { .. }));
+    assert_eq!(gpr.gene_products(), vec!["G_b0351", "G_b1241"]);
+
+    let raw_model = ModelRaw::parse(file_str).unwrap();
+    let written = raw_model.to_string().unwrap();
+    assert!(written.contains("<fbc:geneProductRef fbc:geneProduct=\"G_b0351\"/>"));
+    let model_again = Model::parse(&written).unwrap();
+    assert!(model
+        .gene_products
+        .values()
+        .all(|gp| model_again.gene_products[&gp.id].label == gp.label));
+    assert_eq!(
+        model_again.reactions["R_ACALD"].gene_product_association,
+        model.reactions["R_ACALD"].gene_product_association
+    );
+
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfReactions>
+            <reaction id="R1" reversible="true">
+                <fbc:geneProductAssociation>
+                    <fbc:or fbc:id="or1" metaid="m_or1" sboTerm="SBO:0000174">
+                        <fbc:geneProductRef fbc:id="ref1" fbc:name="first" metaid="m_ref1"
+                            sboTerm="SBO:0000243" fbc:geneProduct="G1"/>
+                        <fbc:and fbc:id="and1" fbc:name="both">
+                            <fbc:geneProductRef fbc:geneProduct="G2"/>
+                            <fbc:geneProductRef fbc:geneProduct="G3"/>
+                        </fbc:and>
+                    </fbc:or>
+                </fbc:geneProductAssociation>
+            </reaction>
+        </listOfReactions></model>"#,
+    );
+    let association = Model::parse(&example).unwrap().reactions["R1"]
+        .gene_product_association
+        .clone()
+        .unwrap()
+        .association;
+    match &association {
+        Association::Or {
+            id,
+            metaid,
+            sbo_term,
+            children,
+            ..
+        } => {
+            assert_eq!(id.as_deref(), Some("or1"));
+            assert_eq!(metaid.as_deref(), Some("m_or1"));
+            assert_eq!(sbo_term.as_deref(), Some("SBO:0000174"));
+            assert!(matches!(
+                &children[0],
+                Association::GeneProductRef { id: Some(id), name: Some(name), .. }
+                    if id == "ref1" && name == "first"
+            ));
+        }
+        _ => panic!("expected an fbc:or, found {:?}", association),
+    }
+    let written = ModelRaw::parse(&example).unwrap().to_string().unwrap();
+    assert_eq!(
+        Model::parse(&written).unwrap().reactions["R1"]
+            .gene_product_association
+            .as_ref()
+            .unwrap()
+            .association,
+        association
+    );
+}
+
+#[test]
+fn flux_bounds_are_resolved_to_values() {
+    let file_str = include_str!("EcoliCore.xml");
+    let model = Model::parse(file_str).unwrap();
+    let bounds = model.all_flux_bounds().unwrap();
+    assert_eq!(bounds.len(), model.reactions.len());
+    assert_eq!(bounds["R_ATPM"], (8.39, 1000.));
+    assert_eq!(bounds["R_PGK"], (-1000., 1000.));
+    assert!(matches!(
+        model.flux_bounds("R_NOT_THERE").unwrap_err(),
+        Error::UnknownId { id, .. } if id == "R_NOT_THERE"
+    ));
+
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfReactions>
+            <reaction id="R1" reversible="true"/>
+            <reaction id="R2" reversible="true" fbc:upperFluxBound="ub"/>
+        </listOfReactions></model>"#,
+    );
+    let model = Model::parse(&example).unwrap();
+    assert_eq!(
+        model.flux_bounds("R1").unwrap(),
+        (f64::NEG_INFINITY, f64::INFINITY)
+    );
+    assert!(matches!(
+        model.flux_bounds("R2").unwrap_err(),
+        Error::UnknownId { id, .. } if id == "ub"
+    ));
+}
+
+#[test]
+fn objectives_keep_sense_coefficients_and_active_flag() {
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<fbc:listOfObjectives fbc:activeObjective="obj1">
+            <fbc:objective fbc:id="obj1" fbc:type="maximize">
+                <fbc:listOfFluxObjectives>
+                    <fbc:fluxObjective fbc:reaction="R101" fbc:coefficient="1"/>
+                </fbc:listOfFluxObjectives>
+            </fbc:objective>
+            <fbc:objective fbc:id="obj2" fbc:type="minimize">
+                <fbc:listOfFluxObjectives>
+                    <fbc:fluxObjective fbc:reaction="R102" fbc:coefficient="-2.5"/>
+                    <fbc:fluxObjective fbc:reaction="R101" fbc:coefficient="1"/>
+                </fbc:listOfFluxObjectives>
+            </fbc:objective>
+        </fbc:listOfObjectives></model>"#,
+    );
+    let mut model = Model::parse(&example).unwrap();
+    assert_eq!(model.active_objective().unwrap().id, "obj1");
+    assert_eq!(model.objectives_of_reaction("R101").len(), 2);
+    model.set_active_objective("obj2").unwrap();
+    let active = model.active_objective().unwrap();
+    assert_eq!(active.sense, ObjectiveSense::Minimize);
+    assert_eq!(active.coefficients[0], ("R102".to_string(), -2.5));
+    assert!(model.set_active_objective("obj3").is_err());
+
+    let mut raw_model = ModelRaw::parse(&example).unwrap();
+    raw_model.set_active_objective("obj2").unwrap();
+    let written = raw_model.to_string().unwrap();
+    assert!(written.contains(r#"<fbc:listOfObjectives fbc:activeObjective="obj2">"#));
+    let model = Model::parse(&written).unwrap();
+    assert_eq!(model.active_objective().unwrap().id, "obj2");
+    assert_eq!(model.objective_functions.len(), 2);
+}
+
+#[test]
+fn stoichiometric_matrix_has_stable_indices() {
+    let file_str = include_str!("EcoliCore.xml");
+    let model = Model::parse(file_str).unwrap();
+    let s = model.stoichiometric_matrix(false);
+    assert_eq!(s.shape(), (72, 95));
+    assert_eq!(
+        s,
+        Model::parse(file_str).unwrap().stoichiometric_matrix(false)
+    );
+    let pgk = s.reactions.iter().position(|r| r == "R_PGK").unwrap();
+    let atp = s.species.iter().position(|m| m == "M_atp_c").unwrap();
+    let adp = s.species.iter().position(|m| m == "M_adp_c").unwrap();
+    assert_eq!((s.get(atp, pgk), s.get(adp, pgk)), (-1., 1.));
+    assert_eq!(s.triplets().count(), s.values.len());
+
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfSpecies>
+            <species id="A" compartment="c" hasOnlySubstanceUnits="false" boundaryCondition="true" constant="false"/>
+            <species id="B" compartment="c" hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+        </listOfSpecies>
+        <listOfReactions>
+            <reaction id="R1" reversible="false">
+                <listOfReactants><speciesReference species="A" constant="true"/></listOfReactants>
+                <listOfProducts><speciesReference species="B" stoichiometry="3" constant="true"/></listOfProducts>
+            </reaction>
+        </listOfReactions></model>"#,
+    );
+    let s = Model::parse(&example).unwrap().stoichiometric_matrix(true);
+    assert_eq!(s.species, vec!["B"]);
+    assert_eq!(s.to_dense(), vec![vec![3.]]);
+}
+
+#[test]
+fn events_round_trip() {
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfEvents>
+            <event id="dose" useValuesFromTriggerTime="false">
+                <trigger initialValue="false" persistent="true">
+                    <math xmlns="http://www.w3.org/1998/Math/MathML">
+                        <apply><gt/><ci> S1 </ci><cn> 2 </cn></apply>
+                    </math>
+                </trigger>
+                <priority>
+                    <math xmlns="http://www.w3.org/1998/Math/MathML"><cn type="integer"> 1 </cn></math>
+                </priority>
+                <delay>
+                    <math xmlns="http://www.w3.org/1998/Math/MathML"><ci> tau </ci></math>
+                </delay>
+                <listOfEventAssignments>
+                    <eventAssignment variable="S1">
+                        <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 0 </cn></math>
+                    </eventAssignment>
+                    <eventAssignment variable="S2">
+                        <math xmlns="http://www.w3.org/1998/Math/MathML"><ci> S1 </ci></math>
+                    </eventAssignment>
+                </listOfEventAssignments>
+            </event>
+            <event>
+                <trigger initialValue="true" persistent="false"/>
+            </event>
+        </listOfEvents></model>"#,
+    );
+    let model = Model::parse(&example).unwrap();
+    assert_eq!(model.events.len(), 2);
+    let dose = &model.events[0];
+    assert_eq!(dose.id.as_deref(), Some("dose"));
+    assert!(!dose.use_values_from_trigger_time);
+    let trigger = dose.trigger.as_ref().unwrap();
+    assert!(!trigger.initial_value && trigger.persistent);
+    assert!(trigger.math.is_some());
+    assert!(dose.delay.as_ref().unwrap().math.is_some());
+    assert!(dose.priority.as_ref().unwrap().math.is_some());
+    let variables: Vec<&str> = dose
+        .list_of_event_assignments
+        .event_assignments
+        .iter()
+        .map(|assignment| assignment.variable.as_str())
+        .collect();
+    assert_eq!(variables, vec!["S1", "S2"]);
+    assert!(model.events[1].trigger.as_ref().unwrap().initial_value);
+
+    let written = ModelRaw::parse(&example).unwrap().to_string().unwrap();
+    assert!(written.contains(r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><apply><gt/>"#));
+    assert!(written.contains(r#"<cn type="integer">1</cn>"#));
+    assert_eq!(Model::parse(&written).unwrap().events, model.events);
+}
+
+#[test]
+fn modifiers_round_trip() {
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfReactions>
+            <reaction id="R1" reversible="false">
+                <listOfReactants><speciesReference species="S" constant="true"/></listOfReactants>
+                <listOfProducts><speciesReference species="P" constant="true"/></listOfProducts>
+                <listOfModifiers>
+                    <modifierSpeciesReference id="cat" species="E" sboTerm="SBO:0000460"/>
+                    <modifierSpeciesReference species="I" sboTerm="SBO:0000020"/>
+                </listOfModifiers>
+            </reaction>
+        </listOfReactions></model>"#,
+    );
+    let model = Model::parse(&example).unwrap();
+    let modifiers = &model.reactions["R1"]
+        .list_of_modifiers
+        .modifier_species_references;
+    assert_eq!(modifiers.len(), 2);
+    assert_eq!(modifiers[0].id.as_deref(), Some("cat"));
+    assert_eq!(modifiers[0].species, "E");
+    assert_eq!(modifiers[1].sbo_term.as_deref(), Some("SBO:0000020"));
+    // modifiers are neither consumed nor produced
+    assert_eq!(model.stoichiometric_matrix(false).species, vec!["P", "S"]);
+
+    let written = ModelRaw::parse(&example).unwrap().to_string().unwrap();
+    assert_eq!(
+        Model::parse(&written).unwrap().reactions["R1"],
+        model.reactions["R1"]
+    );
+}
+
+#[test]
+fn math_is_evaluated() {
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfFunctionDefinitions>
+            <functionDefinition id="mm">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <lambda>
+                        <bvar><ci> v </ci></bvar>
+                        <bvar><ci> km </ci></bvar>
+                        <bvar><ci> s </ci></bvar>
+                        <apply><divide/>
+                            <apply><times/><ci> v </ci><ci> s </ci></apply>
+                            <apply><plus/><ci> km </ci><ci> s </ci></apply>
+                        </apply>
+                    </lambda>
+                </math>
+            </functionDefinition>
+        </listOfFunctionDefinitions>
+        <listOfInitialAssignments>
+            <initialAssignment symbol="x">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <piecewise>
+                        <piece>
+                            <apply><ci> mm </ci><cn> 10 </cn><cn> 1 </cn><ci> S </ci></apply>
+                            <apply><and/>
+                                <apply><gt/><ci> S </ci><cn type="integer"> 0 </cn></apply>
+                                <apply><lt/><csymbol encoding="text"
+                                    definitionURL="http://www.sbml.org/sbml/symbols/time"> t </csymbol>
+                                    <cn> 5 </cn></apply>
+                            </apply>
+                        </piece>
+                        <otherwise>
+                            <apply><times/>
+                                <csymbol encoding="text"
+                                    definitionURL="http://www.sbml.org/sbml/symbols/avogadro"> NA </csymbol>
+                                <apply><power/><cn> 10 </cn><cn> -23 </cn></apply>
+                            </apply>
+                        </otherwise>
+                    </piecewise>
+                </math>
+            </initialAssignment>
+        </listOfInitialAssignments></model>"#,
+    );
+    let model = Model::parse(&example).unwrap();
+    let math = &model.initial_assignments["x"]
+        .math
+        .as_ref()
+        .unwrap()
+        .content;
+    let env = |s: f64, t: f64| {
+        move |id: &str| match id {
+            "S" => Some(s),
+            mathml::TIME_URL => Some(t),
+            _ => None,
+        }
+    };
+    let functions = &model.function_definitions;
+    assert_eq!(
+        math.evaluate_with_functions(&env(4., 0.), functions),
+        Ok(8.)
+    );
+    let otherwise = math
+        .evaluate_with_functions(&env(4., 10.), functions)
+        .unwrap();
+    assert!((otherwise - 6.022_140_76).abs() < 1e-9);
+    assert_eq!(
+        math.evaluate(&env(4., 0.)),
+        Err(mathml::EvalError::UnknownFunction("mm".to_string()))
+    );
+    assert_eq!(
+        math.evaluate_with_functions(&|_| None, functions),
+        Err(mathml::EvalError::UnknownSymbol("S".to_string()))
+    );
+
+    for (apply, expected) in [
+        ("<minus/><cn>3</cn>", -3.),
+        ("<minus/><cn>3</cn><cn>5</cn>", -2.),
+        ("<root/><cn>16</cn>", 4.),
+        ("<log/><cn>1000</cn>", 3.),
+        ("<factorial/><cn type=\"integer\">5</cn>", 120.),
+        ("<factorial/><cn>1e18</cn>", f64::INFINITY),
+        ("<max/><cn>3</cn><cn>7</cn><cn>5</cn>", 7.),
+        ("<xor/><cn>1</cn><cn>1</cn><cn>1</cn>", 1.),
+        ("<leq/><cn>1</cn><cn>2</cn><cn>2</cn>", 1.),
+        ("<not/><apply><eq/><cn>1</cn><cn>2</cn></apply>", 1.),
+        ("<implies/><cn>1</cn><cn>0</cn>", 0.),
+    ] {
+        let math: Math =
+            quick_xml::de::from_str(&format!("<math><apply>{}</apply></math>", apply)).unwrap();
+        assert_eq!(math.evaluate(&|_| None), Ok(expected), "{}", apply);
+    }
+
+    // math with the new nodes is written back
+    let written = ModelRaw::parse(&example).unwrap().to_string().unwrap();
+    let model_again = Model::parse(&written).unwrap();
+    assert_eq!(model_again.function_definitions, model.function_definitions);
+    assert_eq!(
+        model_again.initial_assignments["x"].math,
+        model.initial_assignments["x"].math
+    );
+}
+
+#[test]
+fn recursive_functions_are_not_evaluated_nor_inlined() {
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfFunctionDefinitions>
+            <functionDefinition id="f">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <lambda><bvar><ci> a </ci></bvar><apply><ci> f </ci><ci> a </ci></apply></lambda>
+                </math>
+            </functionDefinition>
+            <functionDefinition id="g">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <lambda><bvar><ci> a </ci></bvar><apply><ci> h </ci><ci> a </ci></apply></lambda>
+                </math>
+            </functionDefinition>
+            <functionDefinition id="h">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <lambda><bvar><ci> a </ci></bvar><apply><ci> g </ci><ci> a </ci></apply></lambda>
+                </math>
+            </functionDefinition>
+            <functionDefinition id="double">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <lambda><bvar><ci> a </ci></bvar><apply><times/><cn> 2 </cn><ci> a </ci></apply></lambda>
+                </math>
+            </functionDefinition>
+        </listOfFunctionDefinitions></model>"#,
+    );
+    let functions = &Model::parse(&example).unwrap().function_definitions;
+    let env = |id: &str| if id == "x" { Some(3.) } else { None };
+    for (formula, expected) in [
+        (
+            "f(x)",
+            Err(mathml::EvalError::RecursiveFunction("f".to_string())),
+        ),
+        (
+            "1 + g(x)",
+            Err(mathml::EvalError::RecursiveFunction("g".to_string())),
+        ),
+        ("double(x)", Ok(6.)),
+    ]
+    .iter()
+    {
+        let math = Math::from_formula(formula).unwrap();
+        assert_eq!(
+            &math.content.evaluate_with_functions(&env, functions),
+            expected,
+            "{}",
+            formula
+        );
+    }
+    for (formula, expected) in [
+        ("f(x) + double(x)", "f(x) + 2 * x"),
+        ("g(double(1))", "g(2)"),
+    ]
+    .iter()
+    {
+        let math = Math::from_formula(formula).unwrap();
+        assert_eq!(
+            math.content.simplify_with_functions(functions).to_formula(),
+            *expected
+        );
+    }
+}
+
+#[cfg(feature = "fba")]
+#[test]
+fn fba_solves_ecoli_core() {
+    use rust_sbml::fba::{self, FbaError};
+
+    let mut ecoli = Model::parse(include_str!("../examples/EcoliCore.xml")).unwrap();
+    let solution = fba::optimize(&ecoli).unwrap();
+    assert!((solution.objective_value - 0.873_921_5).abs() < 1e-6);
+    let s = ecoli.stoichiometric_matrix(true);
+    for row in s.to_dense() {
+        let balance: f64 = row
+            .iter()
+            .zip(s.reactions.iter())
+            .map(|(coef, reac)| coef * solution.fluxes[reac])
+            .sum();
+        assert!(balance.abs() < 1e-6);
+    }
+    for (reac, flux) in solution.fluxes.iter() {
+        let (lb, ub) = ecoli.flux_bounds(reac).unwrap();
+        assert!(*flux >= lb - 1e-6 && *flux <= ub + 1e-6);
+        if *flux > lb + 1e-6 && *flux < ub - 1e-6 {
+            assert!(solution.reduced_costs[reac].abs() < 1e-6);
+        }
+    }
+    // draining glucose from the medium lowers growth
+    assert!(solution.shadow_prices["M_glc__D_e"] < 0.);
+
+    ecoli
+        .parameters
+        .get_mut("R_ATPM_lower_bound")
+        .unwrap()
+        .value = Some(500.);
+    assert_eq!(fba::optimize(&ecoli), Err(FbaError::Infeasible));
+    ecoli.objective_functions.clear();
+    assert_eq!(fba::optimize(&ecoli), Err(FbaError::NoObjective));
+}
+
+#[cfg(feature = "fba")]
+#[test]
+fn flux_variability_keeps_objective_near_optimum() {
+    use rust_sbml::fba::{self, FbaError};
+
+    let ecoli = Model::parse(include_str!("../examples/EcoliCore.xml")).unwrap();
+    let solution = fba::optimize(&ecoli).unwrap();
+    let ranges = fba::flux_variability(&ecoli, None, 1.).unwrap();
+    assert_eq!(ranges.len(), ecoli.reactions.len());
+    for (reac, (min, max)) in ranges.iter() {
+        let flux = solution.fluxes[reac];
+        assert!(*min <= flux + 1e-6 && flux <= *max + 1e-6);
+    }
+    let (min, max) = ranges["R_BIOMASS_Ecoli_core_w_GAM"];
+    assert!((min - solution.objective_value).abs() < 1e-6);
+    assert!((max - solution.objective_value).abs() < 1e-6);
+
+    let relaxed = fba::flux_variability(&ecoli, Some(&["R_BIOMASS_Ecoli_core_w_GAM"]), 0.).unwrap();
+    let (min, max) = relaxed["R_BIOMASS_Ecoli_core_w_GAM"];
+    assert!(min.abs() < 1e-6 && (max - solution.objective_value).abs() < 1e-6);
+    assert!(matches!(
+        fba::flux_variability(&ecoli, Some(&["R_nope"]), 1.),
+        Err(FbaError::Model(Error::UnknownId { .. }))
+    ));
+}
+
+#[cfg(feature = "fba")]
+#[test]
+fn flux_variability_relaxes_negative_optimum() {
+    use rust_sbml::ObjectiveSense;
+
+    // maximize -ATPM, whose optimum is -8.39 at the lower bound of ATPM
+    let mut ecoli = Model::parse(include_str!("../examples/EcoliCore.xml")).unwrap();
+    let objective = ecoli
+        .objective_functions
+        .iter_mut()
+        .find(|o| o.active)
+        .unwrap();
+    objective.sense = ObjectiveSense::Maximize;
+    objective.coefficients = vec![("R_ATPM".to_string(), -1.)];
+    let ranges = ecoli.flux_variability(Some(&["R_ATPM"]), 0.9).unwrap();
+    let (min, max) = ranges["R_ATPM"];
+    assert!((min - 8.39).abs() < 1e-6);
+    assert!((max - 8.39 * 1.1).abs() < 1e-6);
+
+    // minimize ATPM, whose optimum is 8.39
+    let objective = ecoli
+        .objective_functions
+        .iter_mut()
+        .find(|o| o.active)
+        .unwrap();
+    objective.sense = ObjectiveSense::Minimize;
+    objective.coefficients = vec![("R_ATPM".to_string(), 1.)];
+    let (min, max) = ecoli.flux_variability(Some(&["R_ATPM"]), 0.9).unwrap()["R_ATPM"];
+    assert!((min - 8.39).abs() < 1e-6);
+    assert!((max - 8.39 * 1.1).abs() < 1e-6);
+}
+
+#[test]
+fn knockout_genes_evaluates_gene_product_associations() {
+    let ecoli = Model::parse(include_str!("../examples/EcoliCore.xml")).unwrap();
+    // PGI is only catalyzed by b4025, TKT1 by either b2465 or b2935
+    let mut inactive = ecoli.inactive_reactions(&["G_b4025", "G_b2465"]).unwrap();
+    inactive.sort_unstable();
+    assert_eq!(inactive, vec!["R_PGI"]);
+    let knockout = ecoli.knockout_genes(&["G_b4025"]).unwrap();
+    assert_eq!(knockout.flux_bounds("R_PGI").unwrap(), (0., 0.));
+    assert_eq!(
+        knockout.flux_bounds("R_TKT1").unwrap(),
+        ecoli.flux_bounds("R_TKT1").unwrap()
+    );
+    assert_eq!(ecoli.knockout_genes(&[]).unwrap(), ecoli);
+    assert!(matches!(
+        ecoli.knockout_genes(&["G_nope"]),
+        Err(Error::UnknownId { .. })
+    ));
+}
+
+#[cfg(feature = "fba")]
+#[test]
+fn gene_deletion_scans_report_objective() {
+    use rust_sbml::fba;
+
+    let ecoli = Model::parse(include_str!("../examples/EcoliCore.xml")).unwrap();
+    let wild_type = fba::optimize(&ecoli).unwrap().objective_value;
+    let genes = ["G_b2465", "G_b2935", "G_b4025", "G_b1779"];
+    let single = fba::single_gene_deletion(&ecoli, Some(&genes)).unwrap();
+    assert_eq!(single.len(), 4);
+    assert!((single["G_b2465"].unwrap() - wild_type).abs() < 1e-6);
+    assert!(single["G_b1779"].unwrap().abs() < 1e-6);
+    let pgi = fba::optimize(&ecoli.knockout_genes(&["G_b4025"]).unwrap())
+        .unwrap()
+        .objective_value;
+    assert!((single["G_b4025"].unwrap() - pgi).abs() < 1e-6);
+    assert!(pgi < wild_type);
+
+    let double = fba::double_gene_deletion(&ecoli, Some(&genes)).unwrap();
+    assert_eq!(double.len(), 6);
+    for ((a, b), value) in double.iter() {
+        assert!(a < b);
+        let value = value.unwrap_or(0.);
+        assert!(value <= single[a].unwrap() + 1e-6 && value <= single[b].unwrap() + 1e-6);
+    }
+    // without any transketolase there is no growth
+    let tkt = double[&("G_b2465".to_string(), "G_b2935".to_string())];
+    assert!(tkt.unwrap().abs() < 1e-6);
+}
+
+#[test]
+fn formulas_round_trip() {
+    for formula in [
+        "k1 * S1 / (Km + S1)",
+        "a + b + c",
+        "a - b - c",
+        "a - (b - c)",
+        "a * (b * c)",
+        "(a + b) * c / d",
+        "-x^2",
+        "(-x)^2",
+        "x^-2",
+        "2^3^4",
+        "(2^3)^4",
+        "-(a + b)",
+        "a <= b < c",
+        "x > 0 && time < 5 || !(y == 1)",
+        "piecewise(1, x > 0, 2.5)",
+        "lambda(x, y, x * y)",
+        "f(x, 1e-3) + sqrt(x) + log10(y)",
+        "3 mole * avogadro",
+    ] {
+        let math = Math::from_formula(formula).unwrap();
+        assert_eq!(math.to_formula(), formula);
+        let xml = quick_xml::se::to_string(&math).unwrap();
+        let read: Math = quick_xml::de::from_str(&xml).unwrap();
+        assert_eq!(read, math, "{}", xml);
+    }
+
+    let math = Math::from_formula(" asin(x)*(2 +y) ").unwrap();
+    assert_eq!(math.to_formula(), "arcsin(x) * (2 + y)");
+
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfInitialAssignments>
+            <initialAssignment symbol="x">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <apply><times/>
+                        <csymbol encoding="text"
+                            definitionURL="http://www.sbml.org/sbml/symbols/time"> t </csymbol>
+                        <apply><power/><cn> 10 </cn><cn> -23 </cn></apply>
+                        <apply><minus/><ci> a </ci><apply><plus/><ci> b </ci><cn> 1 </cn></apply></apply>
+                    </apply>
+                </math>
+            </initialAssignment>
+        </listOfInitialAssignments></model>"#,
+    );
+    let model = Model::parse(&example).unwrap();
+    let math = model.initial_assignments["x"].math.as_ref().unwrap();
+    assert_eq!(math.to_formula(), "time * 10^-23 * (a - (b + 1))");
+
+    for (formula, position) in [
+        ("a +", 3),
+        ("a $ b", 2),
+        ("(a", 2),
+        ("f(a,)", 4),
+        ("a b", 2),
+        ("1.2.3 + 1", 3),
+        ("1e + 2", 2),
+        ("2.5E-x", 5),
+    ] {
+        assert_eq!(
+            Math::from_formula(formula).unwrap_err().position,
+            position,
+            "{}",
+            formula
+        );
+    }
+    let math = Math::from_formula("1.5e-3 + .5 + 5.").unwrap();
+    assert_eq!(math.evaluate(&|_| None), Ok(5.5015));
+}
+
+#[test]
+fn mathml_constructs_round_trip() {
+    let xml = r#"<math xmlns="http://www.w3.org/1998/Math/MathML">
+        <semantics definitionURL="http://example.org/rate">
+            <apply><plus/>
+                <apply><root/><degree><cn type="integer"> 3 </cn></degree><cn> 27 </cn></apply>
+                <apply><log/><logbase><cn type="integer"> 2 </cn></logbase><cn> 8 </cn></apply>
+                <cn type="e-notation"> 1.5 <sep/> 2 </cn>
+                <cn type="rational"> 1 <sep/> 4 </cn>
+                <piecewise>
+                    <piece><pi/><false/></piece>
+                    <piece><exponentiale/><true/></piece>
+                    <otherwise><infinity/></otherwise>
+                </piecewise>
+            </apply>
+            <annotation encoding="text/plain"> v = ... </annotation>
+        </semantics>
+    </math>"#;
+    let math: Math = quick_xml::de::from_str(xml).unwrap();
+    let written = quick_xml::se::to_string(&math).unwrap();
+    assert!(written.contains(r#"<cn type="e-notation">1.5<sep/>2</cn>"#));
+    assert!(written.contains(r#"<annotation encoding="text/plain">v = ...</annotation>"#));
+    let read: Math = quick_xml::de::from_str(&written).unwrap();
+    assert_eq!(read, math);
+
+    let value = math.evaluate(&|_| None).unwrap();
+    assert!((value - (3. + 3. + 150. + 0.25 + std::f64::consts::E)).abs() < 1e-9);
+    assert_eq!(
+        math.to_formula(),
+        "root(3, 27) + log(2, 8) + 1.5e2 + (1/4) + piecewise(pi, false, exponentiale, true, INF)"
+    );
+    let parsed = Math::from_formula("root(3, x) * log(2, 8) + 1.5e2 + INF").unwrap();
+    assert_eq!(parsed.to_formula(), "root(3, x) * log(2, 8) + 1.5e2 + INF");
+    assert_eq!(
+        parsed.evaluate(&|id| if id == "x" { Some(8.) } else { None }),
+        Ok(f64::INFINITY)
+    );
+}
+
+#[test]
+fn derivatives_match_finite_differences() {
+    let env = |x: f64| {
+        move |id: &str| match id {
+            "x" => Some(x),
+            "k" => Some(0.7),
+            mathml::TIME_URL => Some(2.),
+            _ => None,
+        }
+    };
+    for formula in [
+        "k * x^3 - 2 * x + 1",
+        "k * x / (k + x)",
+        "x^k",
+        "k^x",
+        "x^x",
+        "sqrt(x) + root(3, x) + log(2, x) + log10(x) + ln(x)",
+        "exp(-k * x) * sin(x) / cos(x) + tan(x) - abs(x - 2)",
+        "arcsin(x / 2) + arccos(x / 3) + arctan(x) + sinh(x) * cosh(x) * tanh(x)",
+        "piecewise(x^2, x > 1, -x)",
+        "x * time + time^2",
+    ] {
+        let math = Math::from_formula(formula).unwrap();
+        let derivative = math.content.derivative("x");
+        for x in [0.3, 1.2] {
+            let h = 1e-6;
+            let numeric = (math.evaluate(&env(x + h)).unwrap()
+                - math.evaluate(&env(x - h)).unwrap())
+                / (2. * h);
+            let symbolic = derivative.evaluate(&env(x)).unwrap();
+            assert!(
+                (numeric - symbolic).abs() < 1e-5 * (1. + numeric.abs()),
+                "{} at {}: {} != {}",
+                formula,
+                x,
+                derivative.to_formula(),
+                numeric
+            );
+        }
+    }
+
+    let derivative = |formula: &str, var: &str| {
+        Math::from_formula(formula)
+            .unwrap()
+            .content
+            .derivative(var)
+            .to_formula()
+    };
+    assert_eq!(derivative("x^2 + 3 * x + k", "x"), "2 * x + 3");
+    assert_eq!(derivative("sin(2 * x)", "x"), "2 * cos(2 * x)");
+    assert_eq!(derivative("k * x", "y"), "0");
+    assert_eq!(derivative("x * time", mathml::TIME_URL), "x");
+    assert_eq!(derivative("f(x)", "x"), "diff(x, f(x))");
+}
+
+#[test]
+fn expressions_are_simplified() {
+    for (formula, simplified) in [
+        ("x * 1 + 0", "x"),
+        ("(a + (b + 2)) + 3", "a + b + 5"),
+        ("x - 1 + 3", "x + 2"),
+        ("0 - x", "-x"),
+        ("x^1 + y^0 + z / 1", "x + z + 1"),
+        ("0 * x + y * (2 * (3 * z))", "0 * x + 6 * y * z"),
+        ("0 / x + 2^3", "0 / x + 8"),
+        ("0^-1 + (-8)^0.5", "0^-1 + (-8)^0.5"),
+        ("INF - INF", "INF - INF"),
+        ("NaN - NaN", "NaN - NaN"),
+        ("x - x", "x - x"),
+        ("sin(0) + 2 * 3 - exp(0)", "5"),
+        ("--x", "x"),
+        ("x > 1 && 2 > 1", "x > 1"),
+        ("x > 1 || !(1 < 2)", "x > 1"),
+        ("piecewise(1, 2 > 3, x, true, 0)", "x"),
+        ("piecewise(1, x > 3, 2, 1 < 2, 5)", "piecewise(1, x > 3, 2)"),
+        ("lambda(a, a * 1)", "lambda(a, a)"),
+    ] {
+        let math = Math::from_formula(formula).unwrap();
+        assert_eq!(
+            math.content.simplify().to_formula(),
+            simplified,
+            "{}",
+            formula
+        );
+    }
+
+    let example = include_str!("test_simple.xml").replace(
+        "</model>",
+        r#"<listOfFunctionDefinitions>
+            <functionDefinition id="mm">
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                    <lambda>
+                        <bvar><ci> v </ci></bvar>
+                        <bvar><ci> km </ci></bvar>
+                        <bvar><ci> s </ci></bvar>
+                        <apply><divide/>
+                            <apply><times/><ci> v </ci><ci> s </ci></apply>
+                            <apply><plus/><ci> km </ci><ci> s </ci></apply>
+                        </apply>
+                    </lambda>
+                </math>
+            </functionDefinition>
+        </listOfFunctionDefinitions>
+        <listOfReactions>
+            <reaction id="R1" reversible="false">
+                <kineticLaw>
+                    <math xmlns="http://www.w3.org/1998/Math/MathML">
+                        <apply><times/>
+                            <cn> 1 </cn>
+                            <apply><ci> mm </ci><ci> Vmax </ci><ci> Km </ci><ci> S </ci></apply>
+                        </apply>
+                    </math>
+                    <listOfLocalParameters>
+                        <localParameter id="Vmax" value="10"/>
+                        <localParameter id="Km" value="0.5"/>
+                    </listOfLocalParameters>
+                </kineticLaw>
+            </reaction>
+        </listOfReactions></model>"#,
+    );
+    let model = Model::parse(&example).unwrap();
+    let kinetic_law = model.reactions["R1"].kinetic_law.as_ref().unwrap();
+    assert_eq!(
+        kinetic_law
+            .simplified_math(&model.function_definitions)
+            .to_formula(),
+        "10 * S / (S + 0.5)"
+    );
+}
+
+/// SBML document of a model in a compartment `c` of size 2, with the
+/// species in concentration and the `reactions` given as
+/// `(id, reactants, products, rate)`; repeated species are stoichiometry.
+#[cfg(feature = "simulation")]
+fn kinetic_document(species: &[(&str, f64)], reactions: &[(&str, &str, &str, &str)]) -> String {
+    let math =
+        |formula: &str| quick_xml::se::to_string(&Math::from_formula(formula).unwrap()).unwrap();
+    let references = |ids: &str| {
+        ids.split_whitespace()
+            .map(|id| {
+                format!(
+                    r#"<speciesReference species="{}" stoichiometry="1" constant="true"/>"#,
+                    id
+                )
+            })
+            .collect::<String>()
+    };
+    let species: String = species
+        .iter()
+        .map(|(id, concentration)| {
+            format!(
+                r#"<species id="{}" compartment="c" initialConcentration="{}"
+                    hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>"#,
+                id, concentration
+            )
+        })
+        .collect();
+    let reactions: String = reactions
+        .iter()
+        .map(|(id, reactants, products, rate)| {
+            format!(
+                r#"<reaction id="{}" reversible="false">
+                    <listOfReactants>{}</listOfReactants>
+                    <listOfProducts>{}</listOfProducts>
+                    <kineticLaw>{}</kineticLaw>
+                </reaction>"#,
+                id,
+                references(reactants),
+                references(products),
+                math(rate)
+            )
+        })
+        .collect();
+    include_str!("test_simple.xml").replace(
+        "</model>",
+        &format!(
+            r#"<listOfCompartments>
+                <compartment id="c" size="2" constant="true"/>
+            </listOfCompartments>
+            <listOfSpecies>{}</listOfSpecies>
+            <listOfReactions>{}</listOfReactions></model>"#,
+            species, reactions
+        ),
+    )
+}
+
+#[cfg(feature = "simulation")]
+#[test]
+fn simulation_matches_analytic_solution() {
+    use rust_sbml::simulation::{simulate, Method, SimulationOptions};
+
+    let math =
+        |formula: &str| quick_xml::se::to_string(&Math::from_formula(formula).unwrap()).unwrap();
+    // dA/dt = -k A, with k = 0.5 from an initial assignment; p = 2 A and
+    // dx/dt = p, so x = 4 (1 - exp(-t / 2))
+    let document = kinetic_document(&[("A", 1.), ("B", 0.)], &[("R1", "A", "B", "k * A * c")])
+        .replace(
+            "</model>",
+            &format!(
+                r#"<listOfParameters>
+                <parameter id="k" value="3" constant="true"/>
+                <parameter id="half" value="0.25" constant="true"/>
+                <parameter id="p" constant="false"/>
+                <parameter id="x" value="0" constant="false"/>
+            </listOfParameters>
+            <listOfInitialAssignments>
+                <initialAssignment symbol="k">{}</initialAssignment>
+            </listOfInitialAssignments>
+            <listOfRules>
+                <assignmentRule variable="p">{}</assignmentRule>
+                <rateRule variable="x">{}</rateRule>
+            </listOfRules></model>"#,
+                math("2 * half"),
+                math("2 * A"),
+                math("p")
+            ),
+        );
+    let model = Model::parse(&document).unwrap();
+    assert_eq!(model.rules.len(), 2);
+    for (method, tolerance) in [(Method::Rk45, 1e-5), (Method::Bdf, 1e-3)] {
+        let options = SimulationOptions {
+            method,
+            ..Default::default()
+        };
+        let course = simulate(&model, 5., 21, &options).unwrap();
+        assert_eq!(course.time.len(), 21);
+        for (i, &t) in course.time.iter().enumerate() {
+            let a = (-t / 2.).exp();
+            assert!(
+                (course.values["A"][i] - a).abs() < tolerance,
+                "{:?} {}",
+                method,
+                t
+            );
+            assert!((course.values["B"][i] - (1. - a)).abs() < tolerance);
+            assert!((course.values["p"][i] - 2. * a).abs() < tolerance);
+            assert!((course.values["x"][i] - 4. * (1. - a)).abs() < 4. * tolerance);
+        }
+        assert!(!course.values.contains_key("k"));
+    }
+}
+
+#[cfg(feature = "simulation")]
+#[test]
+fn stiff_system_is_simulated_with_bdf() {
+    use rust_sbml::simulation::{simulate, Method, SimulationOptions};
+
+    // Robertson's chemical kinetics, in a compartment of size 2
+    let model = Model::parse(&kinetic_document(
+        &[("A", 1.), ("B", 0.), ("C", 0.)],
+        &[
+            ("R1", "A", "B", "0.04 * A * c"),
+            ("R2", "B", "A", "1e4 * B * C * c"),
+            ("R3", "B B", "B C", "3e7 * B^2 * c"),
+        ],
+    ))
+    .unwrap();
+    let options = SimulationOptions {
+        method: Method::Bdf,
+        absolute_tolerance: 1e-12,
+        ..Default::default()
+    };
+    let course = simulate(&model, 40., 2, &options).unwrap();
+    let end = |id: &str| course.values[id][1];
+    assert!((end("A") - 0.715_827_1).abs() < 1e-3);
+    assert!((end("B") - 9.185_535e-6).abs() < 1e-7);
+    assert!((end("C") - 0.284_163_7).abs() < 1e-3);
+}
+
+#[cfg(feature = "simulation")]
+#[test]
+fn unsupported_models_are_not_simulated() {
+    use rust_sbml::simulation::{simulate, OdeSystem, SimulationError, SimulationOptions};
+
+    let ecoli = Model::parse(include_str!("EcoliCore.xml")).unwrap();
+    assert!(matches!(
+        OdeSystem::new(&ecoli).unwrap_err(),
+        SimulationError::MissingKineticLaw(_)
+    ));
+
+    let math =
+        |formula: &str| quick_xml::se::to_string(&Math::from_formula(formula).unwrap()).unwrap();
+    let document = kinetic_document(&[("A", 1.)], &[]).replace(
+        "</model>",
+        &format!(
+            r#"<listOfParameters>
+                <parameter id="p" constant="false"/>
+                <parameter id="q" constant="false"/>
+            </listOfParameters>
+            <listOfRules>
+                <assignmentRule variable="p">{}</assignmentRule>
+                <assignmentRule variable="q">{}</assignmentRule>
+            </listOfRules></model>"#,
+            math("q + 1"),
+            math("2 * p")
+        ),
+    );
+    let model = Model::parse(&document).unwrap();
+    assert!(matches!(
+        OdeSystem::new(&model).unwrap_err(),
+        SimulationError::AlgebraicLoop(_)
+    ));
+    assert_eq!(
+        simulate(&model, 0., 10, &SimulationOptions::default()).unwrap_err(),
+        SimulationError::BadTimeSpan
+    );
+
+    // recursive function definitions are not inlined forever
+    let document = kinetic_document(&[("A", 1.)], &[("R1", "A", "", "f(A)")]).replace(
+        "</model>",
+        &format!(
+            r#"<listOfFunctionDefinitions>
+                <functionDefinition id="f">{}</functionDefinition>
+            </listOfFunctionDefinitions></model>"#,
+            math("lambda(x, f(x))")
+        ),
+    );
+    let model = Model::parse(&document).unwrap();
+    assert_eq!(
+        simulate(&model, 1., 10, &SimulationOptions::default()).unwrap_err(),
+        SimulationError::RecursiveFunction("f".to_string())
+    );
+
+    // compartments without size are not given a made-up volume
+    let document =
+        kinetic_document(&[("A", 1.)], &[("R1", "A", "", "A")]).replace(r#"size="2" "#, "");
+    let model = Model::parse(&document).unwrap();
+    assert_eq!(
+        OdeSystem::new(&model).unwrap_err(),
+        SimulationError::MissingValue("c".to_string())
+    );
+    let document = document.replace(
+        "</model>",
+        &format!(
+            r#"<listOfInitialAssignments>
+                <initialAssignment symbol="c">{}</initialAssignment>
+            </listOfInitialAssignments></model>"#,
+            math("3")
+        ),
+    );
+    assert!(OdeSystem::new(&Model::parse(&document).unwrap()).is_ok());
+}
+
+#[cfg(feature = "simulation")]
+#[test]
+fn stochastic_simulation_averages_to_the_deterministic_decay() {
+    use rust_sbml::simulation::{simulate_stochastic, StochasticOptions, Xoshiro256};
+
+    // A -> B with propensity 0.1 A, in molecules
+    let document = kinetic_document(&[("A", 1000.), ("B", 0.)], &[("R1", "A", "B", "0.1 * A")])
+        .replace(r#"initialConcentration"#, r#"initialAmount"#)
+        .replace(
+            r#"hasOnlySubstanceUnits="false""#,
+            r#"hasOnlySubstanceUnits="true""#,
+        );
+    let model = Model::parse(&document).unwrap();
+    let run = |options: &StochasticOptions, seed: u64| {
+        simulate_stochastic(&model, 5., 6, options, &mut Xoshiro256::seed_from_u64(seed)).unwrap()
+    };
+    let exact = StochasticOptions::default();
+    let leaping = StochasticOptions {
+        tau_leaping: Some(0.03),
+        ..Default::default()
+    };
+    assert_eq!(run(&exact, 7), run(&exact, 7));
+    assert_ne!(run(&exact, 7), run(&exact, 8));
+
+    for options in [exact, leaping] {
+        let runs = 200;
+        let mut mean = [0.; 6];
+        for seed in 0..runs {
+            let course = run(&options, seed);
+            let (a, b) = (&course.values["A"], &course.values["B"]);
+            assert_eq!(a[0], 1000.);
+            for i in 0..6 {
+                assert_eq!(a[i] + b[i], 1000.);
+                assert!(a[i] >= 0. && a[i].fract() == 0.);
+                mean[i] += a[i] / runs as f64;
+            }
+        }
+        for (i, &t) in [0., 1., 2., 3., 4., 5.].iter().enumerate() {
+            let expected = 1000. * (-0.1f64 * t).exp();
+            assert!(
+                (mean[i] - expected).abs() < 0.01 * expected,
+                "{:?} {} {}",
+                options,
+                mean[i],
+                expected
+            );
+        }
+    }
+}
+
+#[cfg(feature = "simulation")]
+#[test]
+fn steady_states_report_conservation_and_stability() {
+    use rust_sbml::simulation::{steady_state, Stability};
+
+    // A <-> B, with A + B conserved
+    let model = Model::parse(&kinetic_document(
+        &[("A", 1.), ("B", 0.)],
+        &[
+            ("forward", "A", "B", "2 * A * c"),
+            ("backward", "B", "A", "B * c"),
+        ],
+    ))
+    .unwrap();
+    let steady = steady_state(&model).unwrap();
+    assert!((steady.values["A"] - 1. / 3.).abs() < 1e-8);
+    assert!((steady.values["B"] - 2. / 3.).abs() < 1e-8);
+    assert!((steady.fluxes["forward"] - steady.fluxes["backward"]).abs() < 1e-8);
+    assert!(!steady.integrated);
+    assert_eq!(steady.stability, Stability::Stable);
+    assert_eq!(steady.eigenvalues.len(), 1);
+    assert!((steady.eigenvalues[0].0 + 3.).abs() < 1e-5);
+    assert_eq!(steady.conserved_moieties.len(), 1);
+    let moiety = &steady.conserved_moieties[0];
+    let mut coefficients = moiety.coefficients.clone();
+    coefficients.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        coefficients,
+        vec![("A".to_string(), 1.), ("B".to_string(), 1.)]
+    );
+    // amounts in a compartment of size 2
+    assert_eq!(moiety.total, 2.);
+
+    // dX/dt = X - 1 has an unstable steady state at 1
+    let model = Model::parse(&kinetic_document(
+        &[("X", 0.5)],
+        &[("growth", "", "X", "2 * X"), ("drain", "X", "", "2")],
+    ))
+    .unwrap();
+    let steady = steady_state(&model).unwrap();
+    assert!((steady.values["X"] - 1.).abs() < 1e-8);
+    assert_eq!(steady.stability, Stability::Unstable);
+    assert!(steady.conserved_moieties.is_empty());
+
+    // dX/dt = 1 - X^2 has a singular Jacobian at X = 0, so the Newton
+    // iterations start after integrating towards X = 1
+    let model = Model::parse(&kinetic_document(
+        &[("X", 0.)],
+        &[
+            ("synthesis", "", "X", "2"),
+            ("degradation", "X", "", "2 * X^2"),
+        ],
+    ))
+    .unwrap();
+    let steady = steady_state(&model).unwrap();
+    assert!((steady.values["X"] - 1.).abs() < 1e-8);
+    assert!(steady.integrated);
+    assert_eq!(steady.stability, Stability::Stable);
+}
+
+#[test]
+fn flux_units_are_converted_to_si() {
+    let file_str = include_str!("EcoliCore.xml");
+    let raw_model = ModelRaw::parse(file_str).unwrap();
+    let flux_unit = raw_model
+        .list_of_unit_definitions
+        .unit_definitions
+        .iter()
+        .find(|def| def.id.as_deref() == Some("mmol_per_gDW_per_hr"))
+        .unwrap();
+    let mol = UnitDefinition::from(UnitSId::mole);
+    let kg = UnitDefinition::from(UnitSId::kilogram);
+    let s = UnitDefinition::from(UnitSId::second);
+    let si_flux = &mol / &(kg * s.clone());
+
+    assert!(flux_unit.is_compatible(&si_flux).unwrap());
+    assert!(!flux_unit.is_equivalent(&si_flux).unwrap());
+    assert!(!flux_unit.is_compatible(&mol).unwrap());
+    assert!((unit::convert(3600., flux_unit, &si_flux).unwrap() - 1.).abs() < 1e-12);
+    assert!((unit::convert(1., &si_flux, flux_unit).unwrap() - 3600.).abs() < 1e-9);
+    assert!(matches!(
+        unit::convert(1., flux_unit, &mol),
+        Err(UnitError::Incompatible(_, _))
+    ));
+
+    let si = flux_unit.to_si().unwrap();
+    assert_eq!(si.exponents[&UnitSId::mole], 1.);
+    assert_eq!(si.exponents[&UnitSId::kilogram], -1.);
+    assert_eq!(si.exponents[&UnitSId::second], -1.);
+
+    // the per-hour flux times an hour is an amount per dry weight
+    let hour = UnitDefinition {
+        list_of_units: unit::ListOfUnits {
+            units: vec![Unit {
+                multiplier: 3600.,
+                ..Unit::from(UnitSId::second)
+            }],
+        },
+        ..Default::default()
+    };
+    let content = flux_unit * &hour;
+    assert!(content
+        .is_compatible(&(&mol / &UnitDefinition::from(UnitSId::gram)))
+        .unwrap());
+    // a litre is a cubic decimetre
+    let decimetre = UnitDefinition {
+        list_of_units: unit::ListOfUnits {
+            units: vec![Unit {
+                scale: -1,
+                ..Unit::from(UnitSId::metre)
+            }],
+        },
+        ..Default::default()
+    };
+    assert!(decimetre
+        .pow(3.)
+        .is_equivalent(&UnitDefinition::from(UnitSId::litre))
+        .unwrap());
+    let custom = UnitDefinition {
+        list_of_units: unit::ListOfUnits {
+            units: vec![Unit {
+                kind: UnitSIdRef::CustomUnit("gDW".to_string()),
+                exponent: 1.,
+                scale: 0,
+                multiplier: 1.,
+            }],
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        custom.to_si(),
+        Err(UnitError::UnknownUnit("gDW".to_string()))
+    );
+}
+
+#[test]
+fn unit_inconsistencies_are_reported() {
+    let model = Model::parse(
+        r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2"
+        xmlns:sbml="http://www.sbml.org/sbml/level3/version2/core">
+        <model substanceUnits="mole" timeUnits="second" extentUnits="mole">
+          <listOfFunctionDefinitions>
+            <functionDefinition id="identity">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <lambda><bvar><ci> x </ci></bvar><ci> x </ci></lambda>
+              </math>
+            </functionDefinition>
+          </listOfFunctionDefinitions>
+          <listOfUnitDefinitions>
+            <unitDefinition id="per_second">
+              <listOfUnits>
+                <unit kind="second" exponent="-1" scale="0" multiplier="1"/>
+              </listOfUnits>
+            </unitDefinition>
+          </listOfUnitDefinitions>
+          <listOfCompartments>
+            <compartment id="c" size="1" units="litre" constant="true"/>
+          </listOfCompartments>
+          <listOfSpecies>
+            <species id="S" compartment="c" initialConcentration="1"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+          </listOfSpecies>
+          <listOfParameters>
+            <parameter id="k" value="1" units="per_second" constant="true"/>
+            <parameter id="total" value="1" units="mole" constant="false"/>
+            <parameter id="scale" value="1" constant="true"/>
+            <parameter id="distance" value="1" units="furlong" constant="true"/>
+          </listOfParameters>
+          <listOfInitialAssignments>
+            <initialAssignment symbol="total">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <apply><plus/><cn sbml:units="mole"> 2 </cn><cn> 1 </cn></apply>
+              </math>
+            </initialAssignment>
+          </listOfInitialAssignments>
+          <listOfRules>
+            <rateRule variable="total">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <apply><times/><ci> scale </ci><ci> k </ci></apply>
+              </math>
+            </rateRule>
+            <algebraicRule>
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <apply><minus/><ci> total </ci><ci> distance </ci></apply>
+              </math>
+            </algebraicRule>
+          </listOfRules>
+          <listOfReactions>
+            <reaction id="balanced" reversible="false">
+              <kineticLaw>
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                  <apply><times/>
+                    <ci> k </ci>
+                    <apply><ci> identity </ci><ci> S </ci></apply>
+                    <apply><power/><ci> c </ci><cn> 1 </cn></apply>
+                  </apply>
+                </math>
+              </kineticLaw>
+            </reaction>
+            <reaction id="concentration_rate" reversible="false">
+              <kineticLaw>
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                  <apply><times/><ci> k </ci><ci> S </ci></apply>
+                </math>
+              </kineticLaw>
+            </reaction>
+            <reaction id="mixed" reversible="false">
+              <kineticLaw>
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                  <apply><plus/>
+                    <apply><times/><ci> k </ci><ci> total </ci></apply>
+                    <apply><exp/><ci> S </ci></apply>
+                  </apply>
+                </math>
+              </kineticLaw>
+            </reaction>
+          </listOfReactions>
+        </model>
+        </sbml>"#,
+    )
+    .unwrap();
+    let issues = unit::check_consistency(&model);
+    let problems = |element: MathElement| -> Vec<UnitProblem> {
+        issues
+            .iter()
+            .filter(|issue| issue.element == element)
+            .map(|issue| issue.problem.clone())
+            .collect()
+    };
+    let si = |units: &[(UnitSId, f64)]| {
+        units
+            .iter()
+            .fold(
+                UnitDefinition::from(UnitSId::dimensionless),
+                |product, (kind, e)| product * UnitDefinition::from(kind.clone()).pow(*e),
+            )
+            .to_si()
+            .unwrap()
+    };
+    let mole_per_second = si(&[(UnitSId::mole, 1.), (UnitSId::second, -1.)]);
+
+    assert!(problems(MathElement::KineticLaw("balanced".to_string())).is_empty());
+    assert!(problems(MathElement::InitialAssignment("total".to_string())).is_empty());
+    assert_eq!(
+        problems(MathElement::KineticLaw("concentration_rate".to_string())),
+        vec![UnitProblem::Inconsistent {
+            expected: mole_per_second.clone(),
+            found: si(&[
+                (UnitSId::mole, 1.),
+                (UnitSId::litre, -1.),
+                (UnitSId::second, -1.)
+            ]),
+        }]
+    );
+    let mixed = problems(MathElement::KineticLaw("mixed".to_string()));
+    assert_eq!(
+        mixed[0],
+        UnitProblem::NotDimensionless {
+            operator: "exp".to_string(),
+            found: si(&[(UnitSId::mole, 1.), (UnitSId::litre, -1.)]),
+        }
+    );
+    assert!(matches!(&mixed[1], UnitProblem::Mismatch { operator, .. } if operator == "plus"));
+    assert_eq!(mixed.len(), 2);
+    assert_eq!(
+        problems(MathElement::RateRule("total".to_string())),
+        vec![UnitProblem::Undetermined]
+    );
+    assert_eq!(
+        problems(MathElement::AlgebraicRule(1)),
+        vec![UnitProblem::UnknownUnit("furlong".to_string())]
+    );
+    assert_eq!(issues.len(), 5);
+    assert_eq!(
+        issues[0].to_string(),
+        format!(
+            "kinetic law of `concentration_rate`: expected units `{}`, found `{}`",
+            mole_per_second,
+            si(&[
+                (UnitSId::mole, 1.),
+                (UnitSId::litre, -1.),
+                (UnitSId::second, -1.)
+            ])
+        )
+    );
+
+    // unknown units of the defined element and recursive functions
+    let model = Model::parse(
+        r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+        <model substanceUnits="mole" timeUnits="second" extentUnits="typo_units">
+          <listOfFunctionDefinitions>
+            <functionDefinition id="f">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <lambda><bvar><ci> x </ci></bvar><apply><ci> f </ci><ci> x </ci></apply></lambda>
+              </math>
+            </functionDefinition>
+          </listOfFunctionDefinitions>
+          <listOfCompartments>
+            <compartment id="c" size="1" units="litre" constant="true"/>
+          </listOfCompartments>
+          <listOfSpecies>
+            <species id="S" compartment="c" initialAmount="1"
+              hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+          </listOfSpecies>
+          <listOfReactions>
+            <reaction id="recursive" reversible="false">
+              <kineticLaw>
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                  <apply><ci> f </ci><ci> S </ci></apply>
+                </math>
+              </kineticLaw>
+            </reaction>
+            <reaction id="wrong" reversible="false">
+              <kineticLaw>
+                <math xmlns="http://www.w3.org/1998/Math/MathML">
+                  <apply><times/><ci> kl </ci><ci> c </ci><ci> S </ci></apply>
+                </math>
+                <listOfLocalParameters>
+                  <localParameter id="kl" value="1" units="furlong"/>
+                </listOfLocalParameters>
+              </kineticLaw>
+            </reaction>
+          </listOfReactions>
+        </model>
+        </sbml>"#,
+    )
+    .unwrap();
+    let issues = unit::check_consistency(&model);
+    let problems = |element: MathElement| -> Vec<UnitProblem> {
+        issues
+            .iter()
+            .filter(|issue| issue.element == element)
+            .map(|issue| issue.problem.clone())
+            .collect()
+    };
+    let typo = UnitProblem::UnknownUnit("typo_units".to_string());
+    assert_eq!(
+        problems(MathElement::KineticLaw("recursive".to_string())),
+        vec![
+            typo.clone(),
+            UnitProblem::RecursiveFunction("f".to_string()),
+            UnitProblem::Undetermined
+        ]
+    );
+    assert_eq!(
+        problems(MathElement::KineticLaw("wrong".to_string())),
+        vec![
+            typo,
+            UnitProblem::UnknownUnit("furlong".to_string()),
+            UnitProblem::Undetermined
+        ]
+    );
+}
+
+#[test]
+fn unit_definitions_keep_order_and_repeated_kinds() {
+    let example = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+    <model>
+        <listOfUnitDefinitions>
+            <unitDefinition id="per_minute_squared" metaid="m_per_minute_squared" name="Per minute squared">
+                <listOfUnits>
+                    <unit kind="second" exponent="-1" scale="0" multiplier="60"/>
+                    <unit kind="second" exponent="-1" scale="0" multiplier="60"/>
+                </listOfUnits>
+            </unitDefinition>
+            <unitDefinition id="millimolar">
+                <listOfUnits>
+                    <unit kind="mole" exponent="1" scale="-3" multiplier="1"/>
+                    <unit kind="litre" exponent="-1" scale="0" multiplier="1"/>
+                </listOfUnits>
+            </unitDefinition>
+        </listOfUnitDefinitions>
+    </model>
+</sbml>"#;
+    let model = Model::parse(example).unwrap();
+    let ids: Vec<_> = model
+        .unit_definitions
+        .iter()
+        .map(|def| def.id.as_deref().unwrap())
+        .collect();
+    assert_eq!(ids, ["per_minute_squared", "millimolar"]);
+    let per_minute_squared = model.unit_definition("per_minute_squared").unwrap();
+    assert_eq!(
+        per_minute_squared.metaid.as_deref(),
+        Some("m_per_minute_squared")
+    );
+    assert_eq!(
+        per_minute_squared.name.as_deref(),
+        Some("Per minute squared")
+    );
+    assert_eq!(per_minute_squared.list_of_units.units.len(), 2);
+    assert_eq!(
+        per_minute_squared.to_si().unwrap().exponents[&UnitSId::second],
+        -2.
+    );
+    assert!(model.unit_definition("molar").is_none());
+
+    // writing the definitions of the model recreates the original list
+    let original = ModelRaw::parse(example).unwrap();
+    let raw_model = ModelRaw {
+        list_of_unit_definitions: model.list_of_unit_definitions(),
+        ..Default::default()
+    };
+    let written = raw_model.to_string().unwrap();
+    assert!(written.contains(r#"metaid="m_per_minute_squared""#));
+    assert_eq!(
+        ModelRaw::parse(&written).unwrap().list_of_unit_definitions,
+        original.list_of_unit_definitions
+    );
+    assert_eq!(Model::parse(&written).unwrap(), model);
+}
+
+#[test]
+fn validation_reports_identifier_and_reference_errors() {
+    let ecoli = include_str!("EcoliCore.xml");
+    assert_eq!(validation::validate(&Model::parse(ecoli).unwrap()), vec![]);
+
+    let model = Model::parse(
+        r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2"
+        xmlns:fbc="http://www.sbml.org/sbml/level3/version1/fbc/version2">
+        <model id="m">
+          <listOfCompartments>
+            <compartment id="c" size="1" constant="true"/>
+          </listOfCompartments>
+          <listOfSpecies>
+            <species id="A" compartment="c" initialAmount="1"
+              hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+            <species id="B" compartment="nucleus" initialAmount="1"
+              hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+          </listOfSpecies>
+          <listOfParameters>
+            <parameter id="k" value="1" constant="true"/>
+            <parameter id="x" value="1" constant="false"/>
+            <parameter id="ub" value="10" constant="false"/>
+            <parameter id="A" value="1" constant="true"/>
+          </listOfParameters>
+          <listOfInitialAssignments>
+            <initialAssignment symbol="x">
+              <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 2 </cn></math>
+            </initialAssignment>
+            <initialAssignment symbol="y">
+              <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 2 </cn></math>
+            </initialAssignment>
+          </listOfInitialAssignments>
+          <listOfRules>
+            <assignmentRule variable="x">
+              <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 3 </cn></math>
+            </assignmentRule>
+            <rateRule variable="k">
+              <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 1 </cn></math>
+            </rateRule>
+          </listOfRules>
+          <listOfReactions>
+            <reaction id="R" reversible="false" fbc:lowerFluxBound="lb" fbc:upperFluxBound="ub">
+              <listOfReactants>
+                <speciesReference species="A" stoichiometry="1" constant="true"/>
+              </listOfReactants>
+              <listOfProducts>
+                <speciesReference species="C" stoichiometry="1" constant="true"/>
+              </listOfProducts>
+            </reaction>
+          </listOfReactions>
+        </model>
+        </sbml>"#,
+    )
+    .unwrap();
+    let diagnostics = validation::validate(&model);
+    let found: Vec<(Severity, &str, &str)> = diagnostics
+        .iter()
+        .map(|d| (d.severity, d.rule.as_str(), d.id.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (Severity::Error, "10301", "A"),
+            (Severity::Error, "20601", "B"),
+            (Severity::Error, "21111", "R"),
+            (Severity::Error, "20801", "y"),
+            (Severity::Error, "20904", "k"),
+            (Severity::Error, "20803", "x"),
+            (Severity::Error, "fbc-20705", "R"),
+            (Severity::Error, "fbc-20710", "R"),
+        ]
+    );
+    assert_eq!(
+        diagnostics[2].to_string(),
+        "error 21111 (`R`): unknown species `C`"
+    );
+}
+
+#[test]
+fn raw_validation_reports_repeated_components_and_function_calls() {
+    let raw_model = ModelRaw::parse(
+        r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+        <model id="m">
+          <listOfFunctionDefinitions>
+            <functionDefinition id="f">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <lambda><bvar><ci> a </ci></bvar><apply><ci> g </ci><ci> a </ci></apply></lambda>
+              </math>
+            </functionDefinition>
+            <functionDefinition id="g">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <lambda><bvar><ci> a </ci></bvar><apply><ci> f </ci><ci> a </ci></apply></lambda>
+              </math>
+            </functionDefinition>
+            <functionDefinition id="h">
+              <math xmlns="http://www.w3.org/1998/Math/MathML">
+                <lambda><bvar><ci> a </ci></bvar><apply><ci> u </ci><ci> a </ci></apply></lambda>
+              </math>
+            </functionDefinition>
+          </listOfFunctionDefinitions>
+          <listOfCompartments>
+            <compartment id="c" size="1" constant="true"/>
+          </listOfCompartments>
+          <listOfSpecies>
+            <species id="A" compartment="c" initialAmount="1"
+              hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+            <species id="A" compartment="c" initialAmount="2"
+              hasOnlySubstanceUnits="true" boundaryCondition="false" constant="false"/>
+          </listOfSpecies>
+          <listOfParameters>
+            <parameter id="x" value="1" constant="false"/>
+          </listOfParameters>
+          <listOfInitialAssignments>
+            <initialAssignment symbol="x">
+              <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 2 </cn></math>
+            </initialAssignment>
+            <initialAssignment symbol="x">
+              <math xmlns="http://www.w3.org/1998/Math/MathML"><cn> 3 </cn></math>
+            </initialAssignment>
+          </listOfInitialAssignments>
+        </model>
+        </sbml>"#,
+    )
+    .unwrap();
+    let diagnostics = validation::validate_raw(&raw_model);
+    let found: Vec<(&str, &str)> = diagnostics
+        .iter()
+        .map(|d| (d.rule.as_str(), d.id.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("10301", "A"),
+            ("20802", "x"),
+            ("20303", "f"),
+            ("20303", "g"),
+            ("10214", "h"),
+        ]
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    assert_eq!(
+        diagnostics[4].to_string(),
+        "error 10214 (`h`): call to the undefined function `u`"
+    );
+
+    let without_unit_id = ModelRaw::parse(
+        r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2">
+        <model id="m">
+          <listOfUnitDefinitions>
+            <unitDefinition><listOfUnits>
+              <unit kind="mole" exponent="1" scale="-3" multiplier="1"/>
+            </listOfUnits></unitDefinition>
+          </listOfUnitDefinitions>
+          <listOfParameters>
+            <parameter id="k" value="1" constant="true"/>
+            <parameter id="k" value="2" constant="true"/>
+          </listOfParameters>
+        </model>
+        </sbml>"#,
+    )
+    .unwrap();
+    let diagnostics = validation::validate_raw(&without_unit_id);
+    let found: Vec<(&str, &str)> = diagnostics
+        .iter()
+        .map(|d| (d.rule.as_str(), d.id.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("10301", "k"),
+            ("model", "model/listOfUnitDefinitions/unitDefinition"),
+        ]
+    );
+}
+
+#[test]
+fn mass_balance_reports_unbalanced_reactions() {
+    let formula: ChemicalFormula = "[Fe(CN)6]2 H2O".parse().unwrap();
+    assert_eq!(formula.elements["Fe"], 2.);
+    assert_eq!(formula.elements["C"], 12.);
+    assert_eq!(formula.elements["N"], 12.);
+    assert_eq!(formula.elements["H"], 2.);
+    assert!(formula.unknown_groups().is_empty());
+    assert_eq!(
+        "C10H16N5O13P3X"
+            .parse::<ChemicalFormula>()
+            .unwrap()
+            .unknown_groups(),
+        vec!["X"]
+    );
+    assert_eq!(
+        "C0.5H".parse::<ChemicalFormula>().unwrap().elements["C"],
+        0.5
+    );
+    assert_eq!(
+        "C6(H12O6".parse::<ChemicalFormula>().unwrap_err().position,
+        2
+    );
+    assert_eq!(
+        "C6H12(O6]".parse::<ChemicalFormula>().unwrap_err().position,
+        8
+    );
+    assert_eq!("C6-".parse::<ChemicalFormula>().unwrap_err().position, 2);
+
+    let model = Model::parse(
+        r#"<sbml xmlns="http://www.sbml.org/sbml/level3/version2/core" level="3" version="2"
+        xmlns:fbc="http://www.sbml.org/sbml/level3/version1/fbc/version2">
+        <model>
+          <listOfCompartments>
+            <compartment id="c" size="1" constant="true"/>
+          </listOfCompartments>
+          <listOfSpecies>
+            <species id="atp" compartment="c" fbc:chemicalFormula="C10H12N5O13P3" fbc:charge="-4"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+            <species id="adp" compartment="c" fbc:chemicalFormula="C10H12N5O10P2" fbc:charge="-3"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+            <species id="pi" compartment="c" fbc:chemicalFormula="HO4P" fbc:charge="-2"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+            <species id="h2o" compartment="c" fbc:chemicalFormula="H2O" fbc:charge="0"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+            <species id="h" compartment="c" fbc:chemicalFormula="H" fbc:charge="1"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+            <species id="protein" compartment="c" fbc:chemicalFormula="C3H5NOR"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+            <species id="protein_ext" compartment="c" fbc:chemicalFormula="C3H5NOR"
+              hasOnlySubstanceUnits="false" boundaryCondition="true" constant="false"/>
+            <species id="unknown" compartment="c"
+              hasOnlySubstanceUnits="false" boundaryCondition="false" constant="false"/>
+          </listOfSpecies>
+          <listOfReactions>
+            <reaction id="ATPM" reversible="false">
+              <listOfReactants>
+                <speciesReference species="atp" stoichiometry="1" constant="true"/>
+                <speciesReference species="h2o" stoichiometry="1" constant="true"/>
+              </listOfReactants>
+              <listOfProducts>
+                <speciesReference species="adp" stoichiometry="1" constant="true"/>
+                <speciesReference species="pi" stoichiometry="1" constant="true"/>
+                <speciesReference species="h" stoichiometry="1" constant="true"/>
+              </listOfProducts>
+            </reaction>
+            <reaction id="ATPM_no_proton" reversible="false">
+              <listOfReactants>
+                <speciesReference species="atp" stoichiometry="1" constant="true"/>
+                <speciesReference species="h2o" stoichiometry="1" constant="true"/>
+              </listOfReactants>
+              <listOfProducts>
+                <speciesReference species="adp" stoichiometry="1" constant="true"/>
+                <speciesReference species="pi" stoichiometry="1" constant="true"/>
+              </listOfProducts>
+            </reaction>
+            <reaction id="degradation" reversible="false">
+              <listOfReactants>
+                <speciesReference species="protein" stoichiometry="2" constant="true"/>
+              </listOfReactants>
+              <listOfProducts>
+                <speciesReference species="h2o" stoichiometry="1" constant="true"/>
+              </listOfProducts>
+            </reaction>
+            <reaction id="uptake" reversible="true">
+              <listOfReactants>
+                <speciesReference species="protein_ext" stoichiometry="1" constant="true"/>
+              </listOfReactants>
+              <listOfProducts>
+                <speciesReference species="protein" stoichiometry="1" constant="true"/>
+              </listOfProducts>
+            </reaction>
+            <reaction id="DM_atp" reversible="false" sboTerm="SBO:0000628">
+              <listOfReactants>
+                <speciesReference species="atp" stoichiometry="1" constant="true"/>
+              </listOfReactants>
+            </reaction>
+            <reaction id="mystery" reversible="false">
+              <listOfReactants>
+                <speciesReference species="unknown" stoichiometry="1" constant="true"/>
+              </listOfReactants>
+            </reaction>
+          </listOfReactions>
+        </model>
+        </sbml>"#,
+    )
+    .unwrap();
+    let balance = model.reactions["ATPM"].mass_balance(&model).unwrap();
+    assert!(balance.is_balanced());
+    assert_eq!(balance.charge, Some(0.));
+    let balance = model.reactions["ATPM_no_proton"]
+        .mass_balance(&model)
+        .unwrap();
+    assert_eq!(
+        balance.elements.into_iter().collect::<Vec<_>>(),
+        vec![("H".to_string(), -1.)]
+    );
+    assert_eq!(balance.charge, Some(-1.));
+    let balance = model.reactions["degradation"].mass_balance(&model).unwrap();
+    assert_eq!(balance.elements["R"], -2.);
+    assert_eq!(balance.elements["C"], -6.);
+    assert_eq!(balance.charge, None);
+
+    let report = model.mass_balance_report();
+    assert_eq!(report.skipped, vec!["DM_atp", "uptake"]);
+    assert_eq!(
+        report.unbalanced.keys().collect::<Vec<_>>(),
+        vec!["ATPM_no_proton", "degradation"]
+    );
+    assert_eq!(
+        report.unchecked["mystery"],
+        MassBalanceError::MissingFormula("unknown".to_string())
+    );
+
+    let ecoli = Model::parse(include_str!("EcoliCore.xml")).unwrap();
+    let report = ecoli.mass_balance_report();
+    assert_eq!(report.skipped.len(), 20);
+    assert!(report.unchecked.is_empty());
+    assert_eq!(
+        report.unbalanced.keys().collect::<Vec<_>>(),
+        vec!["R_BIOMASS_Ecoli_core_w_GAM"]
+    );
+}
@@ -185,3 +185,27 @@ def test_benchmark_rust_sbml_big(benchmark):
 @benchmark
 def test_benchmark_libsbml_big(benchmark):
     benchmark(cobra.io.read_sbml_model, "tests_integration/RECON1.xml")
+
+
+def test_stoichiometric_matrix():
+    sparse = pytest.importorskip("scipy.sparse")
+    model = rust_sbml.Model("examples/EcoliCore.xml")
+    csc, shape, species, reactions = model.getStoichiometricMatrix()
+    s = sparse.csc_matrix(csc, shape=shape)
+    cobra_model = cobra.io.read_sbml_model("examples/EcoliCore.xml")
+    for j, rid in enumerate(reactions):
+        cobra_reaction = cobra_model.reactions.get_by_id(rid[2:])
+        for met, coefficient in cobra_reaction.metabolites.items():
+            assert s[species.index(f"M_{met.id}"), j] == coefficient
+
+
+def test_flux_variability():
+    from cobra.flux_analysis import flux_variability_analysis
+
+    model = rust_sbml.Model("examples/EcoliCore.xml")
+    ranges = model.fluxVariability(fraction_of_optimum=0.9)
+    cobra_model = cobra.io.read_sbml_model("examples/EcoliCore.xml")
+    expected = flux_variability_analysis(cobra_model, fraction_of_optimum=0.9)
+    for rid, (minimum, maximum) in ranges.items():
+        assert round(minimum, 4) == round(expected.loc[rid[2:], "minimum"], 4)
+        assert round(maximum, 4) == round(expected.loc[rid[2:], "maximum"], 4)